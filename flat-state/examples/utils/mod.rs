@@ -61,9 +61,9 @@ pub fn print_state_info(state: &FlatState) {
         state.data.contracts_code.len()
     );
 
-    if state.data.accounts.len() > 0 {
+    if !state.data.accounts.is_empty() {
         let account_id = state.data.accounts.keys().next().unwrap();
         println!("First Account: {}", account_id);
-        print_account_info(&state, account_id);
+        print_account_info(state, account_id);
     }
 }
@@ -68,7 +68,7 @@ impl FlatStateData {
             } => {
                 self.data
                     .entry(account_id)
-                    .or_insert_with(HashMap::new)
+                    .or_default()
                     .insert(key.into(), value.into());
             }
 
@@ -80,62 +80,49 @@ impl FlatStateData {
                 let access_key: AccessKey = access_key.into();
                 let is_full_access =
                     matches!(access_key.permission, AccessKeyPermission::FullAccess);
-                match &public_key {
-                    PublicKey::ED25519(pk) => {
-                        if let Some(ak) = self.accounts.get_mut(&account_id) {
-                            if let Some((key, nonce)) = ak.ed_full_key.take() {
-                                if &key.0 == pk {
-                                    if is_full_access {
-                                        ak.ed_full_key = Some((key, access_key.nonce));
-                                        return;
-                                    } else {
-                                        ak.ed_full_key = None;
-                                    }
-                                } else {
-                                    ak.ed_full_key = Some((key, nonce));
-                                }
-                            } else {
+                if let PublicKey::ED25519(pk) = &public_key {
+                    if let Some(ak) = self.accounts.get_mut(&account_id) {
+                        if let Some((key, nonce)) = ak.ed_full_key.take() {
+                            if &key.0 == pk {
                                 if is_full_access {
-                                    ak.ed_full_key = Some((
-                                        BorshifiedED25519PublicKey(pk.clone()),
-                                        access_key.nonce,
-                                    ));
+                                    ak.ed_full_key = Some((key, access_key.nonce));
                                     return;
+                                } else {
+                                    ak.ed_full_key = None;
                                 }
+                            } else {
+                                ak.ed_full_key = Some((key, nonce));
                             }
+                        } else if is_full_access {
+                            ak.ed_full_key =
+                                Some((BorshifiedED25519PublicKey(pk.clone()), access_key.nonce));
+                            return;
                         }
                     }
-                    _ => {}
-                };
+                }
                 self.access_keys
                     .entry(account_id)
-                    .or_insert_with(HashMap::new)
+                    .or_default()
                     .insert(public_key, access_key);
             }
             StateChangeValueView::AccessKeyDeletion {
                 account_id,
                 public_key,
             } => {
-                match &public_key {
-                    PublicKey::ED25519(pk) => {
-                        if let Some(ak) = self.accounts.get_mut(&account_id) {
-                            if let Some(pair) = ak.ed_full_key.take() {
-                                if &pair.0 .0 == pk {
-                                    // Already removed
-                                    return;
-                                } else {
-                                    ak.ed_full_key = Some(pair);
-                                }
+                if let PublicKey::ED25519(pk) = &public_key {
+                    if let Some(ak) = self.accounts.get_mut(&account_id) {
+                        if let Some(pair) = ak.ed_full_key.take() {
+                            if &pair.0 .0 == pk {
+                                // Already removed
+                                return;
+                            } else {
+                                ak.ed_full_key = Some(pair);
                             }
                         }
                     }
-                    _ => {}
-                };
+                }
                 let is_empty = {
-                    let entry = self
-                        .access_keys
-                        .entry(account_id.clone())
-                        .or_insert_with(HashMap::new);
+                    let entry = self.access_keys.entry(account_id.clone()).or_default();
                     entry.remove(&public_key);
                     entry.is_empty()
                 };
@@ -145,10 +132,7 @@ impl FlatStateData {
             }
             StateChangeValueView::DataDeletion { account_id, key } => {
                 let is_empty = {
-                    let entry = self
-                        .data
-                        .entry(account_id.clone())
-                        .or_insert_with(HashMap::new);
+                    let entry = self.data.entry(account_id.clone()).or_default();
                     let key: Vec<u8> = key.into();
                     entry.remove(&key);
                     entry.is_empty()
@@ -1,6 +1,7 @@
 use crate::data::FlatStateData;
 use crate::filter::FlatStateFilter;
 use crate::state::*;
+use fastnear_neardata_fetcher::chains::ChainRegistry;
 use fastnear_neardata_fetcher::fetcher::{fetch_block_by_height, new_reqwest_client};
 use fastnear_primitives::near_primitives::block_header::BlockHeader;
 use fastnear_primitives::near_primitives::state_record::{state_record_to_account_id, StateRecord};
@@ -19,7 +20,18 @@ const RECORDS_FILE: &str = "records.json";
 const DEFAULT_FETCHER_TIMEOUT: Duration = Duration::from_secs(10);
 
 impl FlatState {
+    /// Loads the state from a state dump of mainnet or testnet.
     pub async fn from_state_dump(filter: FlatStateFilter, path: &str) -> FlatStateResult<Self> {
+        Self::from_state_dump_with_chains(filter, path, &ChainRegistry::default()).await
+    }
+
+    /// Loads the state from a state dump. The block at the genesis height is fetched from the
+    /// chain of the genesis config, which must be in the registry.
+    pub async fn from_state_dump_with_chains(
+        filter: FlatStateFilter,
+        path: &str,
+        chains: &ChainRegistry,
+    ) -> FlatStateResult<Self> {
         let path = Path::new(path);
         let genesis = Genesis::from_file(
             path.join(GENESIS_CONFIG_FILENAME),
@@ -39,14 +51,23 @@ impl FlatState {
             ))
         })?;
 
+        let chain = chains.get(&chain_id).ok_or_else(|| {
+            FlatStateError::StateDumpError(format!("Chain {} is not registered", chain_id))
+        })?;
+
         let block = fetch_block_by_height(
             &new_reqwest_client(),
             genesis.config.genesis_height,
             DEFAULT_FETCHER_TIMEOUT,
-            chain_id,
+            chain,
         )
         .await
-        .expect("State dump block is missing");
+        .ok_or_else(|| {
+            FlatStateError::StateDumpError(format!(
+                "State dump block {} is missing",
+                genesis.config.genesis_height
+            ))
+        })?;
 
         let block_hash = block.block.header.hash;
         let block_header: BlockHeaderInnerLiteView = BlockHeader::from(block.block.header).into();
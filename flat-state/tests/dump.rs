@@ -0,0 +1,71 @@
+#![cfg(feature = "statedump")]
+
+use fastnear_flat_state::filter::FlatStateFilter;
+use fastnear_flat_state::state::{FlatState, FlatStateError};
+use fastnear_neardata_fetcher::chains::{ChainConfig, ChainRegistry};
+use fastnear_neardata_fetcher::test_support::{test_block, test_block_hash, TestServer};
+use fastnear_primitives::types::ChainId;
+use serde_json::json;
+use tempfile::TempDir;
+
+const STATE_DUMP_PATH: &str = "../res/state_dump";
+const GENESIS_HEIGHT: u64 = 121015263;
+
+/// Copies the state dump with the chain ID of the genesis config replaced.
+fn state_dump(chain_id: &str) -> TempDir {
+    let dir = tempfile::tempdir().unwrap();
+    let genesis = std::fs::read(format!("{}/genesis.json", STATE_DUMP_PATH)).unwrap();
+    let mut genesis: serde_json::Value = serde_json::from_slice(&genesis).unwrap();
+    genesis["chain_id"] = chain_id.into();
+    std::fs::write(
+        dir.path().join("genesis.json"),
+        serde_json::to_vec(&genesis).unwrap(),
+    )
+    .unwrap();
+    std::fs::copy(
+        format!("{}/records.json", STATE_DUMP_PATH),
+        dir.path().join("records.json"),
+    )
+    .unwrap();
+    dir
+}
+
+#[tokio::test]
+async fn custom_chain_state_dump() {
+    let server = TestServer::start().await;
+    let mut block = test_block(GENESIS_HEIGHT, Some(GENESIS_HEIGHT - 1), 1);
+    // Required to convert the header view to the header of the latest protocol version
+    block["block"]["header"]["chunk_endorsements"] = json!([[0]]);
+    server.add_blocks(GENESIS_HEIGHT..=GENESIS_HEIGHT, &[], 1);
+    server.set_block(GENESIS_HEIGHT, Some(block));
+    let dir = state_dump("localnet");
+    let chain_id: ChainId = "localnet".parse().unwrap();
+    let mut chains = ChainRegistry::default();
+    chains.register(ChainConfig::from_base_url(chain_id.clone(), &server.url()));
+
+    let state = FlatState::from_state_dump_with_chains(
+        FlatStateFilter::full(),
+        dir.path().to_str().unwrap(),
+        &chains,
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(state.config.chain_id, chain_id);
+    assert_eq!(state.block_hash, test_block_hash(GENESIS_HEIGHT));
+    assert_eq!(state.block_header.height, GENESIS_HEIGHT);
+}
+
+#[tokio::test]
+async fn unregistered_chain_state_dump() {
+    let dir = state_dump("localnet");
+
+    let result =
+        FlatState::from_state_dump(FlatStateFilter::full(), dir.path().to_str().unwrap()).await;
+
+    match result {
+        Err(FlatStateError::StateDumpError(e)) => assert!(e.contains("localnet"), "{}", e),
+        Err(e) => panic!("Unexpected error: {}", e),
+        Ok(_) => panic!("The unregistered chain was loaded"),
+    }
+}
@@ -70,10 +70,16 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let fetcher_config = fetcher_config_builder.build();
 
     let (sender, receiver) = mpsc::channel(100);
-    tokio::spawn(fetcher::start_fetcher(fetcher_config, sender, is_running));
+    let fetcher_handle = tokio::spawn(fetcher::start_fetcher(fetcher_config, sender, is_running));
 
     listen_blocks(receiver).await;
 
+    let report = fetcher_handle.await?;
+    println!(
+        "Last delivered block height: {:?}",
+        report.last_delivered_block_height
+    );
+
     Ok(())
 }
 
@@ -98,7 +104,7 @@ async fn listen_blocks(mut stream: mpsc::Receiver<BlockWithTxHashes>) {
             start_time = std::time::Instant::now();
         }
 
-        let block_hash = block.block.header.hash.clone();
+        let block_hash = block.block.header.hash;
         if let Some(prev_block_hash) = prev_block_hash {
             assert_eq!(
                 prev_block_hash, block.block.header.prev_hash,
@@ -106,6 +112,6 @@ async fn listen_blocks(mut stream: mpsc::Receiver<BlockWithTxHashes>) {
                 block.block.header.height
             );
         }
-        prev_block_hash = Some(block_hash.clone());
+        prev_block_hash = Some(block_hash);
     }
 }
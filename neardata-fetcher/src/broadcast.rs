@@ -0,0 +1,161 @@
+//! Fan-out of a single block stream to multiple consumers.
+//!
+//! The broadcast consumes the receiving end of the channel passed to `start_fetcher`, or to
+//! `start_fetcher_with_metadata` for a `BlockBroadcast<DeliveredBlock>`, and forwards every block
+//! to all subscribers. Each subscriber has its own bounded buffer and a lag policy.
+//! A subscriber that joins later starts receiving blocks from the current head.
+use crate::*;
+
+use crate::types::DeliveredBlock;
+use std::sync::Mutex;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LagPolicy {
+    /// Wait for the subscriber to free up space in its buffer. This slows down the producer and
+    /// all other subscribers.
+    Block,
+    /// Disconnect the subscriber once its buffer is full.
+    Drop,
+}
+
+/// A block of the stream that can be broadcasted.
+pub trait BroadcastBlock: Send + Sync + 'static {
+    fn block_height(&self) -> BlockHeight;
+}
+
+impl BroadcastBlock for BlockWithTxHashes {
+    fn block_height(&self) -> BlockHeight {
+        self.block.header.height
+    }
+}
+
+impl BroadcastBlock for DeliveredBlock {
+    fn block_height(&self) -> BlockHeight {
+        DeliveredBlock::block_height(self)
+    }
+}
+
+struct Subscriber<B> {
+    id: u64,
+    sender: mpsc::Sender<Arc<B>>,
+    lag_policy: LagPolicy,
+}
+
+impl<B> Clone for Subscriber<B> {
+    fn clone(&self) -> Self {
+        Self {
+            id: self.id,
+            sender: self.sender.clone(),
+            lag_policy: self.lag_policy,
+        }
+    }
+}
+
+/// The subscribers and the head are updated together, so a new subscriber either receives the
+/// block that is being sent or sees it as the head.
+struct State<B> {
+    subscribers: Vec<Subscriber<B>>,
+    next_subscriber_id: u64,
+    head_block_height: Option<BlockHeight>,
+}
+
+pub struct BlockBroadcast<B = BlockWithTxHashes> {
+    state: Arc<Mutex<State<B>>>,
+}
+
+impl<B> Clone for BlockBroadcast<B> {
+    fn clone(&self) -> Self {
+        Self {
+            state: self.state.clone(),
+        }
+    }
+}
+
+impl<B> Default for BlockBroadcast<B> {
+    fn default() -> Self {
+        Self {
+            state: Arc::new(Mutex::new(State {
+                subscribers: Vec::new(),
+                next_subscriber_id: 0,
+                head_block_height: None,
+            })),
+        }
+    }
+}
+
+impl<B: BroadcastBlock> BlockBroadcast<B> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a new subscriber with the given buffer size. The subscriber receives blocks starting
+    /// from the next block after the current head.
+    pub fn subscribe(&self, buffer_size: usize, lag_policy: LagPolicy) -> mpsc::Receiver<Arc<B>> {
+        let (sender, receiver) = mpsc::channel(buffer_size);
+        let mut state = self.state.lock().unwrap();
+        let id = state.next_subscriber_id;
+        state.next_subscriber_id += 1;
+        state.subscribers.push(Subscriber {
+            id,
+            sender,
+            lag_policy,
+        });
+        receiver
+    }
+
+    pub fn num_subscribers(&self) -> usize {
+        self.state.lock().unwrap().subscribers.len()
+    }
+
+    /// The height of the last broadcasted block, if any. The block may still be in the middle of
+    /// being sent to the subscribers.
+    pub fn head_block_height(&self) -> Option<BlockHeight> {
+        self.state.lock().unwrap().head_block_height
+    }
+
+    /// Forwards blocks from the stream to all subscribers until the stream is closed.
+    pub async fn run(&self, mut blocks_stream: mpsc::Receiver<B>) {
+        while let Some(block) = blocks_stream.recv().await {
+            let block_height = block.block_height();
+            let block = Arc::new(block);
+            // Sending outside of the lock, so new subscribers can join while we wait. They start
+            // from the next block.
+            let subscribers = {
+                let mut state = self.state.lock().unwrap();
+                state.head_block_height = Some(block_height);
+                state.subscribers.clone()
+            };
+            let mut disconnected = Vec::new();
+            for subscriber in subscribers {
+                let is_connected = match subscriber.lag_policy {
+                    LagPolicy::Block => subscriber.sender.send(block.clone()).await.is_ok(),
+                    LagPolicy::Drop => match subscriber.sender.try_send(block.clone()) {
+                        Ok(()) => true,
+                        Err(mpsc::error::TrySendError::Full(_)) => {
+                            tracing::log::warn!(
+                                target: LOG_TARGET,
+                                "Subscriber #{} is lagging behind at block {}, disconnecting",
+                                subscriber.id,
+                                block_height
+                            );
+                            false
+                        }
+                        Err(mpsc::error::TrySendError::Closed(_)) => false,
+                    },
+                };
+                if !is_connected {
+                    disconnected.push(subscriber.id);
+                }
+            }
+            if !disconnected.is_empty() {
+                self.state
+                    .lock()
+                    .unwrap()
+                    .subscribers
+                    .retain(|subscriber| !disconnected.contains(&subscriber.id));
+            }
+        }
+        // Closing all subscriber channels
+        self.state.lock().unwrap().subscribers.clear();
+    }
+}
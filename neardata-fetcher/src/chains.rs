@@ -0,0 +1,248 @@
+//! The registry of the chains served by the fetcher.
+//!
+//! Every chain maps to its live block API and the archives with the block ranges they cover.
+//! Mainnet and testnet are registered by default, other chains (localnet, sandbox, forknet) can
+//! be added with `FetcherConfigBuilder::chain`.
+//! The archives of a chain can be replaced at startup with an archive manifest, so new archive
+//! hosts don't require a new release of the crate.
+use crate::*;
+
+use crate::types::BlockSource;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// An archive endpoint that serves the blocks in the given range.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArchiveEndpoint {
+    /// The URL prefix of the archives, e.g. `https://a1.mainnet.neardata.xyz/raw/`.
+    /// The archive path, e.g. `000123/456/000123456780.tgz`, is appended to it.
+    pub url: String,
+    /// The first block height covered by the endpoint (inclusive)
+    pub start_block_height: BlockHeight,
+    /// The last block height covered by the endpoint (exclusive). `None` if not bounded.
+    #[serde(default)]
+    pub end_block_height: Option<BlockHeight>,
+    /// The R2 archives are only used with `enable_r2_archive_sync`.
+    pub source: BlockSource,
+    /// The endpoint is only used when the auth bearer token is set.
+    #[serde(default)]
+    pub requires_auth: bool,
+}
+
+impl ArchiveEndpoint {
+    pub fn contains(&self, block_height: BlockHeight) -> bool {
+        block_height >= self.start_block_height
+            && self
+                .end_block_height
+                .is_none_or(|end_block_height| block_height < end_block_height)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChainConfig {
+    pub chain_id: ChainId,
+    /// The base URL of the live API, e.g. `https://mainnet.neardata.xyz`
+    pub api_url: String,
+    /// The archive endpoints in the order of preference
+    pub archives: Vec<ArchiveEndpoint>,
+}
+
+impl ChainConfig {
+    pub fn mainnet() -> Self {
+        let neardata_archive =
+            |index: usize, start_block_height, end_block_height| ArchiveEndpoint {
+                url: format!("https://a{}.mainnet.neardata.xyz/raw/", index),
+                start_block_height,
+                end_block_height,
+                source: BlockSource::Archive,
+                requires_auth: false,
+            };
+        Self {
+            chain_id: ChainId::Mainnet,
+            api_url: "https://mainnet.neardata.xyz".to_string(),
+            archives: vec![
+                ArchiveEndpoint {
+                    url: "https://archive.data.fastnear.com/mainnet/".to_string(),
+                    start_block_height: 0,
+                    end_block_height: Some(142000000),
+                    source: BlockSource::R2Archive,
+                    requires_auth: false,
+                },
+                neardata_archive(0, 0, Some(122000000)),
+                neardata_archive(1, 122000000, Some(142000000)),
+                neardata_archive(2, 142000000, None),
+            ],
+        }
+    }
+
+    pub fn testnet() -> Self {
+        Self {
+            chain_id: ChainId::Testnet,
+            api_url: "https://testnet.neardata.xyz".to_string(),
+            archives: vec![
+                ArchiveEndpoint {
+                    url: "https://archive.data.fastnear.com/testnet/".to_string(),
+                    start_block_height: 0,
+                    end_block_height: Some(185670001),
+                    source: BlockSource::R2Archive,
+                    requires_auth: false,
+                },
+                ArchiveEndpoint {
+                    url: "https://testnet.neardata.xyz/raw/".to_string(),
+                    start_block_height: 0,
+                    end_block_height: None,
+                    source: BlockSource::Archive,
+                    requires_auth: false,
+                },
+            ],
+        }
+    }
+
+    /// A chain that serves the live API at `{base_url}/v0/` and all archives at `{base_url}/raw/`,
+    /// like a neardata instance for a custom chain.
+    pub fn from_base_url(chain_id: ChainId, base_url: &str) -> Self {
+        let base_url = base_url.trim_end_matches('/');
+        Self {
+            chain_id,
+            api_url: base_url.to_string(),
+            archives: vec![ArchiveEndpoint {
+                url: format!("{}/raw/", base_url),
+                start_block_height: 0,
+                end_block_height: None,
+                source: BlockSource::Archive,
+                requires_auth: false,
+            }],
+        }
+    }
+
+    /// Returns the archive endpoint for the archive that starts at the given height.
+    pub fn archive_endpoint(
+        &self,
+        archive_block_height: BlockHeight,
+        enable_r2_archive_sync: bool,
+        has_auth: bool,
+    ) -> Option<&ArchiveEndpoint> {
+        self.archives.iter().find(|archive| {
+            archive.contains(archive_block_height)
+                && (enable_r2_archive_sync || archive.source != BlockSource::R2Archive)
+                && (has_auth || !archive.requires_auth)
+        })
+    }
+}
+
+/// The chains known to the fetcher, by chain ID.
+#[derive(Debug, Clone)]
+pub struct ChainRegistry {
+    chains: HashMap<ChainId, ChainConfig>,
+}
+
+impl Default for ChainRegistry {
+    fn default() -> Self {
+        let mut registry = Self {
+            chains: HashMap::new(),
+        };
+        registry.register(ChainConfig::mainnet());
+        registry.register(ChainConfig::testnet());
+        registry
+    }
+}
+
+impl ChainRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the chain or replaces the existing chain with the same ID.
+    pub fn register(&mut self, chain_config: ChainConfig) {
+        self.chains
+            .insert(chain_config.chain_id.clone(), chain_config);
+    }
+
+    pub fn get(&self, chain_id: &ChainId) -> Option<&ChainConfig> {
+        self.chains.get(chain_id)
+    }
+}
+
+/// Where to load the archive manifest from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ArchiveManifestSource {
+    Url(String),
+    File(String),
+}
+
+/// The layout of the archives of a chain, e.g.
+/// ```json
+/// {
+///   "chain_id": "mainnet",
+///   "archives": [
+///     {"url": "https://a0.mainnet.neardata.xyz/raw/", "start_block_height": 0, "end_block_height": 122000000, "source": "archive"},
+///     {"url": "https://a1.mainnet.neardata.xyz/raw/", "start_block_height": 122000000, "source": "archive", "requires_auth": true}
+///   ]
+/// }
+/// ```
+/// The archives of each source must cover the heights from 0 without gaps or overlaps, only the
+/// last one can be unbounded. The heights that no usable archive covers, e.g. the ones of an
+/// endpoint that requires auth without the token, are fetched from the live API.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArchiveManifest {
+    /// The chain of the archives, must match the configured chain
+    pub chain_id: ChainId,
+    /// The archive endpoints in the order of preference
+    pub archives: Vec<ArchiveEndpoint>,
+}
+
+impl ArchiveManifest {
+    pub fn from_file(path: &str) -> Result<Self, String> {
+        let content = std::fs::read(path).map_err(|err| err.to_string())?;
+        Self::from_slice(&content)
+    }
+
+    pub fn from_slice(content: &[u8]) -> Result<Self, String> {
+        serde_json::from_slice(content).map_err(|err| err.to_string())
+    }
+
+    /// Checks that the manifest is for the given chain and that the archives of each source are
+    /// contiguous from height 0.
+    pub fn validate(&self, chain_id: &ChainId) -> Result<(), String> {
+        if &self.chain_id != chain_id {
+            return Err(format!(
+                "The manifest is for chain {}, not {}",
+                self.chain_id, chain_id
+            ));
+        }
+        for source in [BlockSource::Archive, BlockSource::R2Archive] {
+            let mut archives: Vec<_> = self
+                .archives
+                .iter()
+                .filter(|archive| archive.source == source)
+                .collect();
+            archives.sort_by_key(|archive| archive.start_block_height);
+            let mut next_block_height = Some(0);
+            for archive in archives {
+                match next_block_height {
+                    Some(block_height) if block_height == archive.start_block_height => {}
+                    Some(block_height) if block_height < archive.start_block_height => {
+                        return Err(format!(
+                            "Gap in the {:?} archives from block {} to block {}",
+                            source, block_height, archive.start_block_height
+                        ));
+                    }
+                    _ => {
+                        return Err(format!(
+                            "Overlapping {:?} archives at block {}",
+                            source, archive.start_block_height
+                        ));
+                    }
+                }
+                if archive
+                    .end_block_height
+                    .is_some_and(|end_block_height| end_block_height <= archive.start_block_height)
+                {
+                    return Err(format!("Empty {:?} archive at {}", source, archive.url));
+                }
+                next_block_height = archive.end_block_height;
+            }
+        }
+        Ok(())
+    }
+}
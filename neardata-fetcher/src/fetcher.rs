@@ -1,10 +1,24 @@
 pub use crate::types::*;
 pub use crate::utils::*;
 use crate::*;
-use fastnear_primitives::near_primitives::types::Finality;
-use fastnear_primitives::near_primitives::views::BlockView;
-use reqwest::ClientBuilder;
+
+use crate::chains::{ArchiveManifest, ArchiveManifestSource, ChainConfig};
+use crate::filter::BlockFilter;
+use fastnear_primitives::block_with_tx_hash::{
+    IndexerExecutionOutcomeWithReceiptAndTxHash, IndexerShardWithTxHashes,
+};
+use fastnear_primitives::json;
+use fastnear_primitives::near_indexer_primitives::IndexerTransactionWithOutcome;
+use fastnear_primitives::near_primitives::types::{Finality, ShardId};
+use fastnear_primitives::near_primitives::views::{
+    BlockView, ReceiptView, StateChangeWithCauseView,
+};
+use fastnear_primitives::select::{BlockSeed, BlockSelector, OptionalSeed};
+use fastnear_primitives::tolerant::{from_slice_tolerant, Tolerant};
+use reqwest::{ClientBuilder, StatusCode};
 use std::io::Read;
+use std::sync::OnceLock;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 
 #[derive(Debug)]
 struct InterruptedError;
@@ -13,31 +27,224 @@ type InterruptibleResult<T> = Result<T, InterruptedError>;
 
 pub const MAX_REDIRECTS: usize = 5;
 
+/// A successful response together with the details of how it was fetched.
+struct Fetched<T> {
+    value: T,
+    /// The final URL after redirects
+    url: String,
+    num_bytes: u64,
+    num_retries: u32,
+    latency: Duration,
+}
+
+impl<T> Fetched<T> {
+    fn map<U>(self, f: impl FnOnce(T) -> U) -> Fetched<U> {
+        Fetched {
+            value: f(self.value),
+            url: self.url,
+            num_bytes: self.num_bytes,
+            num_retries: self.num_retries,
+            latency: self.latency,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct SinkClosedError;
+
+/// The receiving side of the fetcher, either with or without the delivery metadata.
+#[derive(Clone)]
+enum BlocksSink {
+    Blocks(mpsc::Sender<BlockWithTxHashes>),
+    DeliveredBlocks(mpsc::Sender<DeliveredBlock>),
+}
+
+impl BlocksSink {
+    async fn send(&self, block: DeliveredBlock) -> Result<(), SinkClosedError> {
+        match self {
+            BlocksSink::Blocks(sender) => {
+                sender.send(block.block).await.map_err(|_| SinkClosedError)
+            }
+            BlocksSink::DeliveredBlocks(sender) => {
+                sender.send(block).await.map_err(|_| SinkClosedError)
+            }
+        }
+    }
+
+    fn is_closed(&self) -> bool {
+        match self {
+            BlocksSink::Blocks(sender) => sender.is_closed(),
+            BlocksSink::DeliveredBlocks(sender) => sender.is_closed(),
+        }
+    }
+}
+
+/// Selects the parts of the blocks to decode according to the config: the selected shards and,
+/// in the thin mode, the parts that match the filter.
+struct DecodeSelector<'a> {
+    shards: Option<&'a [ShardId]>,
+    filter: Option<&'a BlockFilter>,
+}
+
+impl BlockSelector for DecodeSelector<'_> {
+    fn select_shard(&self, shard_id: ShardId) -> bool {
+        self.shards.is_none_or(|shards| shards.contains(&shard_id))
+    }
+
+    fn select_transaction(&self, transaction: &IndexerTransactionWithOutcome) -> bool {
+        self.filter
+            .is_none_or(|filter| filter.select_transaction(transaction))
+    }
+
+    fn select_receipt(&self, receipt: &ReceiptView) -> bool {
+        self.filter
+            .is_none_or(|filter| filter.select_receipt(receipt))
+    }
+
+    fn select_outcome(&self, outcome: &IndexerExecutionOutcomeWithReceiptAndTxHash) -> bool {
+        self.filter
+            .is_none_or(|filter| filter.select_outcome(outcome))
+    }
+
+    fn select_state_change(&self, state_change: &StateChangeWithCauseView) -> bool {
+        self.filter
+            .is_none_or(|filter| filter.select_state_change(state_change))
+    }
+}
+
 #[derive(Clone)]
 struct Fetcher {
     client: Client,
     config: FetcherConfig,
+    /// The endpoints of the selected chain
+    chain: Arc<ChainConfig>,
     is_running: Arc<AtomicBool>,
+    /// Set when the first worker notices that the fetcher is stopped
+    shutdown_deadline: Arc<OnceLock<Instant>>,
 }
 
 impl Fetcher {
-    pub async fn fetch<T>(&self, url: &str) -> Result<Option<T>, FetchError>
-    where
-        T: serde::de::DeserializeOwned,
-    {
-        // Manually handle redirects and adding auth headers
-        let mut url = url.to_string();
+    fn retry_duration(&self) -> Duration {
+        self.config.retry_duration.unwrap_or(DEFAULT_RETRY_DURATION)
+    }
+
+    fn shutdown_deadline(&self) -> Instant {
+        *self.shutdown_deadline.get_or_init(|| {
+            Instant::now()
+                + self
+                    .config
+                    .shutdown_timeout
+                    .unwrap_or(DEFAULT_SHUTDOWN_TIMEOUT)
+        })
+    }
+
+    /// Returns `true` while the fetched blocks can be delivered. After the fetcher is stopped, the
+    /// in-flight blocks are delivered until the shutdown deadline.
+    fn is_delivering(&self) -> bool {
+        self.is_running.load(Ordering::SeqCst) || Instant::now() < self.shutdown_deadline()
+    }
+
+    /// Waits until all preceding blocks are delivered. The closure returns the delay in
+    /// milliseconds before checking again, `0` when it's the turn of the caller, or `None` if one
+    /// of the preceding blocks can't be delivered.
+    /// Returns `false` if the caller can't deliver its blocks.
+    async fn wait_for_turn(&self, turn_delay_ms: impl Fn() -> Option<u64>) -> bool {
+        while self.is_delivering() {
+            match turn_delay_ms() {
+                None => return false,
+                Some(0) => return true,
+                Some(delay_ms) => tokio::time::sleep(Duration::from_millis(delay_ms)).await,
+            }
+        }
+        false
+    }
+
+    /// Sends the block to the sink. A full sink is waited for while the fetcher is running. Once
+    /// the fetcher is stopped, including in the middle of the send, gives up at the shutdown
+    /// deadline. Returns `false` if the block wasn't delivered.
+    async fn deliver(&self, blocks_sink: &BlocksSink, block: DeliveredBlock) -> bool {
+        let send = blocks_sink.send(block);
+        tokio::pin!(send);
+        while self.is_running.load(Ordering::SeqCst) {
+            tokio::select! {
+                result = &mut send => return result.is_ok(),
+                _ = tokio::time::sleep(SHUTDOWN_CHECK_INTERVAL) => {}
+            }
+        }
+        let deadline = tokio::time::Instant::from_std(self.shutdown_deadline());
+        matches!(tokio::time::timeout_at(deadline, send).await, Ok(Ok(())))
+    }
+
+    /// Returns a copy of the fetcher that retries quickly, unless the retry duration is configured.
+    fn with_tip_retry_duration(&self) -> Self {
+        let mut fetcher = self.clone();
+        fetcher.config.retry_duration = Some(
+            self.config
+                .retry_duration
+                .unwrap_or(DEFAULT_TIP_RETRY_DURATION),
+        );
+        fetcher
+    }
+
+    /// Reports the latency between the block production (the header timestamp) and its delivery.
+    fn report_tip_latency(&self, block_height: BlockHeight, block_timestamp_nanosec: u64) {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let latency = now.saturating_sub(Duration::from_nanos(block_timestamp_nanosec));
+        tracing::log::debug!(target: LOG_TARGET, "Block {} delivered {:?} after production", block_height, latency);
+        if let Some(stats) = &self.config.stats {
+            let latency_ms = latency.as_millis() as u64;
+            stats
+                .last_block_latency_ms
+                .store(latency_ms, Ordering::Relaxed);
+            stats.tip_latency.record(latency_ms);
+        }
+    }
+
+    fn api_url(&self, suffix: &str) -> String {
+        format!("{}{}", self.chain.api_url, suffix)
+    }
+
+    fn is_archive_sync_enabled(&self) -> bool {
+        !self.config.disable_archive_sync && !self.chain.archives.is_empty()
+    }
+
+    fn is_auth_allowed(&self, url: &url::Url) -> bool {
+        let Some(host) = url.host_str() else {
+            return false;
+        };
+        self.config.auth_allowed_hosts.iter().any(|allowed_host| {
+            host == allowed_host
+                || host
+                    .strip_suffix(allowed_host.as_str())
+                    .is_some_and(|prefix| prefix.ends_with('.'))
+        })
+    }
+
+    /// Downloads the response body for the given URL.
+    /// Redirects are followed manually, so the auth header is re-evaluated for every hop and only
+    /// sent to the allowed hosts. Non-success status codes are returned as `StatusError`.
+    async fn fetch_bytes(&self, url: &str) -> Result<Fetched<Vec<u8>>, FetchError> {
+        let start_time = Instant::now();
+        let mut url = url::Url::parse(url).map_err(|_| FetchError::RedirectError)?;
         for _ in 0..MAX_REDIRECTS {
-            let mut request = self.client.get(&url);
+            let mut request = self.client.get(url.clone());
             if let Some(token) = &self.config.auth_bearer_token {
-                request = request.bearer_auth(token);
+                if self.is_auth_allowed(&url) {
+                    request = request.bearer_auth(token);
+                }
+            }
+            if let Some(stats) = &self.config.stats {
+                stats.num_requests.fetch_add(1, Ordering::Relaxed);
             }
             let response = request
                 .timeout(self.config.timeout_duration.unwrap_or(DEFAULT_TIMEOUT))
                 .send()
                 .await?;
 
-            if response.status().is_redirection() {
+            let status = response.status();
+            if status.is_redirection() {
                 let location = response
                     .headers()
                     .get(reqwest::header::LOCATION)
@@ -45,70 +252,89 @@ impl Fetcher {
                     .to_str()
                     .map_err(|_| FetchError::RedirectError)?;
 
-                let parsed_current =
-                    url::Url::parse(&url).map_err(|_| FetchError::RedirectError)?;
-
                 // Resolve the location relative to the current URL
-                url = parsed_current
-                    .join(location)
-                    .map_err(|_| FetchError::RedirectError)?
-                    .to_string();
+                url = url.join(location).map_err(|_| FetchError::RedirectError)?;
                 continue;
             }
+            if !status.is_success() {
+                return Err(FetchError::StatusError(status));
+            }
 
-            return Ok(response.json().await?);
+            let body = response.bytes().await?;
+            if let Some(stats) = &self.config.stats {
+                stats
+                    .bytes_downloaded
+                    .fetch_add(body.len() as u64, Ordering::Relaxed);
+            }
+            return Ok(Fetched {
+                num_bytes: body.len() as u64,
+                value: body.to_vec(),
+                url: url.to_string(),
+                num_retries: 0,
+                latency: start_time.elapsed(),
+            });
         }
         Err(FetchError::RedirectError)
     }
 
-    pub async fn fetch_until_success<T>(&self, url: &str) -> InterruptibleResult<Option<T>>
-    where
-        T: serde::de::DeserializeOwned,
-    {
+    async fn fetch_with_details<T>(
+        &self,
+        url: &str,
+        decode: &impl Fn(&mut [u8]) -> Result<T, FetchError>,
+    ) -> Result<Fetched<T>, FetchError> {
+        let mut fetched = self.fetch_bytes(url).await?;
+        let value = decode(&mut fetched.value)?;
+        Ok(fetched.map(|_| value))
+    }
+
+    async fn fetch_until_success_with_details<T>(
+        &self,
+        url: &str,
+        decode: impl Fn(&mut [u8]) -> Result<T, FetchError>,
+    ) -> InterruptibleResult<Fetched<T>> {
+        let start_time = Instant::now();
+        let mut num_retries = 0;
         while self.is_running.load(Ordering::SeqCst) {
-            match self.fetch(url).await {
-                Ok(block) => return Ok(block),
-                Err(FetchError::ReqwestError(err)) => {
-                    tracing::log::warn!(target: LOG_TARGET, "Failed to fetch: {}", err);
-                    tokio::time::sleep(
-                        self.config.retry_duration.unwrap_or(DEFAULT_RETRY_DURATION),
-                    )
-                    .await;
+            match self.fetch_with_details(url, &decode).await {
+                Ok(fetched) => {
+                    return Ok(Fetched {
+                        num_retries,
+                        latency: start_time.elapsed(),
+                        ..fetched
+                    })
                 }
-                Err(FetchError::RedirectError) => {
-                    tracing::log::warn!(target: LOG_TARGET, "Redirect error");
-                    tokio::time::sleep(
-                        self.config.retry_duration.unwrap_or(DEFAULT_RETRY_DURATION),
-                    )
-                    .await;
+                Err(err) => {
+                    tracing::log::warn!(target: LOG_TARGET, "Failed to fetch {}: {}", url, err);
+                    tokio::time::sleep(self.retry_duration()).await;
+                    num_retries += 1;
                 }
             }
         }
         Err(InterruptedError)
     }
 
-    pub async fn fetch_block_until_success(
-        &self,
-        url: &str,
-    ) -> InterruptibleResult<Option<BlockWithTxHashes>> {
-        self.fetch_until_success(url).await
+    pub async fn fetch_until_success<T>(&self, url: &str) -> InterruptibleResult<Option<T>>
+    where
+        T: serde::de::DeserializeOwned + serde::Serialize,
+    {
+        Ok(self
+            .fetch_until_success_with_details(url, |data| Ok(self.decode(data, url)?.value))
+            .await?
+            .value)
     }
 
     pub async fn fetch_last_block_headers(
         &self,
         finality: &Finality,
     ) -> InterruptibleResult<Option<BlockView>> {
-        self.fetch_until_success(&target_url(
-            &format!(
-                "/v0/last_block/{}/headers",
-                if finality == &Finality::Final {
-                    "final"
-                } else {
-                    "optimistic"
-                }
-            ),
-            self.config.chain_id,
-        ))
+        self.fetch_until_success(&self.api_url(&format!(
+            "/v0/last_block/{}/headers",
+            if finality == &Finality::Final {
+                "final"
+            } else {
+                "optimistic"
+            }
+        )))
         .await
     }
 
@@ -116,38 +342,206 @@ impl Fetcher {
         &self,
         height: BlockHeight,
         finality: &Finality,
-    ) -> InterruptibleResult<Option<BlockWithTxHashes>> {
-        self.fetch_block_until_success(&target_url(
-            &format!(
-                "/v0/block{}/{}",
-                if finality == &Finality::Final {
-                    ""
-                } else {
-                    "_opt"
-                },
-                height
-            ),
-            self.config.chain_id,
-        ))
-        .await
+    ) -> InterruptibleResult<Option<DeliveredBlock>> {
+        let block_url = self.api_url(&format!(
+            "/v0/block{}/{}",
+            if finality == &Finality::Final {
+                ""
+            } else {
+                "_opt"
+            },
+            height
+        ));
+        let fetched = if let Some(shards) = self
+            .config
+            .shards
+            .as_ref()
+            .filter(|_| self.config.enable_shard_endpoints)
+        {
+            self.fetch_block_shards(&block_url, shards).await?
+        } else {
+            self.fetch_until_success_with_details(&block_url, |data| {
+                self.decode_block(data, &block_url)
+            })
+            .await?
+        };
+        let Fetched {
+            value,
+            url,
+            num_bytes,
+            num_retries,
+            latency,
+        } = fetched;
+        Ok(value
+            .and_then(|block| self.process_block(block))
+            .map(|block| DeliveredBlock {
+                block,
+                source: BlockSource::Api,
+                endpoint: url,
+                fetch_latency: latency,
+                compressed_size: num_bytes,
+                uncompressed_size: num_bytes,
+                num_retries,
+                arrival_time: SystemTime::now(),
+            }))
     }
 
-    async fn fetch_archive(&self, url: &str) -> Result<Option<Vec<u8>>, FetchError> {
-        let mut request = self.client.get(url);
-        if let Some(token) = &self.config.auth_bearer_token {
-            request = request.bearer_auth(token);
-        }
-        let response = request
-            .timeout(self.config.timeout_duration.unwrap_or(DEFAULT_TIMEOUT))
-            .send()
+    /// Assembles the block from the headers and per-shard endpoints, so only the selected shards
+    /// are downloaded.
+    async fn fetch_block_shards(
+        &self,
+        block_url: &str,
+        shards: &[ShardId],
+    ) -> InterruptibleResult<Fetched<Option<BlockWithTxHashes>>> {
+        let headers_url = format!("{}/headers", block_url);
+        let fetched = self
+            .fetch_until_success_with_details(&headers_url, |data| {
+                Ok(self.decode::<Option<BlockView>>(data, &headers_url)?)
+            })
             .await?;
-        if response.status() == 404 {
-            return Ok(None);
+        let mut fetched = fetched.map(|Tolerant { value, unknown }| {
+            value.map(|block| BlockWithTxHashes {
+                block,
+                shards: Vec::with_capacity(shards.len()),
+                unknown: unknown
+                    .into_iter()
+                    .map(|unknown| unknown.with_path_prefix("block"))
+                    .collect(),
+            })
+        });
+        fetched.url = block_url.to_string();
+        let Some(block) = &mut fetched.value else {
+            return Ok(fetched);
+        };
+        for shard_id in shards {
+            let shard_url = format!("{}/shard/{}", block_url, shard_id);
+            let shard = self
+                .fetch_until_success_with_details(&shard_url, |data| {
+                    Ok(self.decode::<Option<IndexerShardWithTxHashes>>(data, &shard_url)?)
+                })
+                .await?;
+            fetched.num_bytes += shard.num_bytes;
+            fetched.num_retries += shard.num_retries;
+            fetched.latency += shard.latency;
+            let Tolerant {
+                value: shard,
+                unknown: shard_unknown,
+            } = shard.value;
+            if let Some(shard) = shard {
+                let prefix = format!("shards[{}]", block.shards.len());
+                block.unknown.extend(
+                    shard_unknown
+                        .into_iter()
+                        .map(|unknown| unknown.with_path_prefix(&prefix)),
+                );
+                block.shards.push(shard);
+            }
+        }
+        Ok(fetched)
+    }
+
+    /// Records the block in the hash index, removes the shards that are not selected in the config
+    /// and applies the block filter. The shards and the thinned parts are usually skipped while
+    /// decoding already, see `decode_block`, so only the non-matching blocks are dropped here.
+    /// Returns `None` if the block is filtered out.
+    fn process_block(&self, mut block: BlockWithTxHashes) -> Option<BlockWithTxHashes> {
+        if let Some(block_hash_index) = &self.config.block_hash_index {
+            block_hash_index.insert_block(&block);
+        }
+        if let Some(shards) = &self.config.shards {
+            block
+                .shards
+                .retain(|shard| shards.contains(&shard.shard_id));
+        }
+        match &self.config.filter {
+            Some(filter) => filter.apply(block),
+            None => Some(block),
+        }
+    }
+
+    /// Loads the archive manifest, if it's configured.
+    /// Returns `None` if it's not configured, can't be loaded or is not valid for the chain.
+    async fn load_archive_manifest(&self) -> Option<ArchiveManifest> {
+        let manifest = match self.config.archive_manifest.as_ref()? {
+            ArchiveManifestSource::Url(url) => match self.fetch_bytes(url).await {
+                Ok(fetched) => ArchiveManifest::from_slice(&fetched.value),
+                Err(err) => Err(err.to_string()),
+            },
+            ArchiveManifestSource::File(path) => ArchiveManifest::from_file(path),
+        };
+        let manifest = manifest.and_then(|manifest| {
+            manifest.validate(&self.chain.chain_id)?;
+            Ok(manifest)
+        });
+        match manifest {
+            Ok(manifest) => Some(manifest),
+            Err(err) => {
+                tracing::log::warn!(target: LOG_TARGET, "Failed to load the archive manifest, using the default archives: {}", err);
+                None
+            }
+        }
+    }
+
+    async fn fetch_archive(&self, url: &str) -> Result<Option<Fetched<Vec<u8>>>, FetchError> {
+        match self.fetch_bytes(url).await {
+            Ok(archive) => Ok(Some(archive)),
+            Err(FetchError::StatusError(StatusCode::NOT_FOUND)) => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Decodes the JSON, with the tolerant decoding if it's enabled in the config. The source is
+    /// the URL or the archive entry for the warnings.
+    fn decode<T>(&self, data: &[u8], source: &str) -> Result<Tolerant<T>, serde_json::Error>
+    where
+        T: serde::de::DeserializeOwned + serde::Serialize,
+    {
+        if !self.config.tolerant_decoding {
+            return Ok(Tolerant {
+                value: serde_json::from_slice(data)?,
+                unknown: vec![],
+            });
+        }
+        let decoded: Tolerant<T> = from_slice_tolerant(data)?;
+        let unknown = &decoded.unknown;
+        if !unknown.is_empty() {
+            let paths: Vec<_> = unknown.iter().map(|unknown| unknown.path()).collect();
+            tracing::log::warn!(target: LOG_TARGET, "Decoded {} with {} unknown values: {}", source, unknown.len(), paths.join(", "));
+            if let Some(stats) = &self.config.stats {
+                stats.num_tolerant_decodes.fetch_add(1, Ordering::Relaxed);
+                stats
+                    .num_unknown_values
+                    .fetch_add(unknown.len() as u64, Ordering::Relaxed);
+            }
+        }
+        Ok(decoded)
+    }
+
+    /// Decodes the block JSON of the API or an archive, `None` for a skipped block. The shards that
+    /// are not selected in the config are skipped while decoding, so they are never built, and
+    /// the thin filter drops the parts that don't match as soon as they are decoded.
+    /// The tolerant decoding decodes the whole block, it's thinned by `process_block`.
+    fn decode_block(
+        &self,
+        data: &mut [u8],
+        source: &str,
+    ) -> Result<Option<BlockWithTxHashes>, FetchError> {
+        if self.config.tolerant_decoding {
+            let Tolerant { value, unknown } =
+                self.decode::<Option<BlockWithTxHashes>>(data, source)?;
+            return Ok(value.map(|value| Tolerant { value, unknown }.into_block()));
         }
-        Ok(response.bytes().await.map(|b| Some(b.to_vec()))?)
+        let selector = DecodeSelector {
+            shards: self.config.shards.as_deref(),
+            filter: self.config.filter.as_ref(),
+        };
+        // With the `simd-json` feature, the data is overwritten by the parser
+        json::from_mut_slice_seed(data, OptionalSeed(BlockSeed(&selector)))
+            .map_err(|err| FetchError::DecodeError(serde::de::Error::custom(err)))
     }
 
-    fn parse_archive(&self, archive: Vec<u8>) -> Result<Vec<BlockWithTxHashes>, String> {
+    /// Returns the blocks from the archive sorted by height, each with its uncompressed size.
+    fn parse_archive(&self, archive: &[u8]) -> Result<Vec<(BlockWithTxHashes, u64)>, String> {
         let archive = std::io::Cursor::new(archive);
         let mut archive = tar::Archive::new(flate2::read::GzDecoder::new(archive));
         let mut blocks = Vec::new();
@@ -157,18 +551,28 @@ impl Fetcher {
             entry
                 .read_to_end(&mut content)
                 .map_err(|err| err.to_string())?;
-            let block: BlockWithTxHashes =
-                serde_json::from_slice(&content).map_err(|err| err.to_string())?;
-            blocks.push(block);
+            let uncompressed_size = content.len() as u64;
+            let path = entry
+                .path()
+                .map(|path| path.display().to_string())
+                .unwrap_or_default();
+            let block = self
+                .decode_block(&mut content, &path)
+                .map_err(|err| err.to_string())?;
+            blocks.extend(
+                block
+                    .and_then(|block| self.process_block(block))
+                    .map(|block| (block, uncompressed_size)),
+            );
         }
-        blocks.sort_by(|a, b| a.block.header.height.cmp(&b.block.header.height));
+        blocks.sort_by_key(|(block, _)| block.block.header.height);
         Ok(blocks)
     }
 
     async fn fetch_blocks_from_archive(
         &self,
         archive_block_height: BlockHeight,
-    ) -> InterruptibleResult<Vec<BlockWithTxHashes>> {
+    ) -> InterruptibleResult<Vec<DeliveredBlock>> {
         let padded_block_height = format!("{:0>12}", archive_block_height);
         let suffix = &format!(
             "{}/{}/{}.tgz",
@@ -176,66 +580,80 @@ impl Fetcher {
             &padded_block_height[6..9],
             padded_block_height
         );
-        let prefix = match self.config.chain_id {
-            ChainId::Mainnet
-                if self.config.enable_r2_archive_sync
-                    && archive_block_height <= MAINNET_R2_LAST_BLOCK_HEIGHT =>
-            {
-                "https://archive.data.fastnear.com/mainnet/".to_string()
-            }
-            ChainId::Testnet
-                if self.config.enable_r2_archive_sync
-                    && archive_block_height <= TESTNET_ARCHIVE_LAST_BLOCK_HEIGHT =>
-            {
-                "https://archive.data.fastnear.com/testnet/".to_string()
-            }
-            ChainId::Mainnet => format!(
-                "https://a{}.mainnet.neardata.xyz/raw/",
-                MAINNET_ARCHIVE_BOUNDARIES
-                    .iter()
-                    .position(|&b| archive_block_height < b)
-                    .unwrap_or(MAINNET_ARCHIVE_BOUNDARIES.len())
-            ),
-            ChainId::Testnet => "https://testnet.neardata.xyz/raw/".to_string(),
+        let Some(archive) = self.chain.archive_endpoint(
+            archive_block_height,
+            self.config.enable_r2_archive_sync,
+            self.config.auth_bearer_token.is_some(),
+        ) else {
+            tracing::log::warn!(target: LOG_TARGET, "No archive endpoint for block {} on chain {}, fetching the blocks from the API", archive_block_height, self.chain.chain_id);
+            return self
+                .fetch_archive_blocks_from_api(archive_block_height)
+                .await;
         };
+        let (prefix, source) = (&archive.url, archive.source);
         let url = format!("{}{}", prefix, suffix);
         tracing::log::debug!(target: LOG_TARGET, "#{}: Fetching archive url: {}", archive_block_height, url);
+        let start_time = Instant::now();
+        let mut num_retries = 0;
         while self.is_running.load(Ordering::SeqCst) {
             match self.fetch_archive(&url).await {
-                Ok(Some(archive)) => match self.parse_archive(archive) {
-                    Ok(blocks) => return Ok(blocks),
+                Ok(Some(archive)) => match self.parse_archive(&archive.value) {
+                    Ok(blocks) => {
+                        let arrival_time = SystemTime::now();
+                        let fetch_latency = start_time.elapsed();
+                        return Ok(blocks
+                            .into_iter()
+                            .map(|(block, uncompressed_size)| DeliveredBlock {
+                                block,
+                                source,
+                                endpoint: archive.url.clone(),
+                                fetch_latency,
+                                compressed_size: archive.num_bytes,
+                                uncompressed_size,
+                                num_retries,
+                                arrival_time,
+                            })
+                            .collect());
+                    }
                     Err(err) => {
                         tracing::log::warn!(target: LOG_TARGET, "Failed to parse archive {} : {}", url, err);
-                        tokio::time::sleep(
-                            self.config.retry_duration.unwrap_or(DEFAULT_RETRY_DURATION),
-                        )
-                        .await;
+                        tokio::time::sleep(self.retry_duration()).await;
                     }
                 },
                 Ok(None) => return Ok(Vec::new()),
-                Err(FetchError::ReqwestError(err)) => {
-                    tracing::log::warn!(target: LOG_TARGET, "Failed to fetch the archive: {}", err);
-                    tokio::time::sleep(
-                        self.config.retry_duration.unwrap_or(DEFAULT_RETRY_DURATION),
-                    )
-                    .await;
-                }
-                Err(FetchError::RedirectError) => {
-                    tracing::log::warn!(target: LOG_TARGET, "Redirect error");
-                    tokio::time::sleep(
-                        self.config.retry_duration.unwrap_or(DEFAULT_RETRY_DURATION),
-                    )
-                    .await;
+                Err(err) => {
+                    tracing::log::warn!(target: LOG_TARGET, "Failed to fetch the archive {} : {}", url, err);
+                    tokio::time::sleep(self.retry_duration()).await;
                 }
             }
+            num_retries += 1;
         }
         Err(InterruptedError)
     }
+
+    /// Fetches the blocks of the archive one by one from the API, for the heights that no archive
+    /// endpoint covers, e.g. a gap in the archives or an endpoint that requires auth without the
+    /// token. Returns the blocks sorted by height, like `fetch_blocks_from_archive`.
+    async fn fetch_archive_blocks_from_api(
+        &self,
+        archive_block_height: BlockHeight,
+    ) -> InterruptibleResult<Vec<DeliveredBlock>> {
+        let mut blocks = Vec::new();
+        for block_height in
+            archive_block_height..archive_block_height + NUMBER_OF_BLOCKS_PER_ARCHIVE
+        {
+            blocks.extend(
+                self.fetch_block_by_height(block_height, &self.config.finality)
+                    .await?,
+            );
+        }
+        Ok(blocks)
+    }
 }
 
 async fn archive_sync(
     fetcher: &Fetcher,
-    blocks_sink: mpsc::Sender<BlockWithTxHashes>,
+    blocks_sink: BlocksSink,
     start_block_height: BlockHeight,
     end_block_height: BlockHeight,
     next_sink_block: Arc<AtomicU64>,
@@ -250,6 +668,8 @@ async fn archive_sync(
     let next_fetch_archive_height = Arc::new(AtomicU64::new(
         start_block_height / NUMBER_OF_BLOCKS_PER_ARCHIVE * NUMBER_OF_BLOCKS_PER_ARCHIVE,
     ));
+    // The lowest archive that can't be delivered during the shutdown
+    let undelivered_archive_height = Arc::new(AtomicU64::new(u64::MAX));
     // starting backfill with multiple threads
     let handles = (0..fetcher.config.num_threads)
             .map(|thread_index| {
@@ -257,6 +677,7 @@ async fn archive_sync(
                 let blocks_sink = blocks_sink.clone();
                 let next_fetch_archive_height = next_fetch_archive_height.clone();
                 let next_sink_block = next_sink_block.clone();
+                let undelivered_archive_height = undelivered_archive_height.clone();
                 tokio::spawn(async move {
                     while fetcher.is_running.load(Ordering::SeqCst) {
                         let archive_block_height = next_fetch_archive_height.fetch_add(NUMBER_OF_BLOCKS_PER_ARCHIVE, Ordering::SeqCst);
@@ -266,30 +687,32 @@ async fn archive_sync(
                         tracing::log::debug!(target: LOG_TARGET, "#{}: Fetching archive: {}", thread_index, archive_block_height);
                         let blocks =
                             fetcher.fetch_blocks_from_archive(archive_block_height).await;
-                        let mut expected_block_height = 0;
-                        while fetcher.is_running.load(Ordering::SeqCst) {
-                            expected_block_height = next_sink_block.load(Ordering::SeqCst);
-                            if expected_block_height < archive_block_height {
-                                tokio::time::sleep(Duration::from_millis(
-                                    (archive_block_height - expected_block_height + NUMBER_OF_BLOCKS_PER_ARCHIVE - 1) / NUMBER_OF_BLOCKS_PER_ARCHIVE * NUMBER_OF_BLOCKS_PER_ARCHIVE,
-                                ))
-                                    .await;
-                            } else {
-                                tracing::log::debug!(target: LOG_TARGET, "#{}: Sending blocks from archive: {}", thread_index, archive_block_height);
-                                break;
+                        let is_turn = blocks.is_ok() && fetcher.wait_for_turn(|| {
+                            if archive_block_height >= undelivered_archive_height.load(Ordering::SeqCst) {
+                                return None;
                             }
-                        }
-                        if !fetcher.is_running.load(Ordering::SeqCst) {
+                            let expected_block_height = next_sink_block.load(Ordering::SeqCst);
+                            Some(archive_block_height.saturating_sub(expected_block_height).div_ceil(NUMBER_OF_BLOCKS_PER_ARCHIVE) * NUMBER_OF_BLOCKS_PER_ARCHIVE)
+                        }).await;
+                        let (true, Ok(blocks)) = (is_turn, blocks) else {
+                            undelivered_archive_height.fetch_min(archive_block_height, Ordering::SeqCst);
                             break;
-                        }
-                        for block in blocks.expect("Can't be interrupted error") {
+                        };
+                        tracing::log::debug!(target: LOG_TARGET, "#{}: Sending blocks from archive: {}", thread_index, archive_block_height);
+                        let expected_block_height = next_sink_block.load(Ordering::SeqCst);
+                        for block in blocks {
+                            let block_height = block.block_height();
                             // Skipping initial blocks from archive
-                            if block.block.header.height < expected_block_height {
+                            if block_height < expected_block_height {
                                 continue;
                             }
-                            blocks_sink.send(block).await.expect("Failed to send block");
+                            if !fetcher.deliver(&blocks_sink, block).await {
+                                undelivered_archive_height.fetch_min(archive_block_height, Ordering::SeqCst);
+                                return;
+                            }
+                            next_sink_block.store(block_height + 1, Ordering::SeqCst);
                         }
-                        next_sink_block.swap(archive_block_height + NUMBER_OF_BLOCKS_PER_ARCHIVE, Ordering::SeqCst);
+                        next_sink_block.store(archive_block_height + NUMBER_OF_BLOCKS_PER_ARCHIVE, Ordering::SeqCst);
                     }
                 })
             })
@@ -299,20 +722,190 @@ async fn archive_sync(
     }
 }
 
+/// Fetches blocks in descending order from `start_block_height` down to `end_block_height`
+/// (both inclusive). Blocks below `archive_block_height` are fetched from the archives, the rest
+/// are fetched one by one. Stops once the receiver of the blocks sink is dropped.
+/// Returns the lowest height down to which all blocks were delivered.
+async fn descending_sync(
+    fetcher: &Fetcher,
+    blocks_sink: BlocksSink,
+    start_block_height: BlockHeight,
+    end_block_height: BlockHeight,
+    archive_block_height: BlockHeight,
+) -> Option<BlockHeight> {
+    tracing::log::info!(
+        target: LOG_TARGET,
+        "Start descending sync from block {} to block {} with {} threads.",
+        start_block_height,
+        end_block_height,
+        fetcher.config.num_threads
+    );
+    // All blocks at this height and above were sent
+    let next_sink_block_end = Arc::new(AtomicU64::new(start_block_height + 1));
+    // The blocks below this height can't be delivered during the shutdown
+    let undelivered_block_end = Arc::new(AtomicU64::new(0));
+    let blocks_end = std::cmp::max(archive_block_height, end_block_height);
+    if start_block_height >= blocks_end {
+        let next_fetch_block_end = Arc::new(AtomicU64::new(start_block_height + 1));
+        let handles = (0..fetcher.config.num_threads)
+            .map(|thread_index| {
+                let fetcher = fetcher.clone();
+                let blocks_sink = blocks_sink.clone();
+                let next_fetch_block_end = next_fetch_block_end.clone();
+                let next_sink_block_end = next_sink_block_end.clone();
+                let undelivered_block_end = undelivered_block_end.clone();
+                tokio::spawn(async move {
+                    while fetcher.is_running.load(Ordering::SeqCst) && !blocks_sink.is_closed() {
+                        let Ok(block_end) = next_fetch_block_end.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |h| h.checked_sub(1)) else {
+                            break;
+                        };
+                        let block_height = block_end - 1;
+                        if block_height < blocks_end {
+                            break;
+                        }
+                        tracing::log::debug!(target: LOG_TARGET, "#{}: Fetching block: {}", thread_index, block_height);
+                        let block =
+                            fetcher.fetch_block_by_height(block_height, &fetcher.config.finality).await;
+                        let is_turn = block.is_ok() && fetcher.wait_for_turn(|| {
+                            if block_end < undelivered_block_end.load(Ordering::SeqCst) {
+                                return None;
+                            }
+                            Some(next_sink_block_end.load(Ordering::SeqCst).saturating_sub(block_end))
+                        }).await;
+                        let (true, Ok(block)) = (is_turn, block) else {
+                            undelivered_block_end.fetch_max(block_end, Ordering::SeqCst);
+                            break;
+                        };
+                        if let Some(block) = block {
+                            if !fetcher.deliver(&blocks_sink, block).await {
+                                undelivered_block_end.fetch_max(block_end, Ordering::SeqCst);
+                                break;
+                            }
+                        } else {
+                            tracing::log::debug!(target: LOG_TARGET, "#{}: Skipped block: {}", thread_index, block_height);
+                        }
+                        next_sink_block_end.store(block_height, Ordering::SeqCst);
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for handle in handles {
+            handle.await.expect("Failed to join fetching thread");
+        }
+    }
+    if fetcher.is_archive_sync_enabled() {
+        let next_fetch_archive_end = Arc::new(AtomicU64::new(
+            next_sink_block_end
+                .load(Ordering::SeqCst)
+                .div_ceil(NUMBER_OF_BLOCKS_PER_ARCHIVE)
+                * NUMBER_OF_BLOCKS_PER_ARCHIVE,
+        ));
+        let handles = (0..fetcher.config.num_threads)
+            .map(|thread_index| {
+                let fetcher = fetcher.clone();
+                let blocks_sink = blocks_sink.clone();
+                let next_fetch_archive_end = next_fetch_archive_end.clone();
+                let next_sink_block_end = next_sink_block_end.clone();
+                let undelivered_block_end = undelivered_block_end.clone();
+                tokio::spawn(async move {
+                    while fetcher.is_running.load(Ordering::SeqCst) && !blocks_sink.is_closed() {
+                        let Ok(archive_end) = next_fetch_archive_end.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |h| h.checked_sub(NUMBER_OF_BLOCKS_PER_ARCHIVE)) else {
+                            break;
+                        };
+                        if archive_end <= end_block_height {
+                            break;
+                        }
+                        let archive_block_height = archive_end - NUMBER_OF_BLOCKS_PER_ARCHIVE;
+                        tracing::log::debug!(target: LOG_TARGET, "#{}: Fetching archive: {}", thread_index, archive_block_height);
+                        let blocks = fetcher.fetch_blocks_from_archive(archive_block_height).await;
+                        let is_turn = blocks.is_ok() && fetcher.wait_for_turn(|| {
+                            if archive_end < undelivered_block_end.load(Ordering::SeqCst) {
+                                return None;
+                            }
+                            let expected_block_end = next_sink_block_end.load(Ordering::SeqCst);
+                            Some(expected_block_end.saturating_sub(archive_end).div_ceil(NUMBER_OF_BLOCKS_PER_ARCHIVE) * NUMBER_OF_BLOCKS_PER_ARCHIVE)
+                        }).await;
+                        let (true, Ok(blocks)) = (is_turn, blocks) else {
+                            undelivered_block_end.fetch_max(archive_end, Ordering::SeqCst);
+                            break;
+                        };
+                        tracing::log::debug!(target: LOG_TARGET, "#{}: Sending blocks from archive: {}", thread_index, archive_block_height);
+                        let expected_block_end = next_sink_block_end.load(Ordering::SeqCst);
+                        for block in blocks.into_iter().rev() {
+                            let block_height = block.block_height();
+                            // Skipping the blocks that were already sent or are below the end
+                            if block_height >= expected_block_end || block_height < end_block_height {
+                                continue;
+                            }
+                            if !fetcher.deliver(&blocks_sink, block).await {
+                                undelivered_block_end.fetch_max(archive_end, Ordering::SeqCst);
+                                return;
+                            }
+                            next_sink_block_end.store(block_height, Ordering::SeqCst);
+                        }
+                        next_sink_block_end.store(std::cmp::max(archive_block_height, end_block_height), Ordering::SeqCst);
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for handle in handles {
+            handle.await.expect("Failed to join fetching thread");
+        }
+    }
+    let next_sink_block_end = next_sink_block_end.load(Ordering::SeqCst);
+    (next_sink_block_end <= start_block_height).then_some(next_sink_block_end)
+}
+
+/// Starts fetching blocks into the sink until the end block height is reached or `is_running`
+/// is set to `false`. After that, the in-flight blocks are delivered until the shutdown timeout.
 pub async fn start_fetcher(
     config: FetcherConfig,
     blocks_sink: mpsc::Sender<BlockWithTxHashes>,
     is_running: Arc<AtomicBool>,
-) {
+) -> ShutdownReport {
+    run_fetcher(config, BlocksSink::Blocks(blocks_sink), is_running).await
+}
+
+/// Same as `start_fetcher`, but sends the blocks with the delivery metadata.
+pub async fn start_fetcher_with_metadata(
+    config: FetcherConfig,
+    blocks_sink: mpsc::Sender<DeliveredBlock>,
+    is_running: Arc<AtomicBool>,
+) -> ShutdownReport {
+    run_fetcher(config, BlocksSink::DeliveredBlocks(blocks_sink), is_running).await
+}
+
+async fn run_fetcher(
+    config: FetcherConfig,
+    blocks_sink: BlocksSink,
+    is_running: Arc<AtomicBool>,
+) -> ShutdownReport {
     let client = ClientBuilder::new()
         .redirect(reqwest::redirect::Policy::none())
         .build()
         .unwrap();
-    let fetcher = Fetcher {
+    let chain = match &config.base_url {
+        Some(base_url) => ChainConfig::from_base_url(config.chain_id.clone(), base_url),
+        None => config
+            .chains
+            .get(&config.chain_id)
+            .unwrap_or_else(|| panic!("Chain {} is not registered", config.chain_id))
+            .clone(),
+    };
+    let mut fetcher = Fetcher {
         client,
         config,
+        chain: Arc::new(chain),
         is_running,
+        shutdown_deadline: Arc::new(OnceLock::new()),
     };
+    if let Some(manifest) = fetcher.load_archive_manifest().await {
+        tracing::log::info!(target: LOG_TARGET, "Loaded the archive manifest with {} archives", manifest.archives.len());
+        fetcher.chain = Arc::new(ChainConfig {
+            archives: manifest.archives,
+            ..(*fetcher.chain).clone()
+        });
+    }
     let max_num_threads = fetcher.config.num_threads;
     let start_block_height = if let Some(start_block_height) = fetcher.config.start_block_height {
         start_block_height
@@ -321,7 +914,7 @@ pub async fn start_fetcher(
             .fetch_last_block_headers(&fetcher.config.finality)
             .await;
         if let Err(InterruptedError) = last_block_height {
-            return;
+            return ShutdownReport::default();
         }
         last_block_height
             .unwrap()
@@ -329,9 +922,38 @@ pub async fn start_fetcher(
             .header
             .height
     };
-    let end_block_height = fetcher.config.end_block_height.unwrap_or(std::u64::MAX);
+    if fetcher.config.descending {
+        let last_block_height = fetcher
+            .fetch_last_block_headers(&fetcher.config.finality)
+            .await;
+        if let Err(InterruptedError) = last_block_height {
+            return ShutdownReport::default();
+        }
+        let last_block_height = last_block_height
+            .unwrap()
+            .expect("Last block doesn't exist")
+            .header
+            .height;
+        let archive_block_height = if !fetcher.is_archive_sync_enabled() {
+            0
+        } else {
+            last_block_height / NUMBER_OF_BLOCKS_PER_ARCHIVE * NUMBER_OF_BLOCKS_PER_ARCHIVE
+        };
+        let last_delivered_block_height = descending_sync(
+            &fetcher,
+            blocks_sink,
+            std::cmp::min(start_block_height, last_block_height),
+            fetcher.config.end_block_height.unwrap_or(0),
+            archive_block_height,
+        )
+        .await;
+        return ShutdownReport {
+            last_delivered_block_height,
+        };
+    }
+    let end_block_height = fetcher.config.end_block_height.unwrap_or(u64::MAX);
     let next_sink_block = Arc::new(AtomicU64::new(start_block_height));
-    while fetcher.is_running.load(Ordering::SeqCst) {
+    while fetcher.is_running.load(Ordering::SeqCst) && !blocks_sink.is_closed() {
         let start_block_height = next_sink_block.load(Ordering::SeqCst);
         if start_block_height > end_block_height {
             break;
@@ -350,7 +972,7 @@ pub async fn start_fetcher(
         let last_block_height = std::cmp::min(last_block_height, end_block_height);
         let rounded_last_block_height =
             last_block_height / NUMBER_OF_BLOCKS_PER_ARCHIVE * NUMBER_OF_BLOCKS_PER_ARCHIVE;
-        if !fetcher.config.disable_archive_sync
+        if fetcher.is_archive_sync_enabled()
             && rounded_last_block_height > start_block_height + ARCHIVE_SYNC_THRESHOLD
         {
             archive_sync(
@@ -364,8 +986,23 @@ pub async fn start_fetcher(
             continue;
         }
         let next_fetch_block = Arc::new(AtomicU64::new(start_block_height));
+        // The lowest block that can't be delivered during the shutdown
+        let undelivered_block_height = Arc::new(AtomicU64::new(u64::MAX));
         let is_backfill = last_block_height > start_block_height + max_num_threads;
-        let num_threads = if is_backfill { max_num_threads } else { 1 };
+        let is_tip_following = !is_backfill && fetcher.config.tip_following;
+        let num_threads = if is_backfill {
+            max_num_threads
+        } else if is_tip_following {
+            // Speculatively requesting the next blocks, while the current one is not produced yet
+            1 + TIP_PREFETCH_BLOCKS
+        } else {
+            1
+        };
+        let fetcher = if is_tip_following {
+            fetcher.with_tip_retry_duration()
+        } else {
+            fetcher.clone()
+        };
         tracing::log::info!(
             target: LOG_TARGET,
             "Start fetching from block {} to block {} with {} threads. Backfill: {:?}",
@@ -381,6 +1018,7 @@ pub async fn start_fetcher(
                 let blocks_sink = blocks_sink.clone();
                 let next_fetch_block = next_fetch_block.clone();
                 let next_sink_block = next_sink_block.clone();
+                let undelivered_block_height = undelivered_block_height.clone();
                 tokio::spawn(async move {
                     while fetcher.is_running.load(Ordering::SeqCst) {
                         let block_height = next_fetch_block.fetch_add(1, Ordering::SeqCst);
@@ -390,23 +1028,26 @@ pub async fn start_fetcher(
                         tracing::log::debug!(target: LOG_TARGET, "#{}: Fetching block: {}", thread_index, block_height);
                         let block =
                             fetcher.fetch_block_by_height(block_height, &fetcher.config.finality).await;
-                        while fetcher.is_running.load(Ordering::SeqCst) {
-                            let expected_block_height = next_sink_block.load(Ordering::SeqCst);
-                            if expected_block_height < block_height {
-                                tokio::time::sleep(Duration::from_millis(
-                                    block_height - expected_block_height,
-                                ))
-                                .await;
-                            } else {
-                                tracing::log::debug!(target: LOG_TARGET, "#{}: Sending block: {}", thread_index, block_height);
-                                break;
+                        let is_turn = block.is_ok() && fetcher.wait_for_turn(|| {
+                            if block_height >= undelivered_block_height.load(Ordering::SeqCst) {
+                                return None;
                             }
-                        }
-                        if !fetcher.is_running.load(Ordering::SeqCst) {
+                            Some(block_height.saturating_sub(next_sink_block.load(Ordering::SeqCst)))
+                        }).await;
+                        let (true, Ok(block)) = (is_turn, block) else {
+                            undelivered_block_height.fetch_min(block_height, Ordering::SeqCst);
                             break;
-                        }
-                        if let Some(block) = block.expect("Can't be interrupted error") {
-                            blocks_sink.send(block).await.expect("Failed to send block");
+                        };
+                        tracing::log::debug!(target: LOG_TARGET, "#{}: Sending block: {}", thread_index, block_height);
+                        if let Some(block) = block {
+                            let block_timestamp = block.block.block.header.timestamp_nanosec;
+                            if !fetcher.deliver(&blocks_sink, block).await {
+                                undelivered_block_height.fetch_min(block_height, Ordering::SeqCst);
+                                break;
+                            }
+                            if is_tip_following {
+                                fetcher.report_tip_latency(block_height, block_timestamp);
+                            }
                         } else {
                             tracing::log::debug!(target: LOG_TARGET, "#{}: Skipped block: {}", thread_index, block_height);
                         }
@@ -419,4 +1060,11 @@ pub async fn start_fetcher(
             handle.await.expect("Failed to join fetching thread");
         }
     }
+    let next_sink_block = next_sink_block.load(Ordering::SeqCst);
+    let report = ShutdownReport {
+        last_delivered_block_height: (next_sink_block > start_block_height)
+            .then(|| next_sink_block - 1),
+    };
+    tracing::log::info!(target: LOG_TARGET, "Fetcher stopped: {:?}", report);
+    report
 }
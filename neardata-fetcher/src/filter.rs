@@ -0,0 +1,243 @@
+use crate::*;
+
+use fastnear_primitives::block_with_tx_hash::IndexerExecutionOutcomeWithReceiptAndTxHash;
+use fastnear_primitives::near_indexer_primitives::{CryptoHash, IndexerTransactionWithOutcome};
+use fastnear_primitives::near_primitives::types::AccountId;
+use fastnear_primitives::near_primitives::views::{ReceiptView, StateChangeWithCauseView};
+use fastnear_primitives::select::BlockSelector;
+use fastnear_primitives::utils::state_change_account_id;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashSet};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BlockFilterMode {
+    /// Drops the blocks that don't match the filter. Matching blocks are kept intact.
+    Drop,
+    /// Drops the blocks that don't match the filter and removes the transactions, receipts,
+    /// execution outcomes and state changes that don't involve the filtered accounts.
+    Thin,
+}
+
+/// Filters blocks by the accounts they touch.
+/// A block matches if any of the following accounts is allowed by the filter:
+/// - transaction signer or receiver
+/// - receipt predecessor or receiver
+/// - state change account
+///
+/// Note, the filtered blocks don't form a chain, so they can't be applied to the flat state.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlockFilter {
+    pub accounts: HashSet<AccountId>,
+    pub account_ranges: Vec<(Option<AccountId>, Option<AccountId>)>,
+    pub mode: BlockFilterMode,
+}
+
+/// A lightweight summary of a block that matched the filter.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MatchedBlock {
+    pub block_height: BlockHeight,
+    pub block_hash: CryptoHash,
+    pub block_timestamp: u64,
+    /// The allowed accounts found in the block
+    pub accounts: BTreeSet<AccountId>,
+    /// The hashes of the matching transactions
+    pub tx_hashes: Vec<CryptoHash>,
+    /// The IDs of the matching executed receipts
+    pub receipt_ids: Vec<CryptoHash>,
+}
+
+impl BlockFilter {
+    pub fn from_accounts(accounts: &[AccountId], mode: BlockFilterMode) -> Self {
+        Self {
+            accounts: accounts.iter().cloned().collect(),
+            account_ranges: Vec::new(),
+            mode,
+        }
+    }
+
+    pub fn is_account_allowed(&self, account: &AccountId) -> bool {
+        if self.accounts.contains(account) {
+            return true;
+        }
+        self.account_ranges.iter().any(|(start, end)| {
+            start.as_ref().is_none_or(|start| start <= account)
+                && end.as_ref().is_none_or(|end| end >= account)
+        })
+    }
+
+    fn is_any_account_allowed(&self, accounts: &[&AccountId]) -> bool {
+        accounts
+            .iter()
+            .any(|account| self.is_account_allowed(account))
+    }
+
+    fn transaction_matches(&self, tx: &IndexerTransactionWithOutcome) -> bool {
+        self.is_any_account_allowed(&[&tx.transaction.signer_id, &tx.transaction.receiver_id])
+    }
+
+    fn receipt_matches(&self, receipt: &ReceiptView) -> bool {
+        self.is_any_account_allowed(&[&receipt.predecessor_id, &receipt.receiver_id])
+    }
+
+    fn state_change_matches(&self, state_change: &StateChangeWithCauseView) -> bool {
+        self.is_account_allowed(state_change_account_id(&state_change.value))
+    }
+
+    pub fn matches(&self, block: &BlockWithTxHashes) -> bool {
+        block.shards.iter().any(|shard| {
+            shard.chunk.iter().any(|chunk| {
+                chunk
+                    .transactions
+                    .iter()
+                    .any(|tx| self.transaction_matches(tx))
+                    || chunk
+                        .receipts
+                        .iter()
+                        .any(|receipt| self.receipt_matches(receipt))
+            }) || shard
+                .receipt_execution_outcomes
+                .iter()
+                .any(|outcome| self.receipt_matches(&outcome.receipt))
+                || shard
+                    .state_changes
+                    .iter()
+                    .any(|state_change| self.state_change_matches(state_change))
+        })
+    }
+
+    /// Applies the filter according to the mode. Returns `None` if the block doesn't match.
+    pub fn apply(&self, mut block: BlockWithTxHashes) -> Option<BlockWithTxHashes> {
+        if !self.matches(&block) {
+            return None;
+        }
+        if self.mode == BlockFilterMode::Thin {
+            for shard in &mut block.shards {
+                if let Some(chunk) = shard.chunk.as_mut() {
+                    chunk.transactions.retain(|tx| self.transaction_matches(tx));
+                    chunk
+                        .receipts
+                        .retain(|receipt| self.receipt_matches(receipt));
+                }
+                shard
+                    .receipt_execution_outcomes
+                    .retain(|outcome| self.receipt_matches(&outcome.receipt));
+                shard
+                    .state_changes
+                    .retain(|state_change| self.state_change_matches(state_change));
+            }
+        }
+        Some(block)
+    }
+
+    /// Returns the summary of the block if it matches the filter.
+    pub fn summarize(&self, block: &BlockWithTxHashes) -> Option<MatchedBlock> {
+        let mut matched_block = MatchedBlock {
+            block_height: block.block.header.height,
+            block_hash: block.block.header.hash,
+            block_timestamp: block.block.header.timestamp_nanosec,
+            accounts: BTreeSet::new(),
+            tx_hashes: Vec::new(),
+            receipt_ids: Vec::new(),
+        };
+        let mut add_allowed = |accounts: &[&AccountId]| {
+            let mut is_matched = false;
+            for account in accounts {
+                if self.is_account_allowed(account) {
+                    matched_block.accounts.insert((*account).clone());
+                    is_matched = true;
+                }
+            }
+            is_matched
+        };
+        let mut tx_hashes = Vec::new();
+        let mut receipt_ids = Vec::new();
+        for shard in &block.shards {
+            if let Some(chunk) = &shard.chunk {
+                for tx in &chunk.transactions {
+                    if add_allowed(&[&tx.transaction.signer_id, &tx.transaction.receiver_id]) {
+                        tx_hashes.push(tx.transaction.hash);
+                    }
+                }
+                for receipt in &chunk.receipts {
+                    add_allowed(&[&receipt.predecessor_id, &receipt.receiver_id]);
+                }
+            }
+            for outcome in &shard.receipt_execution_outcomes {
+                if add_allowed(&[
+                    &outcome.receipt.predecessor_id,
+                    &outcome.receipt.receiver_id,
+                ]) {
+                    receipt_ids.push(outcome.receipt.receipt_id);
+                }
+            }
+            for state_change in &shard.state_changes {
+                add_allowed(&[state_change_account_id(&state_change.value)]);
+            }
+        }
+        if matched_block.accounts.is_empty() {
+            return None;
+        }
+        matched_block.tx_hashes = tx_hashes;
+        matched_block.receipt_ids = receipt_ids;
+        Some(matched_block)
+    }
+}
+
+/// In the `Thin` mode, the fetcher removes the parts of the blocks that don't match while decoding
+/// them. In the `Drop` mode, everything is kept, since a matching block is delivered intact.
+impl BlockSelector for BlockFilter {
+    fn select_transaction(&self, transaction: &IndexerTransactionWithOutcome) -> bool {
+        self.mode == BlockFilterMode::Drop || self.transaction_matches(transaction)
+    }
+
+    fn select_receipt(&self, receipt: &ReceiptView) -> bool {
+        self.mode == BlockFilterMode::Drop || self.receipt_matches(receipt)
+    }
+
+    fn select_outcome(&self, outcome: &IndexerExecutionOutcomeWithReceiptAndTxHash) -> bool {
+        self.mode == BlockFilterMode::Drop || self.receipt_matches(&outcome.receipt)
+    }
+
+    fn select_state_change(&self, state_change: &StateChangeWithCauseView) -> bool {
+        self.mode == BlockFilterMode::Drop || self.state_change_matches(state_change)
+    }
+}
+
+/// Starts the fetcher with the given filter and only sends the summaries of the matching blocks.
+/// The filter mode is ignored.
+/// The report counts the summaries received by the consumer. Once the consumer drops the
+/// receiver, the fetcher is stopped and the last delivered summary is reported.
+pub async fn start_matched_block_fetcher(
+    mut config: FetcherConfig,
+    filter: BlockFilter,
+    matched_blocks_sink: mpsc::Sender<MatchedBlock>,
+    is_running: Arc<AtomicBool>,
+) -> ShutdownReport {
+    // Thinning the blocks early to reduce the memory usage between the fetcher and the summary
+    config.filter = Some(BlockFilter {
+        mode: BlockFilterMode::Thin,
+        ..filter.clone()
+    });
+    let (blocks_sink, mut blocks_stream) = mpsc::channel(1);
+    let fetcher_handle = tokio::spawn(start_fetcher(config, blocks_sink, is_running.clone()));
+    let mut last_delivered_block_height = None;
+    while let Some(block) = blocks_stream.recv().await {
+        let Some(matched_block) = filter.summarize(&block) else {
+            continue;
+        };
+        let block_height = matched_block.block_height;
+        if matched_blocks_sink.send(matched_block).await.is_err() {
+            // The receiver is gone, stopping the fetcher without delivering the remaining blocks
+            is_running.store(false, Ordering::SeqCst);
+            drop(blocks_stream);
+            fetcher_handle.await.expect("Failed to join fetcher");
+            return ShutdownReport {
+                last_delivered_block_height,
+            };
+        }
+        last_delivered_block_height = Some(block_height);
+    }
+    // All blocks of the fetcher were delivered, including the skipped and filtered out ones
+    fetcher_handle.await.expect("Failed to join fetcher")
+}
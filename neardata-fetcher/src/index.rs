@@ -0,0 +1,157 @@
+use crate::*;
+
+use fastnear_primitives::near_indexer_primitives::CryptoHash;
+use std::collections::{HashMap, VecDeque};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::sync::RwLock;
+
+/// Size of a single entry in the saved index: 32 bytes of the hash and 8 bytes of the height.
+const ENTRY_SIZE: usize = 40;
+
+/// The default maximum number of entries in the index, one per block. That's about 7 to 14 days of
+/// mainnet blocks at 0.6 to 1.2 seconds per block.
+pub const DEFAULT_MAX_BLOCK_HASH_INDEX_LEN: usize = 1_000_000;
+
+/// A local index from block hashes to block heights.
+/// The fetcher fills the index with every block it decodes, including the previous block hash of
+/// each block, so the blocks that were seen by the process, or loaded from a saved index, can be
+/// resolved without an RPC node. Other hashes can't be resolved.
+/// Once the index is full, the oldest entries are evicted.
+/// The index is cheap to clone and all clones share the same data.
+#[derive(Debug, Clone)]
+pub struct BlockHashIndex {
+    inner: Arc<RwLock<IndexInner>>,
+}
+
+#[derive(Debug)]
+struct IndexInner {
+    heights: HashMap<CryptoHash, BlockHeight>,
+    /// The hashes in the order of insertion, for the eviction
+    hashes: VecDeque<CryptoHash>,
+    max_len: usize,
+}
+
+impl IndexInner {
+    fn insert(&mut self, block_hash: CryptoHash, block_height: BlockHeight) {
+        if self.heights.insert(block_hash, block_height).is_some() {
+            return;
+        }
+        self.hashes.push_back(block_hash);
+        while self.hashes.len() > self.max_len {
+            if let Some(block_hash) = self.hashes.pop_front() {
+                self.heights.remove(&block_hash);
+            }
+        }
+    }
+}
+
+/// The block hash is not in the index.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NotIndexedError(pub CryptoHash);
+
+impl std::fmt::Display for NotIndexedError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Block hash {} is not indexed", self.0)
+    }
+}
+
+impl std::error::Error for NotIndexedError {}
+
+impl Default for BlockHashIndex {
+    fn default() -> Self {
+        Self::with_max_len(DEFAULT_MAX_BLOCK_HASH_INDEX_LEN)
+    }
+}
+
+impl BlockHashIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The index that keeps at most `max_len` entries, evicting the oldest ones.
+    pub fn with_max_len(max_len: usize) -> Self {
+        Self {
+            inner: Arc::new(RwLock::new(IndexInner {
+                heights: HashMap::new(),
+                hashes: VecDeque::new(),
+                max_len,
+            })),
+        }
+    }
+
+    pub fn insert(&self, block_hash: CryptoHash, block_height: BlockHeight) {
+        self.inner.write().unwrap().insert(block_hash, block_height);
+    }
+
+    pub fn insert_block(&self, block: &BlockWithTxHashes) {
+        let header = &block.block.header;
+        let mut inner = self.inner.write().unwrap();
+        if let Some(prev_height) = header.prev_height {
+            inner.insert(header.prev_hash, prev_height);
+        }
+        inner.insert(header.hash, header.height);
+    }
+
+    /// The height of the block, if the hash is in the index.
+    pub fn get(&self, block_hash: &CryptoHash) -> Result<BlockHeight, NotIndexedError> {
+        self.inner
+            .read()
+            .unwrap()
+            .heights
+            .get(block_hash)
+            .copied()
+            .ok_or(NotIndexedError(*block_hash))
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.read().unwrap().heights.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inner.read().unwrap().heights.is_empty()
+    }
+
+    /// Saves the index to a file, so it can be loaded for offline lookups later.
+    pub fn save(&self, path: &str) -> std::io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        let inner = self.inner.read().unwrap();
+        for block_hash in &inner.hashes {
+            writer.write_all(block_hash.as_bytes())?;
+            writer.write_all(&inner.heights[block_hash].to_le_bytes())?;
+        }
+        writer.flush()
+    }
+
+    /// Loads the index from a file and merges it into this index. The loaded entries are older
+    /// than the existing ones for the eviction.
+    pub fn load(&self, path: &str) -> std::io::Result<()> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut content = Vec::new();
+        reader.read_to_end(&mut content)?;
+        if content.len() % ENTRY_SIZE != 0 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "Invalid block hash index file size",
+            ));
+        }
+        let mut inner = self.inner.write().unwrap();
+        let IndexInner {
+            heights, hashes, ..
+        } = &mut *inner;
+        let existing: Vec<_> = hashes
+            .drain(..)
+            .map(|block_hash| (block_hash, heights[&block_hash]))
+            .collect();
+        heights.clear();
+        for entry in content.chunks_exact(ENTRY_SIZE) {
+            let block_hash = CryptoHash(entry[..32].try_into().unwrap());
+            let block_height = BlockHeight::from_le_bytes(entry[32..].try_into().unwrap());
+            inner.insert(block_hash, block_height);
+        }
+        for (block_hash, block_height) in existing {
+            inner.insert(block_hash, block_height);
+        }
+        Ok(())
+    }
+}
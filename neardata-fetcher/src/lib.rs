@@ -7,7 +7,13 @@ use std::sync::Arc;
 use std::time::Duration;
 use tokio::sync::mpsc;
 
+pub mod broadcast;
+pub mod chains;
 pub mod fetcher;
+pub mod filter;
+pub mod index;
+#[cfg(feature = "test-support")]
+pub mod test_support;
 pub mod types;
 pub mod utils;
 
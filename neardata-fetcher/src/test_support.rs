@@ -0,0 +1,434 @@
+//! An in-process stand-in for the neardata service to test the fetcher without the network.
+//!
+//! The server serves the live API (`/v0/block/{h}`, `/v0/block_opt/{h}`, the per-shard endpoints
+//! and `/v0/last_block/*`) and the archives (`{prefix}/.../{h}.tgz`, e.g. under `/raw/`) from the
+//! fixture blocks. Faults can be injected per request path, and every request is recorded, so the
+//! tests can check what the fetcher asked for.
+//! Point the fetcher at the server with `FetcherConfigBuilder::base_url`.
+use crate::*;
+
+use fastnear_primitives::near_indexer_primitives::CryptoHash;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::io::Write;
+use std::net::SocketAddr;
+use std::sync::Mutex;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// The timestamp of the block at height 0, every next height is produced one second later.
+const GENESIS_TIMESTAMP_NANOSEC: u64 = 1_600_000_000_000_000_000;
+
+/// A fault to inject instead of (or before) the regular response.
+#[derive(Debug, Clone)]
+pub enum Fault {
+    /// Responds with `302 Found` to the given location, absolute or relative to the server
+    Redirect(String),
+    /// Responds with the given status code and an empty body
+    Status(u16),
+    /// Delays the regular response
+    Delay(Duration),
+    /// Responds with `200 OK` and a body that is neither JSON nor an archive
+    CorruptBody,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordedRequest {
+    pub path: String,
+    /// The value of the `Authorization` header, if any
+    pub authorization: Option<String>,
+}
+
+#[derive(Default)]
+struct State {
+    /// The fixture blocks, `None` for the skipped heights
+    blocks: BTreeMap<BlockHeight, Option<Value>>,
+    last_block_height: BlockHeight,
+    faults: HashMap<String, VecDeque<Fault>>,
+    /// Static files served as is, e.g. the archive manifest
+    files: HashMap<String, Vec<u8>>,
+    requests: Vec<RecordedRequest>,
+}
+
+struct Response {
+    status: u16,
+    location: Option<String>,
+    body: Vec<u8>,
+}
+
+impl Response {
+    fn ok(body: Vec<u8>) -> Self {
+        Self {
+            status: 200,
+            location: None,
+            body,
+        }
+    }
+
+    fn json(value: &Value) -> Self {
+        Self::ok(serde_json::to_vec(value).unwrap())
+    }
+
+    fn status(status: u16) -> Self {
+        Self {
+            status,
+            location: None,
+            body: Vec::new(),
+        }
+    }
+}
+
+/// The deterministic hash of the test block at the given height.
+pub fn test_block_hash(height: BlockHeight) -> CryptoHash {
+    CryptoHash::hash_bytes(&height.to_le_bytes())
+}
+
+/// Builds a minimal valid block at the given height in the JSON format of the neardata API.
+/// The block links to the previous block by its test hash and has `num_shards` empty shards.
+pub fn test_block(height: BlockHeight, prev_height: Option<BlockHeight>, num_shards: u64) -> Value {
+    let zero_hash = CryptoHash::default().to_string();
+    let prev_hash = prev_height
+        .map(|prev_height| test_block_hash(prev_height).to_string())
+        .unwrap_or_else(|| zero_hash.clone());
+    let timestamp_nanosec = GENESIS_TIMESTAMP_NANOSEC + height * 1_000_000_000;
+    json!({
+        "block": {
+            "author": "test.near",
+            "header": {
+                "height": height,
+                "prev_height": prev_height,
+                "epoch_id": zero_hash,
+                "next_epoch_id": zero_hash,
+                "hash": test_block_hash(height).to_string(),
+                "prev_hash": prev_hash,
+                "prev_state_root": zero_hash,
+                "block_body_hash": null,
+                "chunk_receipts_root": zero_hash,
+                "chunk_headers_root": zero_hash,
+                "chunk_tx_root": zero_hash,
+                "outcome_root": zero_hash,
+                "chunks_included": 0,
+                "challenges_root": zero_hash,
+                "timestamp": timestamp_nanosec,
+                "timestamp_nanosec": timestamp_nanosec.to_string(),
+                "random_value": zero_hash,
+                "validator_proposals": [],
+                "chunk_mask": vec![false; num_shards as usize],
+                "gas_price": "100000000",
+                "block_ordinal": height,
+                "rent_paid": "0",
+                "validator_reward": "0",
+                "total_supply": "0",
+                "challenges_result": [],
+                "last_final_block": prev_hash,
+                "last_ds_final_block": prev_hash,
+                "next_bp_hash": zero_hash,
+                "block_merkle_root": zero_hash,
+                "epoch_sync_data_hash": null,
+                "approvals": [],
+                "signature": format!("ed25519:{}", "1".repeat(64)),
+                "latest_protocol_version": 73,
+                "chunk_endorsements": null,
+            },
+            "chunks": [],
+        },
+        "shards": (0..num_shards)
+            .map(|shard_id| {
+                json!({
+                    "shard_id": shard_id,
+                    "chunk": null,
+                    "receipt_execution_outcomes": [],
+                    "state_changes": [],
+                })
+            })
+            .collect::<Vec<_>>(),
+    })
+}
+
+/// The in-process HTTP server. Stops when dropped.
+pub struct TestServer {
+    addr: SocketAddr,
+    state: Arc<Mutex<State>>,
+    handle: tokio::task::JoinHandle<()>,
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
+
+impl TestServer {
+    /// Starts the server on a random local port.
+    pub async fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("Failed to bind the test server");
+        let addr = listener.local_addr().unwrap();
+        let state = Arc::new(Mutex::new(State::default()));
+        let server_state = state.clone();
+        let handle = tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(handle_connection(stream, server_state.clone()));
+            }
+        });
+        Self {
+            addr,
+            state,
+            handle,
+        }
+    }
+
+    /// The base URL to pass to `FetcherConfigBuilder::base_url`.
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    pub fn port(&self) -> u16 {
+        self.addr.port()
+    }
+
+    /// Adds the chain of test blocks for the given heights, except the skipped ones. Each block
+    /// links to the previous non-skipped block. The last block height is moved to the end of the
+    /// range.
+    pub fn add_blocks(
+        &self,
+        heights: std::ops::RangeInclusive<BlockHeight>,
+        skipped: &[BlockHeight],
+        num_shards: u64,
+    ) {
+        let mut state = self.state.lock().unwrap();
+        let mut prev_height = heights.start().checked_sub(1);
+        for height in heights.clone() {
+            if skipped.contains(&height) {
+                state.blocks.insert(height, None);
+                continue;
+            }
+            state
+                .blocks
+                .insert(height, Some(test_block(height, prev_height, num_shards)));
+            prev_height = Some(height);
+        }
+        state.last_block_height = std::cmp::max(state.last_block_height, *heights.end());
+    }
+
+    /// Replaces the block at the given height. `None` makes the height skipped.
+    pub fn set_block(&self, height: BlockHeight, block: Option<Value>) {
+        self.state.lock().unwrap().blocks.insert(height, block);
+    }
+
+    /// Serves the content at the given path, e.g. an archive manifest.
+    pub fn add_file(&self, path: &str, content: Vec<u8>) {
+        self.state
+            .lock()
+            .unwrap()
+            .files
+            .insert(path.to_string(), content);
+    }
+
+    /// Sets the height of the last produced block. The blocks above it are not served yet.
+    pub fn set_last_block_height(&self, last_block_height: BlockHeight) {
+        self.state.lock().unwrap().last_block_height = last_block_height;
+    }
+
+    pub fn last_block_height(&self) -> BlockHeight {
+        self.state.lock().unwrap().last_block_height
+    }
+
+    /// Injects a fault for the next request to the given path. Faults for the same path are used
+    /// in the order they were added, one per request.
+    pub fn inject_fault(&self, path: &str, fault: Fault) {
+        self.state
+            .lock()
+            .unwrap()
+            .faults
+            .entry(path.to_string())
+            .or_default()
+            .push_back(fault);
+    }
+
+    /// Returns all requests received so far.
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.state.lock().unwrap().requests.clone()
+    }
+
+    /// Returns the number of requests to the given path.
+    pub fn num_requests(&self, path: &str) -> usize {
+        self.state
+            .lock()
+            .unwrap()
+            .requests
+            .iter()
+            .filter(|request| request.path == path)
+            .count()
+    }
+}
+
+/// The archive path for the given height, e.g. `/raw/000000/000/000000000010.tgz`.
+pub fn archive_path(archive_block_height: BlockHeight) -> String {
+    let padded_block_height = format!("{:0>12}", archive_block_height);
+    format!(
+        "/raw/{}/{}/{}.tgz",
+        &padded_block_height[..6],
+        &padded_block_height[6..9],
+        padded_block_height
+    )
+}
+
+async fn handle_connection(mut stream: TcpStream, state: Arc<Mutex<State>>) {
+    let mut request = Vec::new();
+    let mut buf = [0u8; 1024];
+    while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+        match stream.read(&mut buf).await {
+            Ok(0) | Err(_) => return,
+            Ok(n) => request.extend_from_slice(&buf[..n]),
+        }
+    }
+    let request = String::from_utf8_lossy(&request).to_string();
+    let mut lines = request.lines();
+    let Some(path) = lines
+        .next()
+        .and_then(|line| line.split_whitespace().nth(1))
+        .map(|path| path.to_string())
+    else {
+        return;
+    };
+    let authorization = lines
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("authorization"))
+        .map(|(_, value)| value.trim().to_string());
+
+    let fault = {
+        let mut state = state.lock().unwrap();
+        state.requests.push(RecordedRequest {
+            path: path.clone(),
+            authorization,
+        });
+        state
+            .faults
+            .get_mut(&path)
+            .and_then(|faults| faults.pop_front())
+    };
+    let response = match fault {
+        Some(Fault::Redirect(location)) => Response {
+            status: 302,
+            location: Some(location),
+            body: Vec::new(),
+        },
+        Some(Fault::Status(status)) => Response::status(status),
+        Some(Fault::CorruptBody) => Response::ok(b"corrupt".to_vec()),
+        Some(Fault::Delay(delay)) => {
+            tokio::time::sleep(delay).await;
+            route(&path, &state.lock().unwrap())
+        }
+        None => route(&path, &state.lock().unwrap()),
+    };
+
+    let mut head = format!(
+        "HTTP/1.1 {} {}\r\nContent-Length: {}\r\nConnection: close\r\n",
+        response.status,
+        reason_phrase(response.status),
+        response.body.len()
+    );
+    if let Some(location) = response.location {
+        head.push_str(&format!("Location: {}\r\n", location));
+    }
+    head.push_str("\r\n");
+    let _ = stream.write_all(head.as_bytes()).await;
+    let _ = stream.write_all(&response.body).await;
+    let _ = stream.shutdown().await;
+}
+
+fn reason_phrase(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        302 => "Found",
+        404 => "Not Found",
+        429 => "Too Many Requests",
+        500 => "Internal Server Error",
+        _ => "Unknown",
+    }
+}
+
+fn route(path: &str, state: &State) -> Response {
+    let path = path.split('?').next().unwrap_or_default();
+    let segments: Vec<&str> = path.trim_start_matches('/').split('/').collect();
+    match segments.as_slice() {
+        ["v0", "last_block", "final" | "optimistic", "headers"] => {
+            let block = state
+                .blocks
+                .range(..=state.last_block_height)
+                .rev()
+                .find_map(|(_, block)| block.as_ref());
+            Response::json(block.map_or(&Value::Null, |block| &block["block"]))
+        }
+        ["v0", "block" | "block_opt", height, rest @ ..] => {
+            let Ok(height) = height.parse::<BlockHeight>() else {
+                return Response::status(404);
+            };
+            if height > state.last_block_height {
+                return Response::status(404);
+            }
+            let block = state.blocks.get(&height).and_then(|block| block.as_ref());
+            match (block, rest) {
+                (None, []) | (None, ["headers"]) | (None, ["shard", _]) => {
+                    Response::json(&Value::Null)
+                }
+                (Some(block), []) => Response::json(block),
+                (Some(block), ["headers"]) => Response::json(&block["block"]),
+                (Some(block), ["shard", shard_id]) => Response::json(
+                    block["shards"]
+                        .as_array()
+                        .and_then(|shards| {
+                            shards
+                                .iter()
+                                .find(|shard| shard["shard_id"].as_u64() == shard_id.parse().ok())
+                        })
+                        .unwrap_or(&Value::Null),
+                ),
+                _ => Response::status(404),
+            }
+        }
+        [.., _, _, file_name] if file_name.ends_with(".tgz") => {
+            let Some(archive_block_height) = file_name
+                .strip_suffix(".tgz")
+                .and_then(|height| height.parse::<BlockHeight>().ok())
+            else {
+                return Response::status(404);
+            };
+            // The archive is only available once all of its blocks are produced
+            if archive_block_height + NUMBER_OF_BLOCKS_PER_ARCHIVE > state.last_block_height + 1 {
+                return Response::status(404);
+            }
+            Response::ok(build_archive(state, archive_block_height))
+        }
+        _ => match state.files.get(path) {
+            Some(content) => Response::ok(content.clone()),
+            None => Response::status(404),
+        },
+    }
+}
+
+fn build_archive(state: &State, archive_block_height: BlockHeight) -> Vec<u8> {
+    let encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+    let mut builder = tar::Builder::new(encoder);
+    let blocks = state
+        .blocks
+        .range(archive_block_height..archive_block_height + NUMBER_OF_BLOCKS_PER_ARCHIVE);
+    for (height, block) in blocks {
+        let Some(block) = block else {
+            continue;
+        };
+        let content = serde_json::to_vec(block).unwrap();
+        let mut header = tar::Header::new_gnu();
+        header.set_size(content.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, format!("{:0>12}.json", height), &content[..])
+            .unwrap();
+    }
+    let mut encoder = builder.into_inner().unwrap();
+    encoder.flush().unwrap();
+    encoder.finish().unwrap()
+}
@@ -1,7 +1,15 @@
 use crate::*;
 
-use fastnear_primitives::near_primitives::types::Finality;
-use std::time::Duration;
+use crate::chains::{ArchiveManifestSource, ChainConfig, ChainRegistry};
+use crate::filter::BlockFilter;
+use crate::index::BlockHashIndex;
+
+use fastnear_primitives::near_primitives::types::{Finality, ShardId};
+use reqwest::StatusCode;
+use std::collections::VecDeque;
+use std::fmt::Display;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
 
 pub type BlockResult = Result<Option<BlockWithTxHashes>, FetchError>;
 
@@ -9,6 +17,9 @@ pub type BlockResult = Result<Option<BlockWithTxHashes>, FetchError>;
 pub enum FetchError {
     ReqwestError(reqwest::Error),
     RedirectError,
+    /// The server responded with a non-success status code
+    StatusError(StatusCode),
+    DecodeError(serde_json::Error),
 }
 
 impl From<reqwest::Error> for FetchError {
@@ -17,6 +28,146 @@ impl From<reqwest::Error> for FetchError {
     }
 }
 
+impl From<serde_json::Error> for FetchError {
+    fn from(error: serde_json::Error) -> Self {
+        FetchError::DecodeError(error)
+    }
+}
+
+impl Display for FetchError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FetchError::ReqwestError(e) => write!(f, "Request error: {}", e),
+            FetchError::RedirectError => write!(f, "Redirect error"),
+            FetchError::StatusError(status) => write!(f, "Unexpected status: {}", status),
+            FetchError::DecodeError(e) => write!(f, "Decode error: {}", e),
+        }
+    }
+}
+
+/// Counters shared between the fetcher and the caller.
+#[derive(Debug, Default)]
+pub struct FetcherStats {
+    /// The total number of response body bytes downloaded, as received over the wire
+    pub bytes_downloaded: AtomicU64,
+    /// The total number of HTTP requests sent, including redirects
+    pub num_requests: AtomicU64,
+    /// The delay in milliseconds between the production of the last block (by the header
+    /// timestamp) and its delivery to the blocks sink. Only updated in the tip-following mode.
+    pub last_block_latency_ms: AtomicU64,
+    /// The production to delivery latencies of the recent blocks. Only updated in the
+    /// tip-following mode.
+    pub tip_latency: LatencyWindow,
+    /// The number of responses and archive entries that were decoded only by the tolerant
+    /// decoding, i.e. had unknown fields or elements
+    pub num_tolerant_decodes: AtomicU64,
+    /// The total number of unknown fields and elements found by the tolerant decoding
+    pub num_unknown_values: AtomicU64,
+}
+
+/// The default number of the recent blocks in the latency window
+pub const DEFAULT_LATENCY_WINDOW_LEN: usize = 100;
+
+/// The latencies of the last blocks, in milliseconds.
+#[derive(Debug)]
+pub struct LatencyWindow {
+    latencies_ms: Mutex<VecDeque<u64>>,
+    max_len: usize,
+}
+
+/// The latency statistics over the blocks in the window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LatencySummary {
+    pub num_blocks: usize,
+    pub min_ms: u64,
+    pub max_ms: u64,
+    pub avg_ms: u64,
+}
+
+impl Default for LatencyWindow {
+    fn default() -> Self {
+        Self::new(DEFAULT_LATENCY_WINDOW_LEN)
+    }
+}
+
+impl LatencyWindow {
+    /// The window over the last `max_len` blocks.
+    pub fn new(max_len: usize) -> Self {
+        Self {
+            latencies_ms: Mutex::new(VecDeque::with_capacity(max_len)),
+            max_len,
+        }
+    }
+
+    pub fn record(&self, latency_ms: u64) {
+        let mut latencies_ms = self.latencies_ms.lock().unwrap();
+        if latencies_ms.len() == self.max_len {
+            latencies_ms.pop_front();
+        }
+        if self.max_len > 0 {
+            latencies_ms.push_back(latency_ms);
+        }
+    }
+
+    /// Returns `None` if no latencies were recorded yet.
+    pub fn summary(&self) -> Option<LatencySummary> {
+        let latencies_ms = self.latencies_ms.lock().unwrap();
+        let num_blocks = latencies_ms.len();
+        Some(LatencySummary {
+            num_blocks,
+            min_ms: *latencies_ms.iter().min()?,
+            max_ms: *latencies_ms.iter().max()?,
+            avg_ms: latencies_ms.iter().sum::<u64>() / num_blocks as u64,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BlockSource {
+    /// The live block API, e.g. `https://mainnet.neardata.xyz/v0/block/{height}`
+    Api,
+    /// The neardata archives, e.g. `https://a1.mainnet.neardata.xyz/raw/`
+    Archive,
+    /// The R2 archive at `https://archive.data.fastnear.com`
+    R2Archive,
+}
+
+/// The block with the details of how it was delivered.
+#[derive(Debug)]
+pub struct DeliveredBlock {
+    pub block: BlockWithTxHashes,
+    pub source: BlockSource,
+    /// The URL the block was downloaded from, after redirects
+    pub endpoint: String,
+    /// The time it took to fetch the block, including retries.
+    /// For the archives, it's the time to fetch and parse the whole archive.
+    pub fetch_latency: Duration,
+    /// The number of downloaded bytes. For the archives, it's the size of the whole archive.
+    pub compressed_size: u64,
+    /// The size of the block JSON
+    pub uncompressed_size: u64,
+    pub num_retries: u32,
+    /// The time the block was received by the fetcher
+    pub arrival_time: SystemTime,
+}
+
+impl DeliveredBlock {
+    pub fn block_height(&self) -> BlockHeight {
+        self.block.block.header.height
+    }
+}
+
+/// The state of the fetcher after it stopped.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ShutdownReport {
+    /// All blocks from the start block height up to this height (inclusive) were sent to the sink,
+    /// skipped heights included. In the descending mode, it's the lowest such height.
+    /// Restarting from the next height continues the stream without gaps or duplicates.
+    /// `None` if nothing was delivered.
+    pub last_delivered_block_height: Option<BlockHeight>,
+}
+
 #[derive(Debug, Clone)]
 pub struct FetcherConfig {
     pub num_threads: u64,
@@ -25,6 +176,9 @@ pub struct FetcherConfig {
     /// The end block height to fetch up to (inclusive)
     pub end_block_height: Option<BlockHeight>,
     pub chain_id: ChainId,
+    /// The block API and archives of the known chains. The chain ID must be registered, unless
+    /// the base URL is set.
+    pub chains: ChainRegistry,
     pub timeout_duration: Option<Duration>,
     pub retry_duration: Option<Duration>,
     pub disable_archive_sync: bool,
@@ -32,6 +186,38 @@ pub struct FetcherConfig {
     pub auth_bearer_token: Option<String>,
     pub finality: Finality,
     pub enable_r2_archive_sync: bool,
+    /// The hosts that receive the Bearer token. A host matches if it's equal to an entry or is
+    /// its subdomain. The token is never sent to other hosts, including redirect targets.
+    pub auth_allowed_hosts: Vec<String>,
+    /// Optional counters to track the number of requests and downloaded bytes
+    pub stats: Option<Arc<FetcherStats>>,
+    /// The shards to keep in the blocks. If not set, all shards are kept.
+    pub shards: Option<Vec<ShardId>>,
+    /// Fetch the selected shards from the per-shard endpoints instead of the full block.
+    /// Only applies to the live API, the archives always contain all shards.
+    pub enable_shard_endpoints: bool,
+    /// Filters the blocks by accounts while decoding them
+    pub filter: Option<BlockFilter>,
+    /// Fetch blocks in descending order, from the start block height down to the end block height.
+    pub descending: bool,
+    /// The index to fill with the hashes and heights of the fetched blocks
+    pub block_hash_index: Option<BlockHashIndex>,
+    /// Speculatively request the next blocks when following the tip of the chain.
+    pub tip_following: bool,
+    /// How long to keep delivering the in-flight blocks after `is_running` is set to `false`
+    pub shutdown_timeout: Option<Duration>,
+    /// Overrides the base URL of the live API and the archives, e.g. to use a mirror or a local
+    /// test server. The live API is expected at `{base_url}/v0/` and the archives at
+    /// `{base_url}/raw/`.
+    pub base_url: Option<String>,
+    /// Where to load the archive layout from at startup. The archives of the chain are replaced
+    /// with the ones from the manifest. If the manifest can't be loaded, the archives of the
+    /// chain are used.
+    pub archive_manifest: Option<ArchiveManifestSource>,
+    /// Decode the blocks with the unknown fields and enum variants instead of failing, see
+    /// `fastnear_primitives::tolerant`. The elements that fail to decode are dropped with a
+    /// warning, counted in the stats and delivered in `BlockWithTxHashes::unknown`.
+    pub tolerant_decoding: bool,
 }
 
 #[derive(Debug, Clone)]
@@ -39,6 +225,12 @@ pub struct FetcherConfigBuilder {
     config: FetcherConfig,
 }
 
+impl Default for FetcherConfigBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl FetcherConfigBuilder {
     pub fn new() -> Self {
         FetcherConfigBuilder {
@@ -47,12 +239,28 @@ impl FetcherConfigBuilder {
                 start_block_height: None,
                 end_block_height: None,
                 chain_id: ChainId::Mainnet,
+                chains: ChainRegistry::default(),
                 timeout_duration: None,
                 retry_duration: None,
                 disable_archive_sync: false,
                 auth_bearer_token: None,
                 finality: Finality::Final,
                 enable_r2_archive_sync: false,
+                auth_allowed_hosts: DEFAULT_AUTH_ALLOWED_HOSTS
+                    .iter()
+                    .map(|host| host.to_string())
+                    .collect(),
+                stats: None,
+                shards: None,
+                enable_shard_endpoints: false,
+                filter: None,
+                descending: false,
+                block_hash_index: None,
+                tip_following: false,
+                shutdown_timeout: None,
+                base_url: None,
+                archive_manifest: None,
+                tolerant_decoding: false,
             },
         }
     }
@@ -79,6 +287,13 @@ impl FetcherConfigBuilder {
         self
     }
 
+    /// Registers a chain, e.g. a localnet or a forknet, or replaces the endpoints of a known one.
+    /// Doesn't select the chain, use `chain_id` for that.
+    pub fn chain(mut self, chain_config: ChainConfig) -> Self {
+        self.config.chains.register(chain_config);
+        self
+    }
+
     pub fn timeout_duration(mut self, timeout_duration: Duration) -> Self {
         self.config.timeout_duration = Some(timeout_duration);
         self
@@ -110,6 +325,89 @@ impl FetcherConfigBuilder {
         self
     }
 
+    /// Replaces the list of hosts that receive the Bearer token
+    pub fn auth_allowed_hosts(mut self, auth_allowed_hosts: Vec<String>) -> Self {
+        self.config.auth_allowed_hosts = auth_allowed_hosts;
+        self
+    }
+
+    pub fn stats(mut self, stats: Arc<FetcherStats>) -> Self {
+        self.config.stats = Some(stats);
+        self
+    }
+
+    /// Only keep the given shards in the blocks
+    pub fn shards(mut self, shards: Vec<ShardId>) -> Self {
+        self.config.shards = Some(shards);
+        self
+    }
+
+    /// Requires `shards` to be set
+    pub fn enable_shard_endpoints(mut self, enable_shard_endpoints: bool) -> Self {
+        self.config.enable_shard_endpoints = enable_shard_endpoints;
+        self
+    }
+
+    /// Drops or thins the blocks that don't touch the filtered accounts
+    pub fn filter(mut self, filter: BlockFilter) -> Self {
+        self.config.filter = Some(filter);
+        self
+    }
+
+    /// Fetch blocks in strictly descending order of heights.
+    /// The start block height (default: the last block) is the highest block to fetch, and the end
+    /// block height (default: 0) is the lowest one. The fetcher stops once the receiver is dropped.
+    pub fn descending(mut self, descending: bool) -> Self {
+        self.config.descending = descending;
+        self
+    }
+
+    /// Records the hashes of all fetched blocks into the given index
+    pub fn block_hash_index(mut self, block_hash_index: BlockHashIndex) -> Self {
+        self.config.block_hash_index = Some(block_hash_index);
+        self
+    }
+
+    /// Reduces the delivery latency near the tip of the chain. The fetcher keeps requests for the
+    /// next blocks in flight, while the current block is not produced yet, and retries them with
+    /// a short delay (unless the retry duration is set).
+    /// The production to delivery latency is reported to the stats.
+    pub fn tip_following(mut self, tip_following: bool) -> Self {
+        self.config.tip_following = tip_following;
+        self
+    }
+
+    pub fn shutdown_timeout(mut self, shutdown_timeout: Duration) -> Self {
+        self.config.shutdown_timeout = Some(shutdown_timeout);
+        self
+    }
+
+    pub fn base_url(mut self, base_url: String) -> Self {
+        self.config.base_url = Some(base_url.trim_end_matches('/').to_string());
+        self
+    }
+
+    /// Loads the archive manifest from the given URL at startup
+    pub fn archive_manifest_url(mut self, url: String) -> Self {
+        self.config.archive_manifest = Some(ArchiveManifestSource::Url(url));
+        self
+    }
+
+    /// Loads the archive manifest from the given local file at startup
+    pub fn archive_manifest_file(mut self, path: String) -> Self {
+        self.config.archive_manifest = Some(ArchiveManifestSource::File(path));
+        self
+    }
+
+    /// Keeps decoding the blocks with the unknown fields and enum variants, e.g. after a protocol
+    /// upgrade, instead of retrying them forever.
+    /// The tolerant decoding is several times slower and doesn't use the SIMD parser or skip the
+    /// unselected shards while decoding, so it may limit the backfill throughput.
+    pub fn tolerant_decoding(mut self, tolerant_decoding: bool) -> Self {
+        self.config.tolerant_decoding = tolerant_decoding;
+        self
+    }
+
     pub fn build(self) -> FetcherConfig {
         self.config
     }
@@ -1,15 +1,23 @@
 use crate::*;
 
+use crate::chains::ChainConfig;
+use crate::index::{BlockHashIndex, NotIndexedError};
+use fastnear_primitives::near_indexer_primitives::CryptoHash;
+
 pub const LOG_TARGET: &str = "neardata-fetcher";
 
 pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
 pub const DEFAULT_RETRY_DURATION: Duration = Duration::from_secs(1);
+pub const DEFAULT_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+pub const DEFAULT_TIP_RETRY_DURATION: Duration = Duration::from_millis(100);
 
-pub(crate) const MAINNET_R2_LAST_BLOCK_HEIGHT: u64 = 141999999;
-pub(crate) const TESTNET_ARCHIVE_LAST_BLOCK_HEIGHT: u64 = 185670000;
-pub(crate) const MAINNET_ARCHIVE_BOUNDARIES: &[u64] = &[122000000, 142000000];
+pub const DEFAULT_AUTH_ALLOWED_HOSTS: &[&str] = &["neardata.xyz", "fastnear.com"];
 
 pub(crate) const NUMBER_OF_BLOCKS_PER_ARCHIVE: u64 = 10;
+/// The number of blocks requested ahead of the current one in the tip-following mode
+pub(crate) const TIP_PREFETCH_BLOCKS: u64 = 2;
+/// How often a blocked delivery checks whether the fetcher was stopped
+pub(crate) const SHUTDOWN_CHECK_INTERVAL: Duration = Duration::from_millis(50);
 pub(crate) const ARCHIVE_SYNC_THRESHOLD: u64 = NUMBER_OF_BLOCKS_PER_ARCHIVE * 2;
 
 pub fn new_reqwest_client() -> Client {
@@ -29,42 +37,33 @@ pub async fn fetch_block_until_success(
     loop {
         match fetch_block(client, url, timeout).await {
             Ok(block) => return block,
-            Err(FetchError::ReqwestError(err)) => {
-                tracing::log::warn!(target: LOG_TARGET, "Failed to fetch block: {}", err);
-                tokio::time::sleep(Duration::from_secs(1)).await;
-            }
             Err(FetchError::RedirectError) => {
                 tracing::log::warn!(target: LOG_TARGET, "Redirect error");
                 return None;
             }
+            Err(err) => {
+                tracing::log::warn!(target: LOG_TARGET, "Failed to fetch block: {}", err);
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            }
         }
     }
 }
 
-pub(crate) fn target_url(suffix: &str, chain_id: ChainId) -> String {
-    format!(
-        "{}{}",
-        match chain_id {
-            ChainId::Mainnet => "https://mainnet.neardata.xyz",
-            ChainId::Testnet => "https://testnet.neardata.xyz",
-        },
-        suffix
-    )
-}
-
-pub async fn fetch_first_block(client: &Client, chain_id: ChainId) -> Option<BlockWithTxHashes> {
+/// Fetches the first block of the chain. Look the chain up with `ChainRegistry::get`, or use
+/// `ChainConfig::from_base_url` for a custom chain.
+pub async fn fetch_first_block(client: &Client, chain: &ChainConfig) -> Option<BlockWithTxHashes> {
     fetch_block_until_success(
         client,
-        &target_url("/v0/first_block", chain_id),
+        &format!("{}/v0/first_block", chain.api_url),
         DEFAULT_TIMEOUT,
     )
     .await
 }
 
-pub async fn fetch_last_block(client: &Client, chain_id: ChainId) -> Option<BlockWithTxHashes> {
+pub async fn fetch_last_block(client: &Client, chain: &ChainConfig) -> Option<BlockWithTxHashes> {
     fetch_block_until_success(
         client,
-        &target_url("/v0/last_block/final", chain_id),
+        &format!("{}/v0/last_block/final", chain.api_url),
         DEFAULT_TIMEOUT,
     )
     .await
@@ -74,12 +73,29 @@ pub async fn fetch_block_by_height(
     client: &Client,
     height: BlockHeight,
     timeout: Duration,
-    chain_id: ChainId,
+    chain: &ChainConfig,
 ) -> Option<BlockWithTxHashes> {
     fetch_block_until_success(
         client,
-        &target_url(&format!("/v0/block/{}", height), chain_id),
+        &format!("{}/v0/block/{}", chain.api_url, height),
         timeout,
     )
     .await
 }
+
+/// Fetches the block by its hash. The hash is resolved to the block height using the local index,
+/// so only the blocks that were seen by the fetcher or loaded into the index can be fetched, other
+/// hashes return `NotIndexedError`.
+/// Returns `None` if there is no block at the indexed height or it has a different hash.
+pub async fn fetch_block_by_hash(
+    client: &Client,
+    block_hash_index: &BlockHashIndex,
+    block_hash: &CryptoHash,
+    timeout: Duration,
+    chain: &ChainConfig,
+) -> Result<Option<BlockWithTxHashes>, NotIndexedError> {
+    let height = block_hash_index.get(block_hash)?;
+    Ok(fetch_block_by_height(client, height, timeout, chain)
+        .await
+        .filter(|block| &block.block.header.hash == block_hash))
+}
@@ -0,0 +1,115 @@
+use fastnear_neardata_fetcher::broadcast::{BlockBroadcast, LagPolicy};
+use fastnear_primitives::block_with_tx_hash::BlockWithTxHashes;
+use fastnear_primitives::near_primitives::types::BlockHeight;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+const BLOCK_PATH: &str = "../res/blocks/synthetic_block.json";
+
+fn block(height: BlockHeight) -> BlockWithTxHashes {
+    let mut block: BlockWithTxHashes =
+        serde_json::from_slice(&std::fs::read(BLOCK_PATH).unwrap()).unwrap();
+    block.block.header.height = height;
+    block
+}
+
+async fn receive_all(mut receiver: mpsc::Receiver<Arc<BlockWithTxHashes>>) -> Vec<BlockHeight> {
+    let mut heights = vec![];
+    while let Some(block) = tokio::time::timeout(Duration::from_secs(10), receiver.recv())
+        .await
+        .expect("Timed out waiting for blocks")
+    {
+        heights.push(block.block.header.height);
+    }
+    heights
+}
+
+#[tokio::test]
+async fn multiple_subscribers_with_lagging_one() {
+    let broadcast = BlockBroadcast::new();
+    assert_eq!(broadcast.head_block_height(), None);
+    let fast = broadcast.subscribe(16, LagPolicy::Block);
+    // Never reads until the stream ends, so it's disconnected once its buffer is full
+    let lagging = broadcast.subscribe(2, LagPolicy::Drop);
+    // Reads slowly, so it slows down the producer but receives every block
+    let slow = broadcast.subscribe(1, LagPolicy::Block);
+    assert_eq!(broadcast.num_subscribers(), 3);
+
+    let (sender, receiver) = mpsc::channel(1);
+    let handle = tokio::spawn({
+        let broadcast = broadcast.clone();
+        async move { broadcast.run(receiver).await }
+    });
+    let fast = tokio::spawn(receive_all(fast));
+    let slow = tokio::spawn(async move {
+        let mut slow = slow;
+        let mut heights = vec![];
+        while let Some(block) = slow.recv().await {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+            heights.push(block.block.header.height);
+        }
+        heights
+    });
+    for height in 0..10 {
+        sender.send(block(height)).await.unwrap();
+    }
+    drop(sender);
+    handle.await.unwrap();
+
+    let expected: Vec<_> = (0..10).collect();
+    assert_eq!(fast.await.unwrap(), expected);
+    assert_eq!(slow.await.unwrap(), expected);
+    assert_eq!(receive_all(lagging).await, vec![0, 1]);
+    assert_eq!(broadcast.head_block_height(), Some(9));
+    assert_eq!(broadcast.num_subscribers(), 0);
+}
+
+#[tokio::test]
+async fn late_subscriber_starts_at_head() {
+    let broadcast = BlockBroadcast::new();
+    let early = broadcast.subscribe(16, LagPolicy::Block);
+    let (sender, receiver) = mpsc::channel(1);
+    let handle = tokio::spawn({
+        let broadcast = broadcast.clone();
+        async move { broadcast.run(receiver).await }
+    });
+
+    sender.send(block(0)).await.unwrap();
+    sender.send(block(1)).await.unwrap();
+    while broadcast.head_block_height() != Some(1) {
+        tokio::time::sleep(Duration::from_millis(1)).await;
+    }
+    let late = broadcast.subscribe(16, LagPolicy::Drop);
+    sender.send(block(2)).await.unwrap();
+    drop(sender);
+    handle.await.unwrap();
+
+    assert_eq!(receive_all(early).await, vec![0, 1, 2]);
+    assert_eq!(receive_all(late).await, vec![2]);
+}
+
+#[tokio::test]
+async fn subscriber_joining_during_send_starts_after_head() {
+    let broadcast = BlockBroadcast::new();
+    let blocking = broadcast.subscribe(1, LagPolicy::Block);
+    let (sender, receiver) = mpsc::channel(1);
+    let handle = tokio::spawn({
+        let broadcast = broadcast.clone();
+        async move { broadcast.run(receiver).await }
+    });
+
+    // Block 0 fills the buffer of the blocking subscriber, so the send of block 1 waits
+    sender.send(block(0)).await.unwrap();
+    sender.send(block(1)).await.unwrap();
+    while broadcast.head_block_height() != Some(1) {
+        tokio::time::sleep(Duration::from_millis(1)).await;
+    }
+    let late = broadcast.subscribe(16, LagPolicy::Drop);
+    sender.send(block(2)).await.unwrap();
+    drop(sender);
+
+    assert_eq!(receive_all(blocking).await, vec![0, 1, 2]);
+    handle.await.unwrap();
+    assert_eq!(receive_all(late).await, vec![2]);
+}
@@ -0,0 +1,77 @@
+use fastnear_neardata_fetcher::chains::{ArchiveEndpoint, ArchiveManifest, ChainConfig};
+use fastnear_neardata_fetcher::fetcher::BlockSource;
+use fastnear_primitives::near_primitives::types::BlockHeight;
+use fastnear_primitives::types::ChainId;
+
+fn archive(
+    start_block_height: BlockHeight,
+    end_block_height: Option<BlockHeight>,
+    source: BlockSource,
+) -> ArchiveEndpoint {
+    ArchiveEndpoint {
+        url: format!("https://a{}.example.com/raw/", start_block_height),
+        start_block_height,
+        end_block_height,
+        source,
+        requires_auth: false,
+    }
+}
+
+fn manifest(archives: Vec<ArchiveEndpoint>) -> ArchiveManifest {
+    ArchiveManifest {
+        chain_id: ChainId::Mainnet,
+        archives,
+    }
+}
+
+#[test]
+fn default_archives_are_valid_manifests() {
+    for chain in [ChainConfig::mainnet(), ChainConfig::testnet()] {
+        let manifest = ArchiveManifest {
+            chain_id: chain.chain_id.clone(),
+            archives: chain.archives,
+        };
+        assert_eq!(manifest.validate(&chain.chain_id), Ok(()));
+    }
+}
+
+#[test]
+fn manifest_of_other_chain() {
+    let manifest = manifest(vec![archive(0, None, BlockSource::Archive)]);
+    assert!(manifest.validate(&ChainId::Testnet).is_err());
+    let localnet = ChainId::try_from("localnet".to_string()).unwrap();
+    assert!(manifest.validate(&localnet).is_err());
+}
+
+#[test]
+fn manifest_with_gaps() {
+    let not_from_genesis = manifest(vec![archive(10, None, BlockSource::Archive)]);
+    assert!(not_from_genesis.validate(&ChainId::Mainnet).is_err());
+    let with_gap = manifest(vec![
+        archive(0, Some(100), BlockSource::Archive),
+        archive(200, None, BlockSource::Archive),
+    ]);
+    assert!(with_gap.validate(&ChainId::Mainnet).is_err());
+}
+
+#[test]
+fn manifest_with_overlaps() {
+    let overlapping = manifest(vec![
+        archive(0, Some(200), BlockSource::Archive),
+        archive(100, None, BlockSource::Archive),
+    ]);
+    assert!(overlapping.validate(&ChainId::Mainnet).is_err());
+    let after_unbounded = manifest(vec![
+        archive(100, None, BlockSource::Archive),
+        archive(0, Some(100), BlockSource::Archive),
+        archive(200, Some(300), BlockSource::Archive),
+    ]);
+    assert!(after_unbounded.validate(&ChainId::Mainnet).is_err());
+    // The archives of different sources are alternatives for the same heights
+    let other_sources = manifest(vec![
+        archive(0, Some(100), BlockSource::R2Archive),
+        archive(0, Some(100), BlockSource::Archive),
+        archive(100, None, BlockSource::Archive),
+    ]);
+    assert_eq!(other_sources.validate(&ChainId::Mainnet), Ok(()));
+}
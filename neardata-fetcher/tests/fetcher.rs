@@ -0,0 +1,870 @@
+use fastnear_neardata_fetcher::chains::{ArchiveEndpoint, ArchiveManifest, ChainConfig};
+use fastnear_neardata_fetcher::fetcher::{self, BlockSource, DeliveredBlock, FetcherConfigBuilder};
+use fastnear_neardata_fetcher::filter::{
+    start_matched_block_fetcher, BlockFilter, BlockFilterMode,
+};
+use fastnear_neardata_fetcher::test_support::{archive_path, test_block, Fault, TestServer};
+use fastnear_primitives::block_with_tx_hash::BlockWithTxHashes;
+use fastnear_primitives::near_primitives::types::{BlockHeight, ShardId};
+use fastnear_primitives::types::ChainId;
+use serde_json::json;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::mpsc;
+
+fn config_builder(server: &TestServer) -> FetcherConfigBuilder {
+    FetcherConfigBuilder::new()
+        .base_url(server.url())
+        .num_threads(4)
+        .timeout_duration(Duration::from_secs(5))
+        .retry_duration(Duration::from_millis(10))
+        .shutdown_timeout(Duration::from_secs(1))
+}
+
+/// Runs the fetcher to completion and returns all delivered blocks.
+async fn fetch_all(
+    config: fetcher::FetcherConfig,
+) -> (Vec<DeliveredBlock>, fetcher::ShutdownReport) {
+    let (sender, mut receiver) = mpsc::channel(100);
+    let is_running = Arc::new(AtomicBool::new(true));
+    let handle = tokio::spawn(fetcher::start_fetcher_with_metadata(
+        config, sender, is_running,
+    ));
+    let mut blocks = Vec::new();
+    while let Some(block) = tokio::time::timeout(Duration::from_secs(30), receiver.recv())
+        .await
+        .expect("Timed out waiting for blocks")
+    {
+        blocks.push(block);
+    }
+    (blocks, handle.await.unwrap())
+}
+
+fn heights(blocks: &[DeliveredBlock]) -> Vec<BlockHeight> {
+    blocks.iter().map(|block| block.block_height()).collect()
+}
+
+fn assert_linked(blocks: &[DeliveredBlock]) {
+    for pair in blocks.windows(2) {
+        assert_eq!(
+            pair[0].block.block.header.hash,
+            pair[1].block.block.header.prev_hash,
+            "Blocks {} and {} are not linked",
+            pair[0].block_height(),
+            pair[1].block_height()
+        );
+    }
+}
+
+#[tokio::test]
+async fn archive_to_live_handoff() {
+    let server = TestServer::start().await;
+    server.add_blocks(100..=157, &[], 1);
+    let config = config_builder(&server)
+        .start_block_height(100)
+        .end_block_height(157)
+        .build();
+
+    let (blocks, report) = fetch_all(config).await;
+
+    assert_eq!(heights(&blocks), (100..=157).collect::<Vec<_>>());
+    assert_linked(&blocks);
+    for block in &blocks {
+        let expected_source = if block.block_height() < 150 {
+            BlockSource::Archive
+        } else {
+            BlockSource::Api
+        };
+        assert_eq!(block.source, expected_source, "{}", block.block_height());
+    }
+    assert_eq!(server.num_requests(&archive_path(100)), 1);
+    assert_eq!(server.num_requests(&archive_path(140)), 1);
+    assert_eq!(server.num_requests("/v0/block/149"), 0);
+    assert_eq!(report.last_delivered_block_height, Some(157));
+}
+
+#[tokio::test]
+async fn start_and_end_inside_archives() {
+    let server = TestServer::start().await;
+    server.add_blocks(100..=160, &[], 1);
+    let config = config_builder(&server)
+        .start_block_height(105)
+        .end_block_height(133)
+        .build();
+
+    let (blocks, report) = fetch_all(config).await;
+
+    assert_eq!(heights(&blocks), (105..=133).collect::<Vec<_>>());
+    assert_eq!(report.last_delivered_block_height, Some(133));
+    // The blocks past the end are never requested
+    assert_eq!(server.num_requests("/v0/block/134"), 0);
+}
+
+#[tokio::test]
+async fn skipped_blocks() {
+    let server = TestServer::start().await;
+    server.add_blocks(100..=157, &[103, 104, 151], 1);
+    let config = config_builder(&server)
+        .start_block_height(100)
+        .end_block_height(157)
+        .build();
+
+    let (blocks, report) = fetch_all(config).await;
+
+    let expected: Vec<_> = (100..=157)
+        .filter(|height| ![103, 104, 151].contains(height))
+        .collect();
+    assert_eq!(heights(&blocks), expected);
+    assert_linked(&blocks);
+    assert_eq!(blocks[3].block.block.header.prev_height, Some(102));
+    assert_eq!(report.last_delivered_block_height, Some(157));
+}
+
+#[tokio::test]
+async fn skipped_end_block() {
+    let server = TestServer::start().await;
+    server.add_blocks(10..=20, &[15], 1);
+    let config = config_builder(&server)
+        .start_block_height(10)
+        .end_block_height(15)
+        .build();
+
+    let (blocks, report) = fetch_all(config).await;
+
+    assert_eq!(heights(&blocks), vec![10, 11, 12, 13, 14]);
+    // The skipped end block still counts as delivered
+    assert_eq!(report.last_delivered_block_height, Some(15));
+}
+
+#[tokio::test]
+async fn waits_for_new_blocks() {
+    let server = TestServer::start().await;
+    server.add_blocks(10..=30, &[], 1);
+    server.set_last_block_height(20);
+    let config = config_builder(&server)
+        .start_block_height(10)
+        .end_block_height(30)
+        .build();
+    let (sender, mut receiver) = mpsc::channel(100);
+    let is_running = Arc::new(AtomicBool::new(true));
+    let handle = tokio::spawn(fetcher::start_fetcher(config, sender, is_running));
+
+    let mut heights = Vec::new();
+    while let Some(block) = receiver.recv().await {
+        let height = block.block.header.height;
+        heights.push(height);
+        if height == 20 {
+            server.set_last_block_height(30);
+        }
+    }
+
+    assert_eq!(heights, (10..=30).collect::<Vec<_>>());
+    assert_eq!(handle.await.unwrap().last_delivered_block_height, Some(30));
+}
+
+#[tokio::test]
+async fn shutdown_reports_last_delivered_block() {
+    let server = TestServer::start().await;
+    server.add_blocks(0..=1000, &[], 1);
+    let config = config_builder(&server).start_block_height(0).build();
+    let (sender, mut receiver) = mpsc::channel::<BlockWithTxHashes>(1);
+    let is_running = Arc::new(AtomicBool::new(true));
+    let handle = tokio::spawn(fetcher::start_fetcher(config, sender, is_running.clone()));
+
+    let mut heights = Vec::new();
+    while let Some(block) = receiver.recv().await {
+        heights.push(block.block.header.height);
+        if heights.len() == 25 {
+            is_running.store(false, Ordering::SeqCst);
+        }
+    }
+
+    assert!(heights.len() >= 25);
+    assert_eq!(heights, (0..heights.len() as u64).collect::<Vec<_>>());
+    let report = handle.await.unwrap();
+    assert_eq!(report.last_delivered_block_height, heights.last().copied());
+}
+
+#[tokio::test]
+async fn shutdown_before_first_block() {
+    let server = TestServer::start().await;
+    server.add_blocks(0..=10, &[], 1);
+    let config = config_builder(&server).start_block_height(100).build();
+    let (sender, mut receiver) = mpsc::channel::<BlockWithTxHashes>(1);
+    let is_running = Arc::new(AtomicBool::new(true));
+    let handle = tokio::spawn(fetcher::start_fetcher(config, sender, is_running.clone()));
+
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    is_running.store(false, Ordering::SeqCst);
+
+    assert!(receiver.recv().await.is_none());
+    assert_eq!(handle.await.unwrap().last_delivered_block_height, None);
+}
+
+#[tokio::test]
+async fn shutdown_with_full_channel() {
+    let server = TestServer::start().await;
+    server.add_blocks(0..=1000, &[], 1);
+    let config = config_builder(&server).start_block_height(0).build();
+    let (sender, mut receiver) = mpsc::channel::<BlockWithTxHashes>(1);
+    let is_running = Arc::new(AtomicBool::new(true));
+    let handle = tokio::spawn(fetcher::start_fetcher(config, sender, is_running.clone()));
+
+    let mut heights = Vec::new();
+    for _ in 0..5 {
+        heights.push(receiver.recv().await.unwrap().block.header.height);
+    }
+    // The consumer stops reading, but keeps the channel open, so the fetcher is blocked on a send
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    is_running.store(false, Ordering::SeqCst);
+
+    // Gives up after the shutdown timeout of 1 second
+    let report = tokio::time::timeout(Duration::from_secs(5), handle)
+        .await
+        .expect("The fetcher is stuck on a full channel")
+        .unwrap();
+    while let Ok(block) = receiver.try_recv() {
+        heights.push(block.block.header.height);
+    }
+    assert_eq!(heights, (0..heights.len() as u64).collect::<Vec<_>>());
+    assert_eq!(report.last_delivered_block_height, heights.last().copied());
+}
+
+#[tokio::test]
+async fn recovers_from_faults() {
+    let server = TestServer::start().await;
+    server.add_blocks(100..=157, &[], 1);
+    server.inject_fault(&archive_path(110), Fault::CorruptBody);
+    server.inject_fault(&archive_path(120), Fault::Status(500));
+    server.inject_fault(
+        "/v0/block/151",
+        Fault::Redirect("/v0/block/151?hop=1".into()),
+    );
+    server.inject_fault("/v0/block/152", Fault::Status(429));
+    server.inject_fault("/v0/block/152", Fault::Status(429));
+    server.inject_fault("/v0/block/153", Fault::Status(404));
+    server.inject_fault("/v0/block/154", Fault::Delay(Duration::from_millis(200)));
+    server.inject_fault("/v0/block/155", Fault::CorruptBody);
+    let config = config_builder(&server)
+        .start_block_height(100)
+        .end_block_height(157)
+        .build();
+
+    let (blocks, _) = fetch_all(config).await;
+
+    assert_eq!(heights(&blocks), (100..=157).collect::<Vec<_>>());
+    assert_linked(&blocks);
+    assert_eq!(server.num_requests(&archive_path(110)), 2);
+    assert_eq!(blocks[10].num_retries, 1);
+    assert_eq!(blocks[20].num_retries, 1);
+    let block = |height: BlockHeight| &blocks[(height - 100) as usize];
+    assert!(block(151).endpoint.ends_with("/v0/block/151?hop=1"));
+    assert_eq!(block(151).num_retries, 0);
+    assert_eq!(block(152).num_retries, 2);
+    assert_eq!(block(153).num_retries, 1);
+    assert!(block(154).fetch_latency >= Duration::from_millis(200));
+    assert_eq!(block(155).num_retries, 1);
+}
+
+#[tokio::test]
+async fn tolerant_decoding() {
+    let server = TestServer::start().await;
+    server.add_blocks(100..=157, &[], 1);
+    for height in [120, 152] {
+        let mut block = test_block(height, Some(height - 1), 1);
+        block["block"]["header"]["new_header_field"] = json!(1);
+        block["shards"][0]["state_changes"] = json!([{
+            "cause": {"type": "new_cause"},
+            "type": "new_change",
+            "change": {"account_id": "test.near"}
+        }]);
+        server.set_block(height, Some(block));
+    }
+    let stats = Arc::new(fetcher::FetcherStats::default());
+    let config = config_builder(&server)
+        .start_block_height(100)
+        .end_block_height(157)
+        .stats(stats.clone())
+        .tolerant_decoding(true)
+        .build();
+
+    let (blocks, _) = fetch_all(config).await;
+
+    assert_eq!(heights(&blocks), (100..=157).collect::<Vec<_>>());
+    assert_linked(&blocks);
+    assert!(blocks[20].block.shards[0].state_changes.is_empty());
+    assert_eq!(blocks[52].num_retries, 0);
+    assert_eq!(stats.num_tolerant_decodes.load(Ordering::Relaxed), 2);
+    assert_eq!(stats.num_unknown_values.load(Ordering::Relaxed), 4);
+    // From an archive and from the API
+    for index in [20, 52] {
+        let paths: Vec<_> = blocks[index]
+            .block
+            .unknown
+            .iter()
+            .map(|unknown| unknown.path())
+            .collect();
+        assert_eq!(
+            paths,
+            vec![
+                "shards[0].state_changes[0]",
+                "block.header.new_header_field"
+            ]
+        );
+    }
+    assert!(blocks[21].block.unknown.is_empty());
+}
+
+#[tokio::test]
+async fn tolerant_decoding_of_shard_endpoints() {
+    let server = TestServer::start().await;
+    server.add_blocks(10..=12, &[], 4);
+    let mut block = test_block(11, Some(10), 4);
+    block["block"]["header"]["new_header_field"] = json!(1);
+    block["shards"][3]["state_changes"] = json!([{"type": "new_change"}]);
+    server.set_block(11, Some(block));
+    let config = config_builder(&server)
+        .start_block_height(10)
+        .end_block_height(12)
+        .shards(vec![ShardId::from(1u64), ShardId::from(3u64)])
+        .enable_shard_endpoints(true)
+        .tolerant_decoding(true)
+        .build();
+
+    let (blocks, _) = fetch_all(config).await;
+
+    assert_eq!(heights(&blocks), vec![10, 11, 12]);
+    let paths: Vec<_> = blocks[1]
+        .block
+        .unknown
+        .iter()
+        .map(|unknown| unknown.path())
+        .collect();
+    // The shard 3 is the second shard of the assembled block
+    assert_eq!(
+        paths,
+        vec![
+            "block.header.new_header_field",
+            "shards[1].state_changes[0]"
+        ]
+    );
+}
+
+#[tokio::test]
+async fn tolerant_decoding_without_metadata() {
+    let server = TestServer::start().await;
+    server.add_blocks(10..=12, &[], 1);
+    let mut block = test_block(11, Some(10), 1);
+    block["shards"][0]["state_changes"] = json!([
+        {"cause": {"type": "new_cause"}, "type": "new_change", "change": {}},
+        {
+            "cause": {"type": "not_writable_to_disk"},
+            "type": "account_deletion",
+            "change": {"account_id": "test.near"}
+        }
+    ]);
+    server.set_block(11, Some(block));
+    let config = config_builder(&server)
+        .start_block_height(10)
+        .end_block_height(12)
+        .tolerant_decoding(true)
+        .build();
+    let (sender, mut receiver) = mpsc::channel::<BlockWithTxHashes>(100);
+    let is_running = Arc::new(AtomicBool::new(true));
+    let handle = tokio::spawn(fetcher::start_fetcher(config, sender, is_running));
+
+    let mut blocks = Vec::new();
+    while let Some(block) = receiver.recv().await {
+        blocks.push(block);
+    }
+
+    handle.await.unwrap();
+    assert_eq!(blocks.len(), 3);
+    let block = &blocks[1];
+    assert_eq!(block.shards[0].state_changes.len(), 1);
+    let paths: Vec<_> = block.unknown.iter().map(|unknown| unknown.path()).collect();
+    assert_eq!(paths, vec!["shards[0].state_changes[0]"]);
+    assert_eq!(block.original_index("shards[0].state_changes", 0), 1);
+    assert!(blocks[0].unknown.is_empty());
+}
+
+#[tokio::test]
+async fn tip_latency_window() {
+    let server = TestServer::start().await;
+    let now_nanosec = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_nanos() as u64;
+    // Produced 30, 20 and 10 seconds ago
+    for (height, age_sec) in [(100, 30), (101, 20), (102, 10)] {
+        let timestamp_nanosec = now_nanosec - age_sec * 1_000_000_000;
+        let mut block = test_block(height, Some(height - 1), 1);
+        block["block"]["header"]["timestamp"] = json!(timestamp_nanosec);
+        block["block"]["header"]["timestamp_nanosec"] = json!(timestamp_nanosec.to_string());
+        server.set_block(height, Some(block));
+    }
+    server.set_last_block_height(102);
+    let stats = Arc::new(fetcher::FetcherStats::default());
+    let config = config_builder(&server)
+        .start_block_height(100)
+        .end_block_height(102)
+        .tip_following(true)
+        .stats(stats.clone())
+        .build();
+
+    let (blocks, _) = fetch_all(config).await;
+
+    assert_eq!(heights(&blocks), vec![100, 101, 102]);
+    let summary = stats.tip_latency.summary().unwrap();
+    assert_eq!(summary.num_blocks, 3);
+    // Allowing for the time it takes to run the test
+    assert!((10_000..15_000).contains(&summary.min_ms), "{:?}", summary);
+    assert!((30_000..35_000).contains(&summary.max_ms), "{:?}", summary);
+    assert!((20_000..25_000).contains(&summary.avg_ms), "{:?}", summary);
+    assert_eq!(
+        stats.last_block_latency_ms.load(Ordering::Relaxed),
+        summary.min_ms
+    );
+}
+
+#[test]
+fn latency_window_keeps_recent_blocks() {
+    let window = fetcher::LatencyWindow::new(3);
+    assert_eq!(window.summary(), None);
+    for latency_ms in [1000, 10, 20, 60] {
+        window.record(latency_ms);
+    }
+    assert_eq!(
+        window.summary(),
+        Some(fetcher::LatencySummary {
+            num_blocks: 3,
+            min_ms: 10,
+            max_ms: 60,
+            avg_ms: 30,
+        })
+    );
+}
+
+#[tokio::test]
+async fn redirect_loop_is_retried() {
+    let server = TestServer::start().await;
+    server.add_blocks(10..=12, &[], 1);
+    for _ in 0..fetcher::MAX_REDIRECTS {
+        server.inject_fault("/v0/block/11", Fault::Redirect("/v0/block/11".into()));
+    }
+    let config = config_builder(&server)
+        .start_block_height(10)
+        .end_block_height(12)
+        .build();
+
+    let (blocks, _) = fetch_all(config).await;
+
+    assert_eq!(heights(&blocks), vec![10, 11, 12]);
+    assert_eq!(blocks[1].num_retries, 1);
+}
+
+#[tokio::test]
+async fn auth_token_is_only_sent_to_allowed_hosts() {
+    let server = TestServer::start().await;
+    server.add_blocks(10..=12, &[], 1);
+    let config = config_builder(&server)
+        .start_block_height(10)
+        .end_block_height(12)
+        .auth_bearer_token("secret".into())
+        .build();
+    fetch_all(config).await;
+    assert!(server
+        .requests()
+        .iter()
+        .all(|request| request.authorization.is_none()));
+
+    let server = TestServer::start().await;
+    server.add_blocks(10..=12, &[], 1);
+    server.inject_fault(
+        "/v0/block/11",
+        Fault::Redirect(format!("http://localhost:{}/v0/block/11", server.port())),
+    );
+    let config = config_builder(&server)
+        .start_block_height(10)
+        .end_block_height(12)
+        .auth_bearer_token("secret".into())
+        .auth_allowed_hosts(vec!["127.0.0.1".into()])
+        .build();
+    let (blocks, _) = fetch_all(config).await;
+
+    assert_eq!(heights(&blocks), vec![10, 11, 12]);
+    let requests = server.requests();
+    let block_requests: Vec<_> = requests
+        .iter()
+        .filter(|request| request.path == "/v0/block/11")
+        .collect();
+    assert_eq!(block_requests.len(), 2);
+    assert_eq!(
+        block_requests[0].authorization.as_deref(),
+        Some("Bearer secret")
+    );
+    // The redirect target is not an allowed host
+    assert_eq!(block_requests[1].authorization, None);
+}
+
+#[tokio::test]
+async fn descending_sync() {
+    let server = TestServer::start().await;
+    server.add_blocks(0..=55, &[33], 1);
+    let config = config_builder(&server)
+        .start_block_height(52)
+        .end_block_height(20)
+        .descending(true)
+        .build();
+
+    let (blocks, report) = fetch_all(config).await;
+
+    let expected: Vec<_> = (20..=52).rev().filter(|height| *height != 33).collect();
+    assert_eq!(heights(&blocks), expected);
+    assert_eq!(report.last_delivered_block_height, Some(20));
+    assert_eq!(server.num_requests(&archive_path(10)), 0);
+}
+
+#[tokio::test]
+async fn shard_endpoints() {
+    let server = TestServer::start().await;
+    server.add_blocks(10..=15, &[], 4);
+    let config = config_builder(&server)
+        .start_block_height(10)
+        .end_block_height(15)
+        .shards(vec![ShardId::from(1u64), ShardId::from(3u64)])
+        .enable_shard_endpoints(true)
+        .build();
+
+    let (blocks, _) = fetch_all(config).await;
+
+    assert_eq!(heights(&blocks), (10..=15).collect::<Vec<_>>());
+    for block in &blocks {
+        let shard_ids: Vec<_> = block.block.shards.iter().map(|s| s.shard_id).collect();
+        assert_eq!(shard_ids, vec![ShardId::from(1u64), ShardId::from(3u64)]);
+    }
+    assert_eq!(server.num_requests("/v0/block/12"), 0);
+    assert_eq!(server.num_requests("/v0/block/12/headers"), 1);
+    assert_eq!(server.num_requests("/v0/block/12/shard/1"), 1);
+    assert_eq!(server.num_requests("/v0/block/12/shard/0"), 0);
+}
+
+#[tokio::test]
+async fn shards_are_skipped_while_decoding() {
+    let server = TestServer::start().await;
+    server.add_blocks(100..=157, &[], 4);
+    // The unselected shards are not decoded, so the invalid shards don't fail the blocks
+    for height in [120, 152] {
+        let mut block = test_block(height, Some(height - 1), 4);
+        block["shards"][2]["state_changes"] = json!("invalid state changes");
+        server.set_block(height, Some(block));
+    }
+    let config = config_builder(&server)
+        .start_block_height(100)
+        .end_block_height(157)
+        .shards(vec![ShardId::from(1u64), ShardId::from(3u64)])
+        .build();
+
+    let (blocks, _) = fetch_all(config).await;
+
+    assert_eq!(heights(&blocks), (100..=157).collect::<Vec<_>>());
+    assert_eq!(blocks[20].source, BlockSource::Archive);
+    for block in &blocks {
+        assert_eq!(block.num_retries, 0);
+        let shard_ids: Vec<_> = block.block.shards.iter().map(|s| s.shard_id).collect();
+        assert_eq!(shard_ids, vec![ShardId::from(1u64), ShardId::from(3u64)]);
+    }
+    assert_eq!(server.num_requests(&archive_path(120)), 1);
+}
+
+/// The test blocks with the shards of the sample block at the given heights
+fn add_sample_blocks(server: &TestServer, heights: &[BlockHeight]) {
+    let sample: serde_json::Value =
+        serde_json::from_slice(&std::fs::read("../res/blocks/synthetic_block.json").unwrap())
+            .unwrap();
+    for &height in heights {
+        let mut block = test_block(height, Some(height - 1), 2);
+        block["shards"] = sample["shards"].clone();
+        server.set_block(height, Some(block));
+    }
+}
+
+#[tokio::test]
+async fn thin_filter_with_shards() {
+    let server = TestServer::start().await;
+    server.add_blocks(100..=157, &[], 2);
+    add_sample_blocks(&server, &[120, 152]);
+    let filter = BlockFilter::from_accounts(&["nft.near".parse().unwrap()], BlockFilterMode::Thin);
+    let config = config_builder(&server)
+        .start_block_height(100)
+        .end_block_height(157)
+        .shards(vec![ShardId::from(1u64)])
+        .filter(filter)
+        .build();
+
+    let (blocks, _) = fetch_all(config).await;
+
+    assert_eq!(heights(&blocks), vec![120, 152]);
+    for block in &blocks {
+        let shards = &block.block.shards;
+        assert_eq!(shards.len(), 1);
+        assert_eq!(shards[0].shard_id, ShardId::from(1u64));
+        assert!(shards[0].chunk.as_ref().unwrap().transactions.is_empty());
+        let receivers: Vec<_> = shards[0]
+            .receipt_execution_outcomes
+            .iter()
+            .map(|outcome| outcome.receipt.receiver_id.as_str())
+            .collect();
+        assert_eq!(receivers, vec!["nft.near"]);
+        assert_eq!(shards[0].state_changes.len(), 1);
+    }
+}
+
+#[tokio::test]
+async fn filter_drops_blocks_of_stripped_shards() {
+    let server = TestServer::start().await;
+    server.add_blocks(100..=157, &[], 2);
+    add_sample_blocks(&server, &[120, 152]);
+    // The account is only in shard 1
+    let filter = BlockFilter::from_accounts(&["nft.near".parse().unwrap()], BlockFilterMode::Drop);
+    let config = config_builder(&server)
+        .start_block_height(100)
+        .end_block_height(157)
+        .shards(vec![ShardId::from(0u64)])
+        .filter(filter.clone())
+        .build();
+
+    let (blocks, report) = fetch_all(config).await;
+
+    assert!(blocks.is_empty());
+    assert_eq!(report.last_delivered_block_height, Some(157));
+
+    let config = config_builder(&server)
+        .start_block_height(100)
+        .end_block_height(157)
+        .filter(filter)
+        .build();
+    let (blocks, _) = fetch_all(config).await;
+    assert_eq!(heights(&blocks), vec![120, 152]);
+    // The drop mode keeps the matching blocks intact
+    assert_eq!(
+        blocks[0].block.shards[0].receipt_execution_outcomes.len(),
+        3
+    );
+}
+
+#[tokio::test]
+async fn matched_blocks_dropped_receiver() {
+    let server = TestServer::start().await;
+    server.add_blocks(100..=157, &[], 2);
+    add_sample_blocks(&server, &[120, 152, 157]);
+    let filter = BlockFilter::from_accounts(&["nft.near".parse().unwrap()], BlockFilterMode::Drop);
+    let config = config_builder(&server)
+        .start_block_height(100)
+        .end_block_height(157)
+        .build();
+    let (sender, mut receiver) = mpsc::channel(1);
+    let is_running = Arc::new(AtomicBool::new(true));
+    let handle = tokio::spawn(start_matched_block_fetcher(
+        config,
+        filter,
+        sender,
+        is_running.clone(),
+    ));
+
+    assert_eq!(receiver.recv().await.unwrap().block_height, 120);
+    drop(receiver);
+    let report = handle.await.unwrap();
+
+    // Only the summaries received by the consumer or buffered in its channel count as delivered
+    assert!(matches!(
+        report.last_delivered_block_height,
+        Some(120) | Some(152)
+    ));
+    assert!(!is_running.load(Ordering::SeqCst));
+}
+
+#[tokio::test]
+async fn custom_chain() {
+    let server = TestServer::start().await;
+    server.add_blocks(0..=35, &[], 1);
+    let chain_id = ChainId::try_from("localnet".to_string()).unwrap();
+    let config = FetcherConfigBuilder::new()
+        .chain(ChainConfig::from_base_url(chain_id.clone(), &server.url()))
+        .chain_id(chain_id)
+        .retry_duration(Duration::from_millis(10))
+        .start_block_height(0)
+        .end_block_height(35)
+        .build();
+
+    let (blocks, _) = fetch_all(config).await;
+
+    assert_eq!(heights(&blocks), (0..=35).collect::<Vec<_>>());
+    assert_eq!(server.num_requests(&archive_path(0)), 1);
+}
+
+#[tokio::test]
+async fn gap_in_archives() {
+    let server = TestServer::start().await;
+    server.add_blocks(0..=45, &[], 1);
+    let chain_id = ChainId::try_from("localnet".to_string()).unwrap();
+    let mut chain = ChainConfig::from_base_url(chain_id.clone(), &server.url());
+    chain.archives[0].end_block_height = Some(20);
+    let config = FetcherConfigBuilder::new()
+        .chain(chain)
+        .chain_id(chain_id)
+        .retry_duration(Duration::from_millis(10))
+        .start_block_height(0)
+        .end_block_height(45)
+        .build();
+
+    let (blocks, report) = fetch_all(config).await;
+
+    // The blocks without an archive are fetched from the API
+    assert_eq!(heights(&blocks), (0..=45).collect::<Vec<_>>());
+    assert_linked(&blocks);
+    assert_eq!(blocks[10].source, BlockSource::Archive);
+    assert_eq!(blocks[25].source, BlockSource::Api);
+    assert_eq!(server.num_requests(&archive_path(20)), 0);
+    assert_eq!(server.num_requests("/v0/block/25"), 1);
+    assert_eq!(report.last_delivered_block_height, Some(45));
+}
+
+#[tokio::test]
+async fn archive_manifest_from_url() {
+    let server = TestServer::start().await;
+    server.add_blocks(0..=45, &[], 1);
+    let manifest = serde_json::json!({
+        "chain_id": "mainnet",
+        "archives": [
+            {
+                "url": format!("{}/a0/", server.url()),
+                "start_block_height": 0,
+                "end_block_height": 20,
+                "source": "archive",
+            },
+            {
+                "url": format!("{}/a1/", server.url()),
+                "start_block_height": 20,
+                "end_block_height": 30,
+                "source": "archive",
+            },
+            {
+                "url": format!("{}/private/", server.url()),
+                "start_block_height": 30,
+                "source": "archive",
+                "requires_auth": true,
+            },
+        ]
+    });
+    server.add_file("/manifest.json", serde_json::to_vec(&manifest).unwrap());
+    let config = config_builder(&server)
+        .archive_manifest_url(format!("{}/manifest.json", server.url()))
+        .start_block_height(0)
+        .end_block_height(45)
+        .build();
+
+    let (blocks, _) = fetch_all(config).await;
+
+    assert_eq!(heights(&blocks), (0..=45).collect::<Vec<_>>());
+    assert_eq!(server.num_requests("/a0/000000/000/000000000010.tgz"), 1);
+    assert_eq!(server.num_requests("/a1/000000/000/000000000020.tgz"), 1);
+    assert_eq!(server.num_requests(&archive_path(10)), 0);
+    // The private archive requires auth, so its blocks are fetched from the API
+    assert!(server
+        .requests()
+        .iter()
+        .all(|request| !request.path.starts_with("/private/")));
+    assert_eq!(blocks[30].source, BlockSource::Api);
+}
+
+#[tokio::test]
+async fn invalid_archive_manifest() {
+    let server = TestServer::start().await;
+    server.add_blocks(0..=35, &[], 1);
+    let mirror = |start_block_height, end_block_height| ArchiveEndpoint {
+        url: format!("{}/mirror/", server.url()),
+        start_block_height,
+        end_block_height,
+        source: BlockSource::Archive,
+        requires_auth: false,
+    };
+    let other_chain = ArchiveManifest {
+        chain_id: ChainId::Testnet,
+        archives: vec![mirror(0, None)],
+    };
+    let with_gap = ArchiveManifest {
+        chain_id: ChainId::Mainnet,
+        archives: vec![mirror(0, Some(10)), mirror(20, None)],
+    };
+    for (name, manifest) in [("other_chain", other_chain), ("with_gap", with_gap)] {
+        let path = format!("/{}.json", name);
+        server.add_file(&path, serde_json::to_vec(&manifest).unwrap());
+        let config = config_builder(&server)
+            .archive_manifest_url(format!("{}{}", server.url(), path))
+            .start_block_height(0)
+            .end_block_height(35)
+            .build();
+
+        let (blocks, _) = fetch_all(config).await;
+
+        // The manifest is rejected, so the default archives are used
+        assert_eq!(heights(&blocks), (0..=35).collect::<Vec<_>>(), "{}", name);
+        assert_eq!(server.num_requests(&path), 1);
+        assert_eq!(
+            server.num_requests("/mirror/000000/000/000000000000.tgz"),
+            0
+        );
+    }
+    assert_eq!(server.num_requests(&archive_path(0)), 2);
+}
+
+#[tokio::test]
+async fn archive_manifest_fallback() {
+    let server = TestServer::start().await;
+    server.add_blocks(0..=35, &[], 1);
+    let config = config_builder(&server)
+        .archive_manifest_url(format!("{}/missing.json", server.url()))
+        .start_block_height(0)
+        .end_block_height(35)
+        .build();
+
+    let (blocks, _) = fetch_all(config).await;
+
+    assert_eq!(heights(&blocks), (0..=35).collect::<Vec<_>>());
+    assert_eq!(server.num_requests("/missing.json"), 1);
+    assert_eq!(server.num_requests(&archive_path(0)), 1);
+}
+
+#[tokio::test]
+async fn archive_manifest_from_file() {
+    let server = TestServer::start().await;
+    server.add_blocks(0..=35, &[], 1);
+    let manifest = ArchiveManifest {
+        chain_id: ChainId::Mainnet,
+        archives: vec![ArchiveEndpoint {
+            url: format!("{}/mirror/", server.url()),
+            start_block_height: 0,
+            end_block_height: None,
+            source: BlockSource::Archive,
+            requires_auth: false,
+        }],
+    };
+    let path = std::env::temp_dir().join(format!("archive-manifest-{}.json", server.port()));
+    std::fs::write(&path, serde_json::to_vec(&manifest).unwrap()).unwrap();
+    let config = config_builder(&server)
+        .archive_manifest_file(path.to_string_lossy().to_string())
+        .start_block_height(0)
+        .end_block_height(35)
+        .build();
+
+    let (blocks, _) = fetch_all(config).await;
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(heights(&blocks), (0..=35).collect::<Vec<_>>());
+    assert_eq!(
+        server.num_requests("/mirror/000000/000/000000000000.tgz"),
+        1
+    );
+}
@@ -0,0 +1,127 @@
+use fastnear_neardata_fetcher::filter::{BlockFilter, BlockFilterMode};
+use fastnear_primitives::block_with_tx_hash::BlockWithTxHashes;
+use fastnear_primitives::near_primitives::types::AccountId;
+use fastnear_primitives::utils::state_change_account_id;
+use std::collections::BTreeSet;
+
+const BLOCK_PATH: &str = "../res/blocks/synthetic_block.json";
+
+fn read_block() -> BlockWithTxHashes {
+    serde_json::from_slice(&std::fs::read(BLOCK_PATH).unwrap()).unwrap()
+}
+
+fn account(account_id: &str) -> AccountId {
+    account_id.parse().unwrap()
+}
+
+fn filter(accounts: &[&str], mode: BlockFilterMode) -> BlockFilter {
+    let accounts: Vec<_> = accounts
+        .iter()
+        .map(|account_id| account(account_id))
+        .collect();
+    BlockFilter::from_accounts(&accounts, mode)
+}
+
+fn to_value(block: &BlockWithTxHashes) -> serde_json::Value {
+    serde_json::to_value(block).unwrap()
+}
+
+#[test]
+fn allowed_accounts() {
+    let mut filter = filter(&["alice.near"], BlockFilterMode::Drop);
+    filter.account_ranges = vec![
+        (Some(account("m.near")), Some(account("n.near"))),
+        (None, Some(account("bb"))),
+        (Some(account("yy")), None),
+    ];
+    for allowed in [
+        "alice.near",
+        "m.near",
+        "mike.near",
+        "n.near",
+        "aa",
+        "ba",
+        "yy",
+        "zed.near",
+    ] {
+        assert!(filter.is_account_allowed(&account(allowed)), "{}", allowed);
+    }
+    for denied in ["bob.near", "carol.near", "near", "n.near.x", "x.near"] {
+        assert!(!filter.is_account_allowed(&account(denied)), "{}", denied);
+    }
+}
+
+#[test]
+fn drop_mode() {
+    let block = read_block();
+    let filter = filter(&["carol.near"], BlockFilterMode::Drop);
+    assert!(filter.matches(&block));
+    let expected = to_value(&block);
+    assert_eq!(to_value(&filter.apply(block).unwrap()), expected);
+
+    let block = read_block();
+    let other_filter = self::filter(&["zed.near"], BlockFilterMode::Drop);
+    assert!(!other_filter.matches(&block));
+    assert!(other_filter.apply(block).is_none());
+    assert!(other_filter.summarize(&read_block()).is_none());
+}
+
+#[test]
+fn thin_mode() {
+    let filter = filter(&["carol.near"], BlockFilterMode::Thin);
+    let block = filter.apply(read_block()).unwrap();
+
+    let shard = &block.shards[1];
+    let chunk = shard.chunk.as_ref().unwrap();
+    assert_eq!(chunk.transactions.len(), 1);
+    assert_eq!(chunk.transactions[0].transaction.receiver_id, "carol.near");
+    assert_eq!(shard.receipt_execution_outcomes.len(), 1);
+    assert_eq!(
+        shard.receipt_execution_outcomes[0].receipt.receiver_id,
+        "carol.near"
+    );
+    assert!(shard
+        .state_changes
+        .iter()
+        .all(|state_change| state_change_account_id(&state_change.value) == "carol.near"));
+    let shard = &block.shards[0];
+    assert!(shard.chunk.as_ref().unwrap().transactions.is_empty());
+    assert!(shard.receipt_execution_outcomes.is_empty());
+    assert!(shard.state_changes.is_empty());
+
+    let matched_block = filter.summarize(&read_block()).unwrap();
+    assert_eq!(
+        matched_block.accounts,
+        BTreeSet::from([account("carol.near")])
+    );
+    assert_eq!(
+        matched_block.tx_hashes,
+        vec![chunk.transactions[0].transaction.hash]
+    );
+    assert_eq!(
+        matched_block.receipt_ids,
+        vec![
+            block.shards[1].receipt_execution_outcomes[0]
+                .receipt
+                .receipt_id
+        ]
+    );
+}
+
+#[test]
+fn thin_while_decoding() {
+    let data = std::fs::read(BLOCK_PATH).unwrap();
+    let decode = |filter: &BlockFilter| {
+        let mut deserializer = serde_json::Deserializer::from_slice(&data);
+        BlockWithTxHashes::deserialize_selected(&mut deserializer, filter).unwrap()
+    };
+    for mode in [BlockFilterMode::Drop, BlockFilterMode::Thin] {
+        let filter = filter(&["alice.near", "nft.near"], mode);
+        assert_eq!(
+            to_value(&decode(&filter)),
+            to_value(&filter.apply(read_block()).unwrap())
+        );
+    }
+    let filter = filter(&["zed.near"], BlockFilterMode::Thin);
+    assert!(!filter.matches(&decode(&filter)));
+}
@@ -0,0 +1,131 @@
+use fastnear_neardata_fetcher::chains::ChainConfig;
+use fastnear_neardata_fetcher::index::{BlockHashIndex, NotIndexedError};
+use fastnear_neardata_fetcher::utils::{fetch_block_by_hash, new_reqwest_client};
+use fastnear_primitives::block_with_tx_hash::BlockWithTxHashes;
+use fastnear_primitives::near_indexer_primitives::CryptoHash;
+use fastnear_primitives::near_primitives::types::BlockHeight;
+use std::time::Duration;
+
+const BLOCK_PATH: &str = "../res/blocks/synthetic_block.json";
+
+fn test_block_hash(height: BlockHeight) -> CryptoHash {
+    CryptoHash::hash_bytes(&height.to_le_bytes())
+}
+
+/// The fixture block at the given height, linked to the previous height by the test hashes.
+fn block(height: BlockHeight) -> BlockWithTxHashes {
+    let mut block: BlockWithTxHashes =
+        serde_json::from_slice(&std::fs::read(BLOCK_PATH).unwrap()).unwrap();
+    block.block.header.height = height;
+    block.block.header.hash = test_block_hash(height);
+    block.block.header.prev_hash = test_block_hash(height - 1);
+    block.block.header.prev_height = Some(height - 1);
+    block
+}
+
+fn temp_path(name: &str) -> String {
+    std::env::temp_dir()
+        .join(format!("block-hash-index-{}-{}", name, std::process::id()))
+        .to_string_lossy()
+        .to_string()
+}
+
+#[test]
+fn insert_block_indexes_previous_hash() {
+    let index = BlockHashIndex::new();
+    index.insert_block(&block(101));
+    assert_eq!(index.len(), 2);
+    assert_eq!(index.get(&test_block_hash(101)), Ok(101));
+    assert_eq!(index.get(&test_block_hash(100)), Ok(100));
+    assert_eq!(
+        index.get(&test_block_hash(99)),
+        Err(NotIndexedError(test_block_hash(99)))
+    );
+}
+
+#[test]
+fn save_load_round_trip() {
+    let index = BlockHashIndex::new();
+    for height in 101..=110 {
+        index.insert_block(&block(height));
+    }
+    let path = temp_path("round-trip");
+    index.save(&path).unwrap();
+
+    let loaded = BlockHashIndex::new();
+    loaded.load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(loaded.len(), 11);
+    for height in 100..=110 {
+        assert_eq!(loaded.get(&test_block_hash(height)), Ok(height));
+    }
+}
+
+#[test]
+fn load_rejects_truncated_file() {
+    let index = BlockHashIndex::new();
+    index.insert(test_block_hash(100), 100);
+    let path = temp_path("truncated");
+    index.save(&path).unwrap();
+    let mut content = std::fs::read(&path).unwrap();
+    content.pop();
+    std::fs::write(&path, content).unwrap();
+
+    let error = BlockHashIndex::new().load(&path).unwrap_err();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn oldest_entries_are_evicted() {
+    let index = BlockHashIndex::with_max_len(3);
+    for height in 100..105 {
+        index.insert(test_block_hash(height), height);
+    }
+    // Re-inserting doesn't refresh the entry
+    index.insert(test_block_hash(102), 102);
+    assert_eq!(index.len(), 3);
+    assert!(index.get(&test_block_hash(101)).is_err());
+    for height in 102..105 {
+        assert_eq!(index.get(&test_block_hash(height)), Ok(height));
+    }
+}
+
+#[test]
+fn loaded_entries_are_evicted_first() {
+    let saved = BlockHashIndex::new();
+    for height in 100..103 {
+        saved.insert(test_block_hash(height), height);
+    }
+    let path = temp_path("eviction");
+    saved.save(&path).unwrap();
+
+    let index = BlockHashIndex::with_max_len(4);
+    index.insert(test_block_hash(200), 200);
+    index.insert(test_block_hash(201), 201);
+    index.load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(index.len(), 4);
+    assert!(index.get(&test_block_hash(100)).is_err());
+    for height in [101, 102, 200, 201] {
+        assert_eq!(index.get(&test_block_hash(height)), Ok(height));
+    }
+}
+
+#[tokio::test]
+async fn fetch_by_unknown_hash_is_not_indexed() {
+    let index = BlockHashIndex::new();
+    index.insert_block(&block(101));
+    let result = fetch_block_by_hash(
+        &new_reqwest_client(),
+        &index,
+        &test_block_hash(50),
+        Duration::from_secs(1),
+        &ChainConfig::mainnet(),
+    )
+    .await;
+    assert_eq!(
+        result.map(|block| block.is_some()),
+        Err(NotIndexedError(test_block_hash(50)))
+    );
+}
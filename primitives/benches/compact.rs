@@ -0,0 +1,115 @@
+//! Compares the compact binary encoding of the blocks with JSON, the format of the archives, on an
+//! archive of the synthetic blocks and on each of the heavy blocks from `res/blocks/heavy`.
+//! Run with `cargo bench -p fastnear-primitives --features compact`.
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use fastnear_primitives::block_with_tx_hash::BlockWithTxHashes;
+use fastnear_primitives::compact::CompactEncoding;
+use std::io::Read;
+
+const BLOCK_PATH: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/../res/blocks/synthetic_block.json"
+);
+const HEAVY_BLOCKS_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../res/blocks/heavy");
+/// The number of blocks in a single archive
+const NUM_BLOCKS: usize = 10;
+
+/// The sets of blocks by name: an archive of the synthetic blocks and every heavy block on its own
+fn read_block_sets() -> Vec<(String, Vec<BlockWithTxHashes>)> {
+    let json = std::fs::read(BLOCK_PATH).unwrap();
+    let archive = (0..NUM_BLOCKS)
+        .map(|_| serde_json::from_slice(&json).unwrap())
+        .collect();
+    let mut block_sets = vec![("synthetic_archive".to_string(), archive)];
+    let mut heavy_blocks: Vec<_> = std::fs::read_dir(HEAVY_BLOCKS_DIR)
+        .unwrap()
+        .filter_map(|entry| {
+            let path = entry.unwrap().path();
+            let name = path.file_name()?.to_str()?.strip_suffix(".json.gz")?;
+            let mut data = vec![];
+            flate2::read::GzDecoder::new(std::fs::File::open(&path).unwrap())
+                .read_to_end(&mut data)
+                .unwrap();
+            Some((
+                name.to_string(),
+                vec![serde_json::from_slice(&data).unwrap()],
+            ))
+        })
+        .collect();
+    heavy_blocks.sort_by(|(a, _), (b, _)| a.cmp(b));
+    block_sets.extend(heavy_blocks);
+    block_sets
+}
+
+fn bench_encoding(c: &mut Criterion) {
+    let block_sets = read_block_sets();
+    let encoded: Vec<_> = block_sets
+        .iter()
+        .map(|(name, blocks)| {
+            let json: Vec<Vec<u8>> = blocks
+                .iter()
+                .map(|block| serde_json::to_vec(block).unwrap())
+                .collect();
+            let compact: Vec<Vec<u8>> = blocks
+                .iter()
+                .map(|block| block.to_compact().unwrap())
+                .collect();
+            let json_size: usize = json.iter().map(Vec::len).sum();
+            let compact_size: usize = compact.iter().map(Vec::len).sum();
+            println!(
+                "Size of {} ({} blocks): JSON {} bytes, compact {} bytes ({:.2}x)",
+                name,
+                blocks.len(),
+                json_size,
+                compact_size,
+                json_size as f64 / compact_size as f64
+            );
+            (json, compact)
+        })
+        .collect();
+
+    let mut group = c.benchmark_group("encode");
+    for (name, blocks) in &block_sets {
+        group.throughput(Throughput::Elements(blocks.len() as u64));
+        group.bench_with_input(BenchmarkId::new("json", name), blocks, |b, blocks| {
+            b.iter(|| {
+                for block in blocks {
+                    serde_json::to_vec(block).unwrap();
+                }
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("compact", name), blocks, |b, blocks| {
+            b.iter(|| {
+                for block in blocks {
+                    block.to_compact().unwrap();
+                }
+            })
+        });
+    }
+    group.finish();
+
+    let mut group = c.benchmark_group("decode");
+    for ((name, blocks), (json, compact)) in block_sets.iter().zip(&encoded) {
+        group.throughput(Throughput::Elements(blocks.len() as u64));
+        // The plain `serde_json` decoding of the whole blocks, without the selection or the SIMD
+        // parser of the fetcher, see the `json` benchmark for those
+        group.bench_with_input(BenchmarkId::new("json", name), json, |b, json| {
+            b.iter(|| {
+                for data in json {
+                    serde_json::from_slice::<BlockWithTxHashes>(data).unwrap();
+                }
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("compact", name), compact, |b, compact| {
+            b.iter(|| {
+                for data in compact {
+                    BlockWithTxHashes::from_compact(data).unwrap();
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_encoding);
+criterion_main!(benches);
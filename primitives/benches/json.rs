@@ -0,0 +1,77 @@
+//! Compares the JSON decoding of the blocks with `serde_json` and with the SIMD parser on the heavy
+//! blocks from `res/blocks/heavy`. The `parse` group only parses the JSON without building the
+//! blocks, so the difference with the `decode` group is the time spent in the NEAR types.
+//! The `tolerant` case measures the tolerant decoding.
+//! Run with `cargo bench -p fastnear-primitives --bench json --features simd-json`, without the
+//! feature only `serde_json` is measured.
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use fastnear_primitives::block_with_tx_hash::BlockWithTxHashes;
+use serde::de::IgnoredAny;
+use std::io::Read;
+
+const HEAVY_BLOCKS_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../res/blocks/heavy");
+
+/// The decompressed heavy blocks by the file name without the extension
+fn read_heavy_blocks() -> Vec<(String, Vec<u8>)> {
+    let mut blocks: Vec<_> = std::fs::read_dir(HEAVY_BLOCKS_DIR)
+        .unwrap()
+        .filter_map(|entry| {
+            let path = entry.unwrap().path();
+            let name = path.file_name()?.to_str()?.strip_suffix(".json.gz")?;
+            let mut data = vec![];
+            flate2::read::GzDecoder::new(std::fs::File::open(&path).unwrap())
+                .read_to_end(&mut data)
+                .unwrap();
+            Some((name.to_string(), data))
+        })
+        .collect();
+    blocks.sort();
+    blocks
+}
+
+fn bench_decoding(c: &mut Criterion) {
+    let blocks = read_heavy_blocks();
+
+    let mut group = c.benchmark_group("parse");
+    for (name, data) in &blocks {
+        group.throughput(Throughput::Bytes(data.len() as u64));
+        group.bench_with_input(BenchmarkId::new("serde_json", name), data, |b, data| {
+            b.iter(|| serde_json::from_slice::<IgnoredAny>(data).unwrap())
+        });
+        #[cfg(feature = "simd-json")]
+        group.bench_with_input(BenchmarkId::new("simd_json", name), data, |b, data| {
+            b.iter_batched_ref(
+                || data.clone(),
+                |data| fastnear_primitives::json::from_mut_slice::<IgnoredAny>(data).unwrap(),
+                criterion::BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+
+    let mut group = c.benchmark_group("decode");
+    for (name, data) in &blocks {
+        group.throughput(Throughput::Bytes(data.len() as u64));
+        group.bench_with_input(BenchmarkId::new("serde_json", name), data, |b, data| {
+            b.iter(|| serde_json::from_slice::<BlockWithTxHashes>(data).unwrap())
+        });
+        // The decoding of the fetcher with `tolerant_decoding`, always with `serde_json`
+        group.bench_with_input(BenchmarkId::new("tolerant", name), data, |b, data| {
+            b.iter(|| BlockWithTxHashes::from_slice_tolerant(data).unwrap())
+        });
+        #[cfg(feature = "simd-json")]
+        group.bench_with_input(BenchmarkId::new("simd_json", name), data, |b, data| {
+            b.iter_batched_ref(
+                || data.clone(),
+                |data| {
+                    fastnear_primitives::json::from_mut_slice::<BlockWithTxHashes>(data).unwrap()
+                },
+                criterion::BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_decoding);
+criterion_main!(benches);
@@ -0,0 +1,169 @@
+//! Decoding of the function call arguments.
+//!
+//! The arguments are decoded as JSON by default, the arguments that are not JSON are returned as
+//! binary. The contracts with binary arguments, e.g. Borsh, can register their own decoders by
+//! the receiver account and the method name.
+use crate::iter::{BlockItem, ReceiptAction};
+use near_indexer_primitives::IndexerTransactionWithOutcome;
+use near_primitives::serialize::to_base64;
+use near_primitives::types::AccountId;
+use near_primitives::views::ActionView;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case", tag = "type", content = "value")]
+pub enum DecodedArgs {
+    Empty,
+    Json(serde_json::Value),
+    /// Decoded by the registered decoder
+    Custom(serde_json::Value),
+    /// Serialized as base64
+    Binary(#[serde(serialize_with = "serialize_base64")] Vec<u8>),
+    /// The registered decoder failed to decode the arguments
+    Invalid {
+        error: String,
+        #[serde(serialize_with = "serialize_base64")]
+        args: Vec<u8>,
+    },
+}
+
+impl DecodedArgs {
+    /// Decodes the arguments as JSON, or returns them as binary.
+    pub fn from_bytes(args: &[u8]) -> Self {
+        if args.is_empty() {
+            return DecodedArgs::Empty;
+        }
+        match serde_json::from_slice(args) {
+            Ok(value) => DecodedArgs::Json(value),
+            Err(_) => DecodedArgs::Binary(args.to_vec()),
+        }
+    }
+
+    /// The JSON value of the arguments, if they were decoded.
+    pub fn as_json(&self) -> Option<&serde_json::Value> {
+        match self {
+            DecodedArgs::Json(value) | DecodedArgs::Custom(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+fn serialize_base64<S: serde::Serializer>(args: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&to_base64(args))
+}
+
+/// Decodes the arguments of a method of a contract, e.g. with the Borsh schema of the method.
+pub trait ArgsDecoder: Send + Sync {
+    fn decode(&self, args: &[u8]) -> Result<serde_json::Value, String>;
+}
+
+impl<F> ArgsDecoder for F
+where
+    F: Fn(&[u8]) -> Result<serde_json::Value, String> + Send + Sync,
+{
+    fn decode(&self, args: &[u8]) -> Result<serde_json::Value, String> {
+        self(args)
+    }
+}
+
+/// The registered decoders by the receiver account and the method name.
+#[derive(Clone, Default)]
+pub struct ArgsDecoderRegistry {
+    /// The decoders by the receiver and the method name, nested so the lookups don't allocate
+    decoders: HashMap<AccountId, HashMap<String, Arc<dyn ArgsDecoder>>>,
+}
+
+impl ArgsDecoderRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the decoder or replaces the existing decoder of the method.
+    pub fn register(
+        &mut self,
+        receiver_id: AccountId,
+        method_name: impl Into<String>,
+        decoder: impl ArgsDecoder + 'static,
+    ) {
+        self.decoders
+            .entry(receiver_id)
+            .or_default()
+            .insert(method_name.into(), Arc::new(decoder));
+    }
+
+    pub fn get(&self, receiver_id: &AccountId, method_name: &str) -> Option<&dyn ArgsDecoder> {
+        self.decoders
+            .get(receiver_id)?
+            .get(method_name)
+            .map(|decoder| decoder.as_ref())
+    }
+
+    /// Decodes the arguments with the registered decoder, or as JSON if there is none.
+    pub fn decode(&self, receiver_id: &AccountId, method_name: &str, args: &[u8]) -> DecodedArgs {
+        match self.get(receiver_id, method_name) {
+            Some(decoder) => match decoder.decode(args) {
+                Ok(value) => DecodedArgs::Custom(value),
+                Err(error) => DecodedArgs::Invalid {
+                    error,
+                    args: args.to_vec(),
+                },
+            },
+            None => DecodedArgs::from_bytes(args),
+        }
+    }
+
+    /// Decodes the arguments of the function call action. `None` for other actions.
+    pub fn decode_action(
+        &self,
+        receiver_id: &AccountId,
+        action: &ActionView,
+    ) -> Option<DecodedArgs> {
+        match action {
+            ActionView::FunctionCall {
+                method_name, args, ..
+            } => Some(self.decode(receiver_id, method_name, args)),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Debug for ArgsDecoderRegistry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ArgsDecoderRegistry")
+            .field(
+                "decoders",
+                &self
+                    .decoders
+                    .iter()
+                    .flat_map(|(receiver_id, methods)| {
+                        methods
+                            .keys()
+                            .map(move |method_name| (receiver_id, method_name))
+                    })
+                    .collect::<Vec<_>>(),
+            )
+            .finish()
+    }
+}
+
+impl ReceiptAction<'_> {
+    /// The decoded arguments of the function call. `None` for other actions.
+    pub fn args(&self, registry: &ArgsDecoderRegistry) -> Option<DecodedArgs> {
+        registry.decode_action(&self.receipt.receiver_id, self.action)
+    }
+}
+
+impl BlockItem<&IndexerTransactionWithOutcome> {
+    /// The decoded arguments of every action of the transaction. `None` for the actions other
+    /// than function calls.
+    pub fn args(&self, registry: &ArgsDecoderRegistry) -> Vec<Option<DecodedArgs>> {
+        let transaction = &self.item.transaction;
+        transaction
+            .actions
+            .iter()
+            .map(|action| registry.decode_action(&transaction.receiver_id, action))
+            .collect()
+    }
+}
@@ -0,0 +1,721 @@
+//! Arrow record batches and Parquet files from the blocks.
+//!
+//! Every block is split into 8 tables: blocks, chunks, transactions, actions, receipts, outcomes,
+//! logs and state changes. The schemas are stable, new columns are only appended.
+//! The hashes are base58 strings and the balances are decimal strings, since they don't fit into
+//! 64-bit integers.
+use crate::block_with_tx_hash::BlockWithTxHashes;
+use crate::utils::{action_kind, cause_receipt_id, state_change_account_id};
+use arrow_array::builder::{
+    ArrayBuilder, BooleanBuilder, ListBuilder, StringBuilder, UInt64Builder,
+};
+use arrow_array::{ArrayRef, RecordBatch};
+use arrow_schema::{ArrowError, DataType, Field, Schema, SchemaRef};
+use near_primitives::serialize::to_base64;
+use near_primitives::types::BlockHeight;
+use near_primitives::views::{
+    ActionView, ExecutionStatusView, ReceiptEnumView, StateChangeCauseView,
+};
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::errors::ParquetError;
+use parquet::file::properties::WriterProperties;
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+#[derive(Debug)]
+pub enum ArrowExportError {
+    ArrowError(ArrowError),
+    ParquetError(ParquetError),
+    IoError(std::io::Error),
+    /// The block is not above the last written block
+    UnorderedBlock {
+        block_height: BlockHeight,
+        last_block_height: BlockHeight,
+    },
+}
+
+impl From<ArrowError> for ArrowExportError {
+    fn from(error: ArrowError) -> Self {
+        ArrowExportError::ArrowError(error)
+    }
+}
+
+impl From<ParquetError> for ArrowExportError {
+    fn from(error: ParquetError) -> Self {
+        ArrowExportError::ParquetError(error)
+    }
+}
+
+impl From<std::io::Error> for ArrowExportError {
+    fn from(error: std::io::Error) -> Self {
+        ArrowExportError::IoError(error)
+    }
+}
+
+impl Display for ArrowExportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ArrowExportError::ArrowError(e) => write!(f, "Arrow error: {}", e),
+            ArrowExportError::ParquetError(e) => write!(f, "Parquet error: {}", e),
+            ArrowExportError::IoError(e) => write!(f, "IO error: {}", e),
+            ArrowExportError::UnorderedBlock {
+                block_height,
+                last_block_height,
+            } => write!(
+                f,
+                "Block {} is written after block {}, the blocks have to be in ascending order",
+                block_height, last_block_height
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ArrowExportError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Table {
+    Blocks,
+    Chunks,
+    Transactions,
+    Actions,
+    Receipts,
+    Outcomes,
+    Logs,
+    StateChanges,
+}
+
+impl Table {
+    pub const ALL: [Table; 8] = [
+        Table::Blocks,
+        Table::Chunks,
+        Table::Transactions,
+        Table::Actions,
+        Table::Receipts,
+        Table::Outcomes,
+        Table::Logs,
+        Table::StateChanges,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Table::Blocks => "blocks",
+            Table::Chunks => "chunks",
+            Table::Transactions => "transactions",
+            Table::Actions => "actions",
+            Table::Receipts => "receipts",
+            Table::Outcomes => "outcomes",
+            Table::Logs => "logs",
+            Table::StateChanges => "state_changes",
+        }
+    }
+
+    pub fn schema(&self) -> SchemaRef {
+        let u64_field = |name| Field::new(name, DataType::UInt64, false);
+        let str_field = |name| Field::new(name, DataType::Utf8, false);
+        let opt_u64_field = |name| Field::new(name, DataType::UInt64, true);
+        let opt_str_field = |name| Field::new(name, DataType::Utf8, true);
+        let fields = match self {
+            Table::Blocks => vec![
+                u64_field("block_height"),
+                str_field("block_hash"),
+                str_field("prev_block_hash"),
+                u64_field("block_timestamp"),
+                str_field("epoch_id"),
+                str_field("author_id"),
+                str_field("gas_price"),
+                str_field("total_supply"),
+                u64_field("num_chunks"),
+            ],
+            Table::Chunks => vec![
+                u64_field("block_height"),
+                u64_field("block_timestamp"),
+                u64_field("shard_id"),
+                str_field("chunk_hash"),
+                str_field("author_id"),
+                u64_field("gas_used"),
+                u64_field("gas_limit"),
+                str_field("balance_burnt"),
+                u64_field("num_transactions"),
+                u64_field("num_receipts"),
+            ],
+            Table::Transactions => vec![
+                u64_field("block_height"),
+                u64_field("block_timestamp"),
+                u64_field("shard_id"),
+                str_field("tx_hash"),
+                str_field("signer_id"),
+                str_field("public_key"),
+                u64_field("nonce"),
+                str_field("receiver_id"),
+                u64_field("priority_fee"),
+                u64_field("num_actions"),
+                str_field("status"),
+                u64_field("gas_burnt"),
+                str_field("tokens_burnt"),
+                opt_str_field("converted_receipt_id"),
+            ],
+            Table::Actions => vec![
+                u64_field("block_height"),
+                u64_field("block_timestamp"),
+                u64_field("shard_id"),
+                opt_str_field("tx_hash"),
+                str_field("receipt_id"),
+                u64_field("action_index"),
+                str_field("predecessor_id"),
+                str_field("receiver_id"),
+                str_field("signer_id"),
+                str_field("action_kind"),
+                opt_str_field("method_name"),
+                opt_str_field("args_base64"),
+                opt_u64_field("gas"),
+                opt_str_field("deposit"),
+                opt_str_field("stake"),
+            ],
+            Table::Receipts => vec![
+                u64_field("block_height"),
+                u64_field("block_timestamp"),
+                u64_field("shard_id"),
+                str_field("receipt_id"),
+                opt_str_field("tx_hash"),
+                str_field("predecessor_id"),
+                str_field("receiver_id"),
+                str_field("receipt_kind"),
+                u64_field("priority"),
+            ],
+            Table::Outcomes => vec![
+                u64_field("block_height"),
+                u64_field("block_timestamp"),
+                u64_field("shard_id"),
+                str_field("id"),
+                opt_str_field("tx_hash"),
+                str_field("outcome_kind"),
+                str_field("executor_id"),
+                str_field("status"),
+                u64_field("gas_burnt"),
+                str_field("tokens_burnt"),
+                Field::new(
+                    "receipt_ids",
+                    DataType::List(Arc::new(Field::new("item", DataType::Utf8, true))),
+                    false,
+                ),
+            ],
+            Table::Logs => vec![
+                u64_field("block_height"),
+                u64_field("block_timestamp"),
+                u64_field("shard_id"),
+                str_field("outcome_id"),
+                opt_str_field("tx_hash"),
+                str_field("executor_id"),
+                u64_field("log_index"),
+                str_field("log"),
+                Field::new("is_event", DataType::Boolean, false),
+            ],
+            Table::StateChanges => vec![
+                u64_field("block_height"),
+                u64_field("block_timestamp"),
+                u64_field("shard_id"),
+                opt_str_field("tx_hash"),
+                str_field("cause_type"),
+                opt_str_field("cause_hash"),
+                str_field("change_type"),
+                str_field("account_id"),
+                str_field("change"),
+            ],
+        };
+        Arc::new(Schema::new(fields))
+    }
+}
+
+/// A value of a row, matching the column type.
+enum Cell {
+    U64(Option<u64>),
+    Str(Option<String>),
+    Bool(bool),
+    StrList(Vec<String>),
+}
+
+fn u64_cell(value: impl Into<u64>) -> Cell {
+    Cell::U64(Some(value.into()))
+}
+
+fn str_cell(value: impl ToString) -> Cell {
+    Cell::Str(Some(value.to_string()))
+}
+
+fn opt_str_cell<T: ToString>(value: Option<T>) -> Cell {
+    Cell::Str(value.map(|value| value.to_string()))
+}
+
+/// The Arrow builder of a column.
+enum ColumnBuilder {
+    U64(UInt64Builder),
+    Str(StringBuilder),
+    Bool(BooleanBuilder),
+    StrList(ListBuilder<StringBuilder>),
+}
+
+impl ColumnBuilder {
+    fn new(field: &Field) -> Self {
+        match field.data_type() {
+            DataType::UInt64 => ColumnBuilder::U64(UInt64Builder::new()),
+            DataType::Utf8 => ColumnBuilder::Str(StringBuilder::new()),
+            DataType::Boolean => ColumnBuilder::Bool(BooleanBuilder::new()),
+            DataType::List(item) => ColumnBuilder::StrList(
+                ListBuilder::new(StringBuilder::new()).with_field(item.clone()),
+            ),
+            data_type => unreachable!("Unsupported data type {}", data_type),
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            ColumnBuilder::U64(builder) => builder.len(),
+            ColumnBuilder::Str(builder) => builder.len(),
+            ColumnBuilder::Bool(builder) => builder.len(),
+            ColumnBuilder::StrList(builder) => builder.len(),
+        }
+    }
+
+    fn append(&mut self, cell: Cell) {
+        match (self, cell) {
+            (ColumnBuilder::U64(builder), Cell::U64(value)) => builder.append_option(value),
+            (ColumnBuilder::Str(builder), Cell::Str(value)) => builder.append_option(value),
+            (ColumnBuilder::Bool(builder), Cell::Bool(value)) => builder.append_value(value),
+            (ColumnBuilder::StrList(builder), Cell::StrList(values)) => {
+                for value in values {
+                    builder.values().append_value(value);
+                }
+                builder.append(true);
+            }
+            _ => unreachable!("The value doesn't match the column type"),
+        }
+    }
+
+    /// Builds the array, keeping the values in the builder.
+    fn finish_cloned(&self) -> ArrayRef {
+        match self {
+            ColumnBuilder::U64(builder) => Arc::new(builder.finish_cloned()),
+            ColumnBuilder::Str(builder) => Arc::new(builder.finish_cloned()),
+            ColumnBuilder::Bool(builder) => Arc::new(builder.finish_cloned()),
+            ColumnBuilder::StrList(builder) => Arc::new(builder.finish_cloned()),
+        }
+    }
+
+    /// Builds the array and clears the builder.
+    fn finish(&mut self) -> ArrayRef {
+        match self {
+            ColumnBuilder::U64(builder) => Arc::new(builder.finish()),
+            ColumnBuilder::Str(builder) => Arc::new(builder.finish()),
+            ColumnBuilder::Bool(builder) => Arc::new(builder.finish()),
+            ColumnBuilder::StrList(builder) => Arc::new(builder.finish()),
+        }
+    }
+}
+
+fn status_kind(status: &ExecutionStatusView) -> &'static str {
+    match status {
+        ExecutionStatusView::Unknown => "unknown",
+        ExecutionStatusView::Failure(_) => "failure",
+        ExecutionStatusView::SuccessValue(_) => "success_value",
+        ExecutionStatusView::SuccessReceiptId(_) => "success_receipt_id",
+    }
+}
+
+/// The `type` tag of the serialized value, e.g. `account_update`.
+fn json_type(value: &serde_json::Value) -> String {
+    value["type"].as_str().unwrap_or_default().to_string()
+}
+
+/// Appends the rows of the blocks to the Arrow builders of the tables and builds the record
+/// batches.
+pub struct RecordBatchBuilder {
+    columns: BTreeMap<Table, Vec<ColumnBuilder>>,
+}
+
+impl Default for RecordBatchBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RecordBatchBuilder {
+    pub fn new() -> Self {
+        Self {
+            columns: Table::ALL
+                .iter()
+                .map(|table| {
+                    let schema = table.schema();
+                    let columns = schema
+                        .fields()
+                        .iter()
+                        .map(|field| ColumnBuilder::new(field));
+                    (*table, columns.collect())
+                })
+                .collect(),
+        }
+    }
+
+    pub fn num_rows(&self, table: Table) -> usize {
+        self.columns[&table][0].len()
+    }
+
+    fn push<const N: usize>(&mut self, table: Table, row: [Cell; N]) {
+        let columns = self.columns.get_mut(&table).unwrap();
+        assert_eq!(columns.len(), N, "Invalid row of table {}", table.name());
+        for (column, cell) in columns.iter_mut().zip(row) {
+            column.append(cell);
+        }
+    }
+
+    pub fn append_block(&mut self, block: &BlockWithTxHashes) {
+        let header = &block.block.header;
+        let block_height = header.height;
+        let block_timestamp = header.timestamp_nanosec;
+        self.push(
+            Table::Blocks,
+            [
+                u64_cell(block_height),
+                str_cell(header.hash),
+                str_cell(header.prev_hash),
+                u64_cell(block_timestamp),
+                str_cell(header.epoch_id),
+                str_cell(&block.block.author),
+                str_cell(header.gas_price),
+                str_cell(header.total_supply),
+                u64_cell(header.chunks_included),
+            ],
+        );
+
+        for shard in &block.shards {
+            let Some(chunk) = &shard.chunk else {
+                continue;
+            };
+            self.push(
+                Table::Chunks,
+                [
+                    u64_cell(block_height),
+                    u64_cell(block_timestamp),
+                    u64_cell(shard.shard_id),
+                    str_cell(chunk.header.chunk_hash),
+                    str_cell(&chunk.author),
+                    u64_cell(chunk.header.gas_used),
+                    u64_cell(chunk.header.gas_limit),
+                    str_cell(chunk.header.balance_burnt),
+                    u64_cell(chunk.transactions.len() as u64),
+                    u64_cell(chunk.receipts.len() as u64),
+                ],
+            );
+        }
+
+        for tx in block.transactions() {
+            let transaction = &tx.transaction;
+            let outcome = &tx.outcome.execution_outcome.outcome;
+            self.push(
+                Table::Transactions,
+                [
+                    u64_cell(block_height),
+                    u64_cell(block_timestamp),
+                    u64_cell(tx.shard_id),
+                    str_cell(transaction.hash),
+                    str_cell(&transaction.signer_id),
+                    str_cell(&transaction.public_key),
+                    u64_cell(transaction.nonce),
+                    str_cell(&transaction.receiver_id),
+                    u64_cell(transaction.priority_fee),
+                    u64_cell(transaction.actions.len() as u64),
+                    str_cell(status_kind(&outcome.status)),
+                    u64_cell(outcome.gas_burnt),
+                    str_cell(outcome.tokens_burnt),
+                    opt_str_cell(outcome.receipt_ids.first()),
+                ],
+            );
+        }
+
+        for action in block.actions() {
+            let (method_name, args_base64, gas, deposit, stake) = match action.action {
+                ActionView::FunctionCall {
+                    method_name,
+                    args,
+                    gas,
+                    deposit,
+                } => (
+                    Some(method_name.clone()),
+                    Some(to_base64(args)),
+                    Some(*gas),
+                    Some(*deposit),
+                    None,
+                ),
+                ActionView::Transfer { deposit } => (None, None, None, Some(*deposit), None),
+                ActionView::Stake { stake, .. } => (None, None, None, None, Some(*stake)),
+                _ => (None, None, None, None, None),
+            };
+            let action_kind = serde_json::to_value(action_kind(action.action)).unwrap();
+            let receipt = action.receipt;
+            self.push(
+                Table::Actions,
+                [
+                    u64_cell(block_height),
+                    u64_cell(block_timestamp),
+                    u64_cell(action.shard_id),
+                    opt_str_cell(action.tx_hash),
+                    str_cell(receipt.receipt_id),
+                    u64_cell(action.index as u64),
+                    str_cell(&receipt.predecessor_id),
+                    str_cell(&receipt.receiver_id),
+                    str_cell(action.signer_id),
+                    str_cell(action_kind.as_str().unwrap()),
+                    opt_str_cell(method_name),
+                    opt_str_cell(args_base64),
+                    Cell::U64(gas),
+                    opt_str_cell(deposit),
+                    opt_str_cell(stake),
+                ],
+            );
+        }
+
+        for receipt in block.receipts() {
+            let receipt_kind = match receipt.receipt {
+                ReceiptEnumView::Action { .. } => "action",
+                ReceiptEnumView::Data { .. } => "data",
+            };
+            self.push(
+                Table::Receipts,
+                [
+                    u64_cell(block_height),
+                    u64_cell(block_timestamp),
+                    u64_cell(receipt.shard_id),
+                    str_cell(receipt.receipt_id),
+                    opt_str_cell(receipt.tx_hash),
+                    str_cell(&receipt.predecessor_id),
+                    str_cell(&receipt.receiver_id),
+                    str_cell(receipt_kind),
+                    u64_cell(receipt.priority),
+                ],
+            );
+        }
+
+        for outcome in block.outcomes() {
+            // The ID of the transaction outcome is the transaction hash
+            let outcome_kind = if Some(outcome.id) == outcome.tx_hash {
+                "transaction"
+            } else {
+                "receipt"
+            };
+            let execution_outcome = &outcome.outcome;
+            self.push(
+                Table::Outcomes,
+                [
+                    u64_cell(block_height),
+                    u64_cell(block_timestamp),
+                    u64_cell(outcome.shard_id),
+                    str_cell(outcome.id),
+                    opt_str_cell(outcome.tx_hash),
+                    str_cell(outcome_kind),
+                    str_cell(&execution_outcome.executor_id),
+                    str_cell(status_kind(&execution_outcome.status)),
+                    u64_cell(execution_outcome.gas_burnt),
+                    str_cell(execution_outcome.tokens_burnt),
+                    Cell::StrList(
+                        execution_outcome
+                            .receipt_ids
+                            .iter()
+                            .map(|receipt_id| receipt_id.to_string())
+                            .collect(),
+                    ),
+                ],
+            );
+            for (log_index, log) in execution_outcome.logs.iter().enumerate() {
+                self.push(
+                    Table::Logs,
+                    [
+                        u64_cell(block_height),
+                        u64_cell(block_timestamp),
+                        u64_cell(outcome.shard_id),
+                        str_cell(outcome.id),
+                        opt_str_cell(outcome.tx_hash),
+                        str_cell(&execution_outcome.executor_id),
+                        u64_cell(log_index as u64),
+                        str_cell(log),
+                        Cell::Bool(log.starts_with(crate::events::EVENT_JSON_PREFIX)),
+                    ],
+                );
+            }
+        }
+
+        for state_change in block.state_changes() {
+            let cause_hash = match &state_change.cause {
+                StateChangeCauseView::TransactionProcessing { tx_hash } => Some(*tx_hash),
+                cause => cause_receipt_id(cause),
+            };
+            let cause = serde_json::to_value(&state_change.cause).unwrap();
+            let mut value = serde_json::to_value(&state_change.value).unwrap();
+            self.push(
+                Table::StateChanges,
+                [
+                    u64_cell(block_height),
+                    u64_cell(block_timestamp),
+                    u64_cell(state_change.shard_id),
+                    opt_str_cell(state_change.tx_hash),
+                    str_cell(json_type(&cause)),
+                    opt_str_cell(cause_hash),
+                    str_cell(json_type(&value)),
+                    str_cell(state_change_account_id(&state_change.value)),
+                    str_cell(value["change"].take()),
+                ],
+            );
+        }
+    }
+
+    /// Builds the record batches of all tables and clears the rows.
+    pub fn finish(&mut self) -> Result<BTreeMap<Table, RecordBatch>, ArrowError> {
+        self.columns
+            .iter_mut()
+            .map(|(table, columns)| {
+                let columns = columns.iter_mut().map(ColumnBuilder::finish).collect();
+                Ok((*table, RecordBatch::try_new(table.schema(), columns)?))
+            })
+            .collect()
+    }
+
+    /// Builds the record batches of all tables, keeping the rows.
+    fn finish_cloned(&self) -> Result<BTreeMap<Table, RecordBatch>, ArrowError> {
+        self.columns
+            .iter()
+            .map(|(table, columns)| {
+                let columns = columns.iter().map(ColumnBuilder::finish_cloned).collect();
+                Ok((*table, RecordBatch::try_new(table.schema(), columns)?))
+            })
+            .collect()
+    }
+}
+
+/// Converts the blocks to the record batches of all tables.
+pub fn to_record_batches(
+    blocks: &[BlockWithTxHashes],
+) -> Result<BTreeMap<Table, RecordBatch>, ArrowError> {
+    let mut builder = RecordBatchBuilder::new();
+    for block in blocks {
+        builder.append_block(block);
+    }
+    builder.finish()
+}
+
+/// Writes the blocks to Parquet files, one file per table and height range:
+/// `{dir}/{table}/{start_height:012}-{end_height:012}.parquet` with the exclusive end height.
+/// The ranges are aligned to `blocks_per_file`, so the files don't depend on the first block.
+/// The blocks have to be written in ascending order, a lower block fails with `UnorderedBlock`.
+/// The existing files are never overwritten, so writing a range of a previous export fails with
+/// the `AlreadyExists` IO error.
+/// If the files of a range can't be written, its blocks are kept and the files are written again
+/// by the next call.
+pub struct ParquetWriter {
+    dir: PathBuf,
+    blocks_per_file: BlockHeight,
+    properties: WriterProperties,
+    builder: RecordBatchBuilder,
+    range_start: Option<BlockHeight>,
+    last_block_height: Option<BlockHeight>,
+    written_files: Vec<PathBuf>,
+}
+
+impl ParquetWriter {
+    pub fn new(dir: impl Into<PathBuf>, blocks_per_file: BlockHeight) -> Self {
+        assert!(blocks_per_file > 0, "blocks_per_file must be positive");
+        Self {
+            dir: dir.into(),
+            blocks_per_file,
+            properties: WriterProperties::builder()
+                .set_compression(Compression::SNAPPY)
+                .build(),
+            builder: RecordBatchBuilder::new(),
+            range_start: None,
+            last_block_height: None,
+            written_files: vec![],
+        }
+    }
+
+    pub fn with_properties(mut self, properties: WriterProperties) -> Self {
+        self.properties = properties;
+        self
+    }
+
+    /// The files written so far.
+    pub fn written_files(&self) -> &[PathBuf] {
+        &self.written_files
+    }
+
+    pub fn write_block(&mut self, block: &BlockWithTxHashes) -> Result<(), ArrowExportError> {
+        let block_height = block.block.header.height;
+        if let Some(last_block_height) = self
+            .last_block_height
+            .filter(|last_block_height| block_height <= *last_block_height)
+        {
+            return Err(ArrowExportError::UnorderedBlock {
+                block_height,
+                last_block_height,
+            });
+        }
+        let range_start = block_height - block_height % self.blocks_per_file;
+        if self
+            .range_start
+            .is_some_and(|current_start| current_start != range_start)
+        {
+            self.flush()?;
+        }
+        self.range_start = Some(range_start);
+        self.last_block_height = Some(block_height);
+        self.builder.append_block(block);
+        Ok(())
+    }
+
+    /// Writes the files of the current range. On an error, the files written so far are removed
+    /// and the blocks are kept.
+    fn flush(&mut self) -> Result<(), ArrowExportError> {
+        let Some(range_start) = self.range_start else {
+            return Ok(());
+        };
+        let range_end = range_start + self.blocks_per_file;
+        let mut paths = vec![];
+        let result = self
+            .builder
+            .finish_cloned()
+            .map_err(ArrowExportError::from)
+            .and_then(|batches| {
+                for (table, batch) in batches {
+                    let dir = self.dir.join(table.name());
+                    std::fs::create_dir_all(&dir)?;
+                    let path = dir.join(format!("{:012}-{:012}.parquet", range_start, range_end));
+                    let file = std::fs::File::options()
+                        .write(true)
+                        .create_new(true)
+                        .open(&path)?;
+                    paths.push(path);
+                    let mut writer =
+                        ArrowWriter::try_new(file, batch.schema(), Some(self.properties.clone()))?;
+                    writer.write(&batch)?;
+                    writer.close()?;
+                }
+                Ok(())
+            });
+        if let Err(err) = result {
+            for path in paths {
+                let _ = std::fs::remove_file(path);
+            }
+            return Err(err);
+        }
+        self.builder = RecordBatchBuilder::new();
+        self.range_start = None;
+        self.written_files.extend(paths);
+        Ok(())
+    }
+
+    /// Writes the remaining blocks and returns all written files. On an error, the remaining
+    /// blocks are kept, so it can be called again.
+    pub fn finish(&mut self) -> Result<Vec<PathBuf>, ArrowExportError> {
+        self.flush()?;
+        Ok(self.written_files.clone())
+    }
+}
@@ -0,0 +1,120 @@
+//! Native NEAR balance changes from the account updates in the state changes.
+//!
+//! The state changes only have the new state of the account, so the change is computed against
+//! the previous update of the account. For the first update of an account the previous state is
+//! unknown, unless it's tracked across blocks with `BlockWithTxHashes::balance_changes_with`.
+use crate::block_with_tx_hash::BlockWithTxHashes;
+use crate::utils::cause_receipt_id;
+use near_primitives::hash::CryptoHash;
+use near_primitives::serialize::dec_format;
+use near_primitives::types::{AccountId, Balance, BlockHeight, ShardId, StorageUsage};
+use near_primitives::views::{AccountView, StateChangeCauseView, StateChangeValueView};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccountBalance {
+    #[serde(with = "dec_format")]
+    pub amount: Balance,
+    #[serde(with = "dec_format")]
+    pub locked: Balance,
+    pub storage_usage: StorageUsage,
+}
+
+impl From<&AccountView> for AccountBalance {
+    fn from(account: &AccountView) -> Self {
+        Self {
+            amount: account.amount,
+            locked: account.locked,
+            storage_usage: account.storage_usage,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BalanceChange {
+    pub block_height: BlockHeight,
+    /// The block timestamp in nanoseconds
+    pub block_timestamp: u64,
+    pub shard_id: ShardId,
+    pub account_id: AccountId,
+    /// The cause of the change, e.g. the transaction or the receipt, the validator reward or the
+    /// gas refund
+    pub cause: StateChangeCauseView,
+    /// The receipt from the cause, if any
+    pub receipt_id: Option<CryptoHash>,
+    /// The transaction from the cause, or the transaction of the receipt if it's executed in the block
+    pub tx_hash: Option<CryptoHash>,
+    /// The balance before the change. `None` if it's unknown.
+    pub before: Option<AccountBalance>,
+    /// The balance after the change. `None` if the account is deleted.
+    pub after: Option<AccountBalance>,
+}
+
+impl BalanceChange {
+    /// The change of the account balance. `None` if the previous balance is unknown.
+    pub fn amount_delta(&self) -> Option<i128> {
+        self.delta(|balance| balance.amount as i128)
+    }
+
+    pub fn locked_delta(&self) -> Option<i128> {
+        self.delta(|balance| balance.locked as i128)
+    }
+
+    pub fn storage_usage_delta(&self) -> Option<i128> {
+        self.delta(|balance| balance.storage_usage as i128)
+    }
+
+    fn delta(&self, value: impl Fn(&AccountBalance) -> i128) -> Option<i128> {
+        let before = value(self.before.as_ref()?);
+        let after = self.after.as_ref().map(&value).unwrap_or_default();
+        Some(after - before)
+    }
+}
+
+impl BlockWithTxHashes {
+    /// The balance changes of the accounts in the block, in the order of the state changes.
+    /// The account updates that don't change the balance, e.g. a contract deployment, are skipped.
+    pub fn balance_changes(&self) -> Vec<BalanceChange> {
+        self.balance_changes_with(&mut HashMap::new())
+    }
+
+    /// Same as `balance_changes`, but takes the known balances of the accounts before the block
+    /// and updates them with the changes of the block, so the balances can be tracked across
+    /// consecutive blocks.
+    pub fn balance_changes_with(
+        &self,
+        balances: &mut HashMap<AccountId, AccountBalance>,
+    ) -> Vec<BalanceChange> {
+        let mut changes = vec![];
+        for state_change in self.state_changes() {
+            let (account_id, after) = match &state_change.value {
+                StateChangeValueView::AccountUpdate {
+                    account_id,
+                    account,
+                } => (account_id, Some(AccountBalance::from(account))),
+                StateChangeValueView::AccountDeletion { account_id } => (account_id, None),
+                _ => continue,
+            };
+            let before = match after {
+                Some(after) => balances.insert(account_id.clone(), after),
+                None => balances.remove(account_id),
+            };
+            if before.is_some() && before == after {
+                continue;
+            }
+            changes.push(BalanceChange {
+                block_height: state_change.block_height,
+                block_timestamp: state_change.block_timestamp,
+                shard_id: state_change.shard_id,
+                account_id: account_id.clone(),
+                cause: state_change.cause.clone(),
+                receipt_id: cause_receipt_id(&state_change.cause),
+                tx_hash: state_change.tx_hash,
+                before,
+                after,
+            });
+        }
+        changes
+    }
+}
@@ -1,27 +1,68 @@
+use crate::tolerant::UnknownValue;
 use near_indexer_primitives::{
     IndexerChunkView, IndexerExecutionOutcomeWithReceipt, IndexerShard, StreamerMessage,
 };
 use near_primitives::hash::CryptoHash;
 use near_primitives::types::ShardId;
 use near_primitives::views::{
-    BlockView, ExecutionOutcomeWithIdView, ReceiptView, StateChangeWithCauseView,
+    BlockView, ExecutionOutcomeWithIdView, ReceiptEnumView, ReceiptView, StateChangeWithCauseView,
 };
+use std::collections::HashMap;
 
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(
+    feature = "compact",
+    derive(borsh::BorshSerialize, borsh::BorshDeserialize)
+)]
 pub struct BlockWithTxHashes {
+    #[cfg_attr(
+        feature = "compact",
+        borsh(
+            serialize_with = "crate::compact::serialize_view",
+            deserialize_with = "crate::compact::deserialize_view"
+        )
+    )]
     pub block: BlockView,
     pub shards: Vec<IndexerShardWithTxHashes>,
+    /// The unknown fields and elements found by the tolerant decoding, with the paths in the
+    /// block JSON. The unknown elements are removed from the block, see `crate::tolerant`.
+    /// Not part of the JSON or the compact encoding.
+    #[serde(skip)]
+    #[cfg_attr(feature = "compact", borsh(skip))]
+    pub unknown: Vec<UnknownValue>,
 }
 
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(
+    feature = "compact",
+    derive(borsh::BorshSerialize, borsh::BorshDeserialize)
+)]
 pub struct IndexerShardWithTxHashes {
     pub shard_id: ShardId,
+    #[cfg_attr(
+        feature = "compact",
+        borsh(
+            serialize_with = "crate::compact::serialize_chunk",
+            deserialize_with = "crate::compact::deserialize_chunk"
+        )
+    )]
     pub chunk: Option<IndexerChunkView>,
     pub receipt_execution_outcomes: Vec<IndexerExecutionOutcomeWithReceiptAndTxHash>,
+    #[cfg_attr(
+        feature = "compact",
+        borsh(
+            serialize_with = "crate::compact::serialize_view",
+            deserialize_with = "crate::compact::deserialize_view"
+        )
+    )]
     pub state_changes: Vec<StateChangeWithCauseView>,
 }
 
 #[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(
+    feature = "compact",
+    derive(borsh::BorshSerialize, borsh::BorshDeserialize)
+)]
 pub struct IndexerExecutionOutcomeWithReceiptAndTxHash {
     pub execution_outcome: ExecutionOutcomeWithIdView,
     pub receipt: ReceiptView,
@@ -63,6 +104,115 @@ impl From<StreamerMessage> for BlockWithTxHashes {
                     },
                 )
                 .collect(),
+            unknown: vec![],
+        }
+    }
+}
+
+impl From<BlockWithTxHashes> for StreamerMessage {
+    /// Converts the block to the near-lake format. The transaction hashes of the receipts are
+    /// dropped, since `StreamerMessage` doesn't have them.
+    fn from(block: BlockWithTxHashes) -> Self {
+        Self {
+            block: block.block,
+            shards: block
+                .shards
+                .into_iter()
+                .map(
+                    |IndexerShardWithTxHashes {
+                         shard_id,
+                         chunk,
+                         receipt_execution_outcomes,
+                         state_changes,
+                     }| IndexerShard {
+                        shard_id,
+                        chunk,
+                        receipt_execution_outcomes: receipt_execution_outcomes
+                            .into_iter()
+                            .map(
+                                |IndexerExecutionOutcomeWithReceiptAndTxHash {
+                                     execution_outcome,
+                                     receipt,
+                                     ..
+                                 }| {
+                                    IndexerExecutionOutcomeWithReceipt {
+                                        execution_outcome,
+                                        receipt,
+                                    }
+                                },
+                            )
+                            .collect(),
+                        state_changes,
+                    },
+                )
+                .collect(),
+        }
+    }
+}
+
+impl BlockWithTxHashes {
+    /// Same as `From<StreamerMessage>`, but fills `tx_hash` of the executed receipts by tracing
+    /// them back to the transactions through the execution outcomes and the data dependencies.
+    /// Only the transactions and receipts within the message are traced, so the receipts that
+    /// originate from transactions in earlier blocks keep `tx_hash: None`.
+    pub fn from_streamer_message_with_tx_hashes(streamer_message: StreamerMessage) -> Self {
+        let tx_hashes = trace_tx_hashes(&streamer_message);
+        let mut block = Self::from(streamer_message);
+        for shard in &mut block.shards {
+            for outcome in &mut shard.receipt_execution_outcomes {
+                outcome.tx_hash = tx_hashes.get(&outcome.receipt.receipt_id).copied();
+            }
+        }
+        block
+    }
+}
+
+/// Returns the transaction hash for every receipt that can be traced within the message.
+fn trace_tx_hashes(streamer_message: &StreamerMessage) -> HashMap<CryptoHash, CryptoHash> {
+    let mut tx_hashes = HashMap::new();
+    for shard in &streamer_message.shards {
+        for tx in shard.chunk.iter().flat_map(|chunk| &chunk.transactions) {
+            let tx_hash = tx.transaction.hash;
+            for receipt_id in &tx.outcome.execution_outcome.outcome.receipt_ids {
+                tx_hashes.insert(*receipt_id, tx_hash);
+            }
+        }
+    }
+    let outcomes: Vec<_> = streamer_message
+        .shards
+        .iter()
+        .flat_map(|shard| &shard.receipt_execution_outcomes)
+        .collect();
+    // The data receipts are matched to the receipts that produce the data
+    let mut data_tx_hashes = HashMap::new();
+    // The outcomes across shards are not ordered by causality, so repeating until nothing changes
+    loop {
+        let num_traced = tx_hashes.len() + data_tx_hashes.len();
+        for outcome in &outcomes {
+            let receipt = &outcome.receipt;
+            let tx_hash = match &receipt.receipt {
+                ReceiptEnumView::Data { data_id, .. } => data_tx_hashes.get(data_id),
+                ReceiptEnumView::Action { .. } => tx_hashes.get(&receipt.receipt_id),
+            };
+            let Some(tx_hash) = tx_hash.copied() else {
+                continue;
+            };
+            tx_hashes.insert(receipt.receipt_id, tx_hash);
+            for receipt_id in &outcome.execution_outcome.outcome.receipt_ids {
+                tx_hashes.insert(*receipt_id, tx_hash);
+            }
+            if let ReceiptEnumView::Action {
+                output_data_receivers,
+                ..
+            } = &receipt.receipt
+            {
+                for data_receiver in output_data_receivers {
+                    data_tx_hashes.insert(data_receiver.data_id, tx_hash);
+                }
+            }
+        }
+        if tx_hashes.len() + data_tx_hashes.len() == num_traced {
+            return tx_hashes;
         }
     }
 }
@@ -0,0 +1,146 @@
+//! The compact binary encoding of the blocks.
+//!
+//! The encoding mixes two formats:
+//! - Borsh for the structure of the blocks and shards and for the views with Borsh support: the
+//!   shard IDs, the transactions, the receipts, the execution outcomes and the chunk authors.
+//! - MessagePack with named fields for the views without Borsh support: the block header (the
+//!   whole `BlockView`), the chunk header and the state changes. Each such view is embedded in
+//!   the Borsh stream as a length-prefixed byte array.
+//!
+//! Every encoded value starts with a 4-byte header: the `FNB` magic and `COMPACT_VERSION`. The
+//! version covers both formats, so it's bumped on any change of the layout, including the fields
+//! of the MessagePack views, and the data of other versions is rejected with
+//! `CompactError::UnsupportedVersion`.
+use crate::block_with_tx_hash::{
+    BlockWithTxHashes, IndexerExecutionOutcomeWithReceiptAndTxHash, IndexerShardWithTxHashes,
+};
+use borsh::{BorshDeserialize, BorshSerialize};
+use near_indexer_primitives::{
+    IndexerChunkView, IndexerExecutionOutcomeWithOptionalReceipt, IndexerTransactionWithOutcome,
+};
+use std::fmt::Display;
+use std::io::{Read, Write};
+
+pub const COMPACT_MAGIC: &[u8; 3] = b"FNB";
+/// The version of the layout, see the module docs
+pub const COMPACT_VERSION: u8 = 1;
+const HEADER_SIZE: usize = COMPACT_MAGIC.len() + 1;
+
+#[derive(Debug)]
+pub enum CompactError {
+    /// The data doesn't start with the compact encoding header
+    InvalidHeader,
+    UnsupportedVersion(u8),
+    IoError(std::io::Error),
+}
+
+impl From<std::io::Error> for CompactError {
+    fn from(error: std::io::Error) -> Self {
+        CompactError::IoError(error)
+    }
+}
+
+impl Display for CompactError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CompactError::InvalidHeader => write!(f, "Invalid header"),
+            CompactError::UnsupportedVersion(version) => {
+                write!(f, "Unsupported version: {}", version)
+            }
+            CompactError::IoError(e) => write!(f, "IO error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for CompactError {}
+
+/// Encodes and decodes the value in the compact binary encoding.
+pub trait CompactEncoding: BorshSerialize + BorshDeserialize {
+    fn to_compact(&self) -> Result<Vec<u8>, CompactError> {
+        let mut data = Vec::with_capacity(1024);
+        data.extend_from_slice(COMPACT_MAGIC);
+        data.push(COMPACT_VERSION);
+        borsh::to_writer(&mut data, self)?;
+        Ok(data)
+    }
+
+    fn from_compact(data: &[u8]) -> Result<Self, CompactError> {
+        if data.len() < HEADER_SIZE || &data[..COMPACT_MAGIC.len()] != COMPACT_MAGIC {
+            return Err(CompactError::InvalidHeader);
+        }
+        match data[COMPACT_MAGIC.len()] {
+            COMPACT_VERSION => Ok(borsh::from_slice(&data[HEADER_SIZE..])?),
+            version => Err(CompactError::UnsupportedVersion(version)),
+        }
+    }
+}
+
+impl CompactEncoding for BlockWithTxHashes {}
+impl CompactEncoding for IndexerShardWithTxHashes {}
+impl CompactEncoding for IndexerExecutionOutcomeWithReceiptAndTxHash {}
+/// A range of blocks, e.g. the content of an archive
+impl CompactEncoding for Vec<BlockWithTxHashes> {}
+
+/// Writes a view without Borsh support as MessagePack, length-prefixed by Borsh.
+pub(crate) fn serialize_view<T: serde::Serialize, W: Write>(
+    view: &T,
+    writer: &mut W,
+) -> std::io::Result<()> {
+    let data = rmp_serde::to_vec_named(view).map_err(std::io::Error::other)?;
+    data.serialize(writer)
+}
+
+pub(crate) fn deserialize_view<T: serde::de::DeserializeOwned, R: Read>(
+    reader: &mut R,
+) -> std::io::Result<T> {
+    let data = Vec::<u8>::deserialize_reader(reader)?;
+    rmp_serde::from_slice(&data)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+}
+
+pub(crate) fn serialize_chunk<W: Write>(
+    chunk: &Option<IndexerChunkView>,
+    writer: &mut W,
+) -> std::io::Result<()> {
+    let Some(chunk) = chunk else {
+        return 0u8.serialize(writer);
+    };
+    1u8.serialize(writer)?;
+    chunk.author.serialize(writer)?;
+    serialize_view(&chunk.header, writer)?;
+    (chunk.transactions.len() as u32).serialize(writer)?;
+    for tx in &chunk.transactions {
+        tx.transaction.serialize(writer)?;
+        tx.outcome.execution_outcome.serialize(writer)?;
+        tx.outcome.receipt.serialize(writer)?;
+    }
+    chunk.receipts.serialize(writer)
+}
+
+pub(crate) fn deserialize_chunk<R: Read>(
+    reader: &mut R,
+) -> std::io::Result<Option<IndexerChunkView>> {
+    if !bool::deserialize_reader(reader)? {
+        return Ok(None);
+    }
+    let author = BorshDeserialize::deserialize_reader(reader)?;
+    let header = deserialize_view(reader)?;
+    let num_transactions = u32::deserialize_reader(reader)?;
+    let mut transactions = Vec::new();
+    for _ in 0..num_transactions {
+        transactions.push(IndexerTransactionWithOutcome {
+            transaction: BorshDeserialize::deserialize_reader(reader)?,
+            outcome: IndexerExecutionOutcomeWithOptionalReceipt {
+                execution_outcome: BorshDeserialize::deserialize_reader(reader)?,
+                receipt: BorshDeserialize::deserialize_reader(reader)?,
+            },
+        });
+    }
+    let receipts = BorshDeserialize::deserialize_reader(reader)?;
+    Ok(Some(IndexerChunkView {
+        author,
+        header,
+        transactions,
+        receipts,
+    }))
+}
@@ -0,0 +1,120 @@
+//! NEP-297 events from the execution outcome logs.
+//!
+//! The contracts emit the events as logs of the form `EVENT_JSON:{"standard":..,"version":..,"event":..,"data":..}`.
+//! See <https://github.com/near/NEPs/blob/master/neps/nep-0297.md>.
+use crate::block_with_tx_hash::{BlockWithTxHashes, IndexerExecutionOutcomeWithReceiptAndTxHash};
+use near_primitives::hash::CryptoHash;
+use near_primitives::types::{AccountId, BlockHeight};
+use near_primitives::views::ExecutionStatusView;
+use std::fmt::Display;
+
+pub const EVENT_JSON_PREFIX: &str = "EVENT_JSON:";
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Event {
+    pub standard: String,
+    pub version: String,
+    pub event: String,
+    /// The event data. Optional by the standard.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<serde_json::Value>,
+}
+
+impl Event {
+    /// Parses the event from the log. Returns `None` if the log is not an event log.
+    pub fn from_log(log: &str) -> Option<Result<Self, serde_json::Error>> {
+        let json = log.strip_prefix(EVENT_JSON_PREFIX)?;
+        Some(serde_json::from_str(json.trim()))
+    }
+}
+
+/// Where the event log was emitted.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct EventContext {
+    pub block_height: BlockHeight,
+    pub receipt_id: CryptoHash,
+    pub tx_hash: Option<CryptoHash>,
+    /// The account of the contract that emitted the event
+    pub account_id: AccountId,
+    /// The index of the log in the execution outcome
+    pub log_index: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ContractEvent {
+    #[serde(flatten)]
+    pub context: EventContext,
+    #[serde(flatten)]
+    pub event: Event,
+}
+
+/// A log with the event prefix that is not a valid event.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct MalformedEvent {
+    #[serde(flatten)]
+    pub context: EventContext,
+    pub log: String,
+    pub error: String,
+}
+
+impl Display for MalformedEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Malformed event log #{} of receipt {} at block {}: {}",
+            self.context.log_index, self.context.receipt_id, self.context.block_height, self.error
+        )
+    }
+}
+
+impl std::error::Error for MalformedEvent {}
+
+/// Returns the events of the executed receipt, in the order of the logs.
+/// The failed receipts have no effect, so their logs are ignored.
+pub fn outcome_events(
+    outcome: &IndexerExecutionOutcomeWithReceiptAndTxHash,
+    block_height: BlockHeight,
+) -> Vec<Result<ContractEvent, MalformedEvent>> {
+    let execution_outcome = &outcome.execution_outcome;
+    if matches!(
+        execution_outcome.outcome.status,
+        ExecutionStatusView::Failure(_)
+    ) {
+        return vec![];
+    }
+    execution_outcome
+        .outcome
+        .logs
+        .iter()
+        .enumerate()
+        .filter_map(|(log_index, log)| {
+            let result = Event::from_log(log)?;
+            let context = EventContext {
+                block_height,
+                receipt_id: outcome.receipt.receipt_id,
+                tx_hash: outcome.tx_hash,
+                account_id: execution_outcome.outcome.executor_id.clone(),
+                log_index,
+            };
+            Some(match result {
+                Ok(event) => Ok(ContractEvent { context, event }),
+                Err(err) => Err(MalformedEvent {
+                    context,
+                    log: log.clone(),
+                    error: err.to_string(),
+                }),
+            })
+        })
+        .collect()
+}
+
+impl BlockWithTxHashes {
+    /// The events of all receipts executed in the block.
+    pub fn events(&self) -> impl Iterator<Item = Result<ContractEvent, MalformedEvent>> + '_ {
+        let block_height = self.block.header.height;
+        self.shards
+            .iter()
+            .flat_map(|shard| &shard.receipt_execution_outcomes)
+            .flat_map(move |outcome| outcome_events(outcome, block_height))
+    }
+}
@@ -0,0 +1,241 @@
+//! Iterators over the contents of a block.
+//!
+//! Every item carries the context it was found in: the block height and timestamp, the shard and
+//! the hash of the transaction it originates from, if it's known.
+use crate::block_with_tx_hash::{BlockWithTxHashes, IndexerShardWithTxHashes};
+use crate::utils::{
+    action_kind, action_method_name, cause_receipt_id, receipt_account_ids,
+    state_change_account_id, transaction_account_ids, ActionKind,
+};
+use near_indexer_primitives::IndexerTransactionWithOutcome;
+use near_primitives::hash::CryptoHash;
+use near_primitives::types::{AccountId, BlockHeight, ShardId};
+use near_primitives::views::{
+    ActionView, ExecutionOutcomeWithIdView, ReceiptEnumView, ReceiptView, StateChangeCauseView,
+    StateChangeWithCauseView,
+};
+use std::collections::HashMap;
+use std::ops::Deref;
+
+/// An item of the block with its context. Dereferences to the item.
+#[derive(Debug, Clone, Copy)]
+pub struct BlockItem<T> {
+    pub block_height: BlockHeight,
+    /// The block timestamp in nanoseconds
+    pub block_timestamp: u64,
+    pub shard_id: ShardId,
+    /// The hash of the transaction the item originates from, if it's known
+    pub tx_hash: Option<CryptoHash>,
+    pub item: T,
+}
+
+impl<T> Deref for BlockItem<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.item
+    }
+}
+
+/// An action of an executed receipt.
+#[derive(Debug, Clone, Copy)]
+pub struct ReceiptAction<'a> {
+    pub receipt: &'a ReceiptView,
+    /// The signer of the transaction that produced the receipt
+    pub signer_id: &'a AccountId,
+    /// The index of the action in the receipt
+    pub index: usize,
+    pub action: &'a ActionView,
+}
+
+impl ReceiptAction<'_> {
+    pub fn kind(&self) -> ActionKind {
+        action_kind(self.action)
+    }
+
+    pub fn method_name(&self) -> Option<&str> {
+        action_method_name(self.action)
+    }
+}
+
+impl BlockItem<&IndexerTransactionWithOutcome> {
+    pub fn involves(&self, account_id: &AccountId) -> bool {
+        transaction_account_ids(&self.item.transaction).contains(&account_id)
+    }
+}
+
+impl BlockItem<&ReceiptView> {
+    pub fn involves(&self, account_id: &AccountId) -> bool {
+        receipt_account_ids(self.item).contains(&account_id)
+    }
+}
+
+impl<'a> BlockItem<&'a StateChangeWithCauseView> {
+    pub fn account_id(&self) -> &'a AccountId {
+        state_change_account_id(&self.item.value)
+    }
+}
+
+impl BlockItem<ReceiptAction<'_>> {
+    pub fn involves(&self, account_id: &AccountId) -> bool {
+        receipt_account_ids(self.item.receipt).contains(&account_id)
+    }
+}
+
+impl BlockWithTxHashes {
+    fn item<T>(
+        &self,
+        shard: &IndexerShardWithTxHashes,
+        tx_hash: Option<CryptoHash>,
+        item: T,
+    ) -> BlockItem<T> {
+        BlockItem {
+            block_height: self.block.header.height,
+            block_timestamp: self.block.header.timestamp_nanosec,
+            shard_id: shard.shard_id,
+            tx_hash,
+            item,
+        }
+    }
+
+    /// The transactions included in the chunks of the block.
+    pub fn transactions(&self) -> impl Iterator<Item = BlockItem<&IndexerTransactionWithOutcome>> {
+        self.shards.iter().flat_map(move |shard| {
+            shard
+                .chunk
+                .iter()
+                .flat_map(|chunk| &chunk.transactions)
+                .map(move |tx| self.item(shard, Some(tx.transaction.hash), tx))
+        })
+    }
+
+    /// The receipts executed in the block.
+    pub fn receipts(&self) -> impl Iterator<Item = BlockItem<&ReceiptView>> {
+        self.shards.iter().flat_map(move |shard| {
+            shard
+                .receipt_execution_outcomes
+                .iter()
+                .map(move |outcome| self.item(shard, outcome.tx_hash, &outcome.receipt))
+        })
+    }
+
+    /// The execution outcomes of the transactions and the receipts, in this order for each shard.
+    pub fn outcomes(&self) -> impl Iterator<Item = BlockItem<&ExecutionOutcomeWithIdView>> {
+        self.shards.iter().flat_map(move |shard| {
+            let transaction_outcomes = shard
+                .chunk
+                .iter()
+                .flat_map(|chunk| &chunk.transactions)
+                .map(move |tx| {
+                    self.item(
+                        shard,
+                        Some(tx.transaction.hash),
+                        &tx.outcome.execution_outcome,
+                    )
+                });
+            let receipt_outcomes = shard
+                .receipt_execution_outcomes
+                .iter()
+                .map(move |outcome| self.item(shard, outcome.tx_hash, &outcome.execution_outcome));
+            transaction_outcomes.chain(receipt_outcomes)
+        })
+    }
+
+    /// The state changes of the block. The transaction hash is resolved from the cause of the
+    /// change, the receipts are resolved through the receipts executed in the block.
+    pub fn state_changes(&self) -> impl Iterator<Item = BlockItem<&StateChangeWithCauseView>> {
+        let receipt_tx_hashes: HashMap<CryptoHash, CryptoHash> = self
+            .shards
+            .iter()
+            .flat_map(|shard| &shard.receipt_execution_outcomes)
+            .filter_map(|outcome| Some((outcome.receipt.receipt_id, outcome.tx_hash?)))
+            .collect();
+        self.shards
+            .iter()
+            .flat_map(|shard| {
+                shard
+                    .state_changes
+                    .iter()
+                    .map(move |state_change| (shard, state_change))
+            })
+            .map(move |(shard, state_change)| {
+                let tx_hash = match &state_change.cause {
+                    StateChangeCauseView::TransactionProcessing { tx_hash } => Some(*tx_hash),
+                    cause => cause_receipt_id(cause)
+                        .and_then(|receipt_id| receipt_tx_hashes.get(&receipt_id).copied()),
+                };
+                self.item(shard, tx_hash, state_change)
+            })
+    }
+
+    /// The actions of the receipts executed in the block. The actions of a transaction appear
+    /// once its receipt is executed, possibly in the next block.
+    pub fn actions(&self) -> impl Iterator<Item = BlockItem<ReceiptAction<'_>>> {
+        self.receipts().flat_map(|receipt| {
+            let (signer_id, actions) = match &receipt.item.receipt {
+                ReceiptEnumView::Action {
+                    signer_id, actions, ..
+                } => (signer_id, actions.as_slice()),
+                ReceiptEnumView::Data { .. } => (&receipt.item.predecessor_id, &[][..]),
+            };
+            actions
+                .iter()
+                .enumerate()
+                .map(move |(index, action)| BlockItem {
+                    block_height: receipt.block_height,
+                    block_timestamp: receipt.block_timestamp,
+                    shard_id: receipt.shard_id,
+                    tx_hash: receipt.tx_hash,
+                    item: ReceiptAction {
+                        receipt: receipt.item,
+                        signer_id,
+                        index,
+                        action,
+                    },
+                })
+        })
+    }
+
+    /// The transactions signed by or sent to the account.
+    pub fn transactions_by_account<'a>(
+        &'a self,
+        account_id: &'a AccountId,
+    ) -> impl Iterator<Item = BlockItem<&'a IndexerTransactionWithOutcome>> {
+        self.transactions()
+            .filter(move |tx| tx.involves(account_id))
+    }
+
+    /// The executed receipts sent by or to the account.
+    pub fn receipts_by_account<'a>(
+        &'a self,
+        account_id: &'a AccountId,
+    ) -> impl Iterator<Item = BlockItem<&'a ReceiptView>> {
+        self.receipts()
+            .filter(move |receipt| receipt.involves(account_id))
+    }
+
+    /// The state changes of the account.
+    pub fn state_changes_by_account<'a>(
+        &'a self,
+        account_id: &'a AccountId,
+    ) -> impl Iterator<Item = BlockItem<&'a StateChangeWithCauseView>> {
+        self.state_changes()
+            .filter(move |state_change| state_change.account_id() == account_id)
+    }
+
+    pub fn actions_by_kind(
+        &self,
+        kind: ActionKind,
+    ) -> impl Iterator<Item = BlockItem<ReceiptAction<'_>>> {
+        self.actions().filter(move |action| action.kind() == kind)
+    }
+
+    /// The executed function calls with the given method name.
+    pub fn function_calls<'a>(
+        &'a self,
+        method_name: &'a str,
+    ) -> impl Iterator<Item = BlockItem<ReceiptAction<'a>>> {
+        self.actions()
+            .filter(move |action| action.method_name() == Some(method_name))
+    }
+}
@@ -0,0 +1,105 @@
+//! JSON decoding of the blocks.
+//!
+//! With the `simd-json` feature, the JSON is decoded with the SIMD parser, otherwise with
+//! `serde_json`. Both produce the same values. Most of the decoding time is spent in the NEAR
+//! types rather than in the parser, so compare both on your CPU with the `json` benchmark before
+//! enabling the feature.
+//!
+//! The fetcher decodes the blocks of both the API and the archives with these functions, so the
+//! feature applies to both. The tolerant decoding (`crate::tolerant`) always uses `serde_json`.
+use crate::block_with_tx_hash::BlockWithTxHashes;
+use crate::select::{BlockSeed, BlockSelector};
+use serde::de::{DeserializeOwned, DeserializeSeed};
+
+#[derive(Debug)]
+pub enum JsonError {
+    Json(serde_json::Error),
+    #[cfg(feature = "simd-json")]
+    Simd(simd_json::Error),
+}
+
+impl std::fmt::Display for JsonError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JsonError::Json(e) => write!(f, "JSON error: {}", e),
+            #[cfg(feature = "simd-json")]
+            JsonError::Simd(e) => write!(f, "JSON error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for JsonError {}
+
+impl From<serde_json::Error> for JsonError {
+    fn from(error: serde_json::Error) -> Self {
+        JsonError::Json(error)
+    }
+}
+
+#[cfg(feature = "simd-json")]
+impl From<simd_json::Error> for JsonError {
+    fn from(error: simd_json::Error) -> Self {
+        JsonError::Simd(error)
+    }
+}
+
+/// Decodes the JSON. The SIMD parser needs a mutable buffer, so the data is copied with the
+/// `simd-json` feature. Use `from_mut_slice` to avoid the copy.
+pub fn from_slice<T: DeserializeOwned>(data: &[u8]) -> Result<T, JsonError> {
+    #[cfg(feature = "simd-json")]
+    {
+        from_mut_slice(&mut data.to_vec())
+    }
+    #[cfg(not(feature = "simd-json"))]
+    {
+        Ok(serde_json::from_slice(data)?)
+    }
+}
+
+/// Decodes the JSON in place. With the `simd-json` feature, the data is overwritten by the parser
+/// and shouldn't be used after.
+pub fn from_mut_slice<T: DeserializeOwned>(data: &mut [u8]) -> Result<T, JsonError> {
+    #[cfg(feature = "simd-json")]
+    {
+        Ok(simd_json::serde::from_slice(data)?)
+    }
+    #[cfg(not(feature = "simd-json"))]
+    {
+        Ok(serde_json::from_slice(data)?)
+    }
+}
+
+/// Decodes the JSON in place with the seed, see `from_mut_slice`.
+pub fn from_mut_slice_seed<'de, S: DeserializeSeed<'de>>(
+    data: &'de mut [u8],
+    seed: S,
+) -> Result<S::Value, JsonError> {
+    #[cfg(feature = "simd-json")]
+    {
+        let mut deserializer = simd_json::Deserializer::from_slice(data)?;
+        Ok(seed.deserialize(&mut deserializer)?)
+    }
+    #[cfg(not(feature = "simd-json"))]
+    {
+        let mut deserializer = serde_json::Deserializer::from_slice(data);
+        let value = seed.deserialize(&mut deserializer)?;
+        deserializer.end()?;
+        Ok(value)
+    }
+}
+
+impl BlockWithTxHashes {
+    /// Decodes the block with the JSON parser selected by the `simd-json` feature.
+    pub fn from_json_slice(data: &[u8]) -> Result<Self, JsonError> {
+        from_slice(data)
+    }
+
+    /// Decodes the block in place, keeping only the parts selected by the selector. See
+    /// `from_mut_slice` for the state of the data after.
+    pub fn from_json_mut_slice_selected<S: BlockSelector + ?Sized>(
+        data: &mut [u8],
+        selector: &S,
+    ) -> Result<Self, JsonError> {
+        from_mut_slice_seed(data, BlockSeed(selector))
+    }
+}
@@ -1,4 +1,18 @@
+pub mod args;
+#[cfg(feature = "arrow")]
+pub mod arrow;
+pub mod balances;
 pub mod block_with_tx_hash;
+#[cfg(feature = "compact")]
+pub mod compact;
+pub mod events;
+pub mod iter;
+pub mod json;
+pub mod select;
+pub mod status;
+pub mod tokens;
+pub mod tolerant;
+pub mod trace;
 pub mod types;
 pub mod utils;
 
@@ -0,0 +1,342 @@
+//! Selective decoding of the blocks.
+//!
+//! `BlockSelector` decides which parts of the block to keep while it's decoded. The skipped
+//! shards are not built at all, their JSON is only scanned. The rejected transactions, receipts,
+//! outcomes and state changes are dropped as soon as each of them is decoded, so the block never
+//! holds them in memory.
+//!
+//! A shard is only skipped without building it if its `shard_id` comes before the other fields,
+//! as in the neardata JSON. Otherwise, the fields before it are decoded and then dropped.
+use crate::block_with_tx_hash::{
+    BlockWithTxHashes, IndexerExecutionOutcomeWithReceiptAndTxHash, IndexerShardWithTxHashes,
+};
+use near_indexer_primitives::{IndexerChunkView, IndexerTransactionWithOutcome};
+use near_primitives::types::{AccountId, ShardId};
+use near_primitives::views::{BlockView, ChunkHeaderView, ReceiptView, StateChangeWithCauseView};
+use serde::de::{self, DeserializeSeed, Deserializer, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde::Deserialize;
+use std::fmt;
+use std::marker::PhantomData;
+
+/// Selects the parts of the block to keep while decoding it. Everything is kept by default.
+pub trait BlockSelector {
+    fn select_shard(&self, _shard_id: ShardId) -> bool {
+        true
+    }
+
+    fn select_transaction(&self, _transaction: &IndexerTransactionWithOutcome) -> bool {
+        true
+    }
+
+    /// The receipts of the chunk
+    fn select_receipt(&self, _receipt: &ReceiptView) -> bool {
+        true
+    }
+
+    fn select_outcome(&self, _outcome: &IndexerExecutionOutcomeWithReceiptAndTxHash) -> bool {
+        true
+    }
+
+    fn select_state_change(&self, _state_change: &StateChangeWithCauseView) -> bool {
+        true
+    }
+}
+
+/// Decodes the block with the selector, see `BlockWithTxHashes::deserialize_selected`.
+pub struct BlockSeed<'a, S: ?Sized>(pub &'a S);
+
+impl BlockWithTxHashes {
+    /// Decodes the block, keeping only the parts selected by the selector.
+    pub fn deserialize_selected<'de, D, S>(deserializer: D, selector: &S) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+        S: BlockSelector + ?Sized,
+    {
+        BlockSeed(selector).deserialize(deserializer)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(field_identifier, rename_all = "snake_case")]
+enum BlockField {
+    Block,
+    Shards,
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Deserialize)]
+#[serde(field_identifier, rename_all = "snake_case")]
+enum ShardField {
+    ShardId,
+    Chunk,
+    ReceiptExecutionOutcomes,
+    StateChanges,
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Deserialize)]
+#[serde(field_identifier, rename_all = "snake_case")]
+enum ChunkField {
+    Author,
+    Header,
+    Transactions,
+    Receipts,
+    #[serde(other)]
+    Other,
+}
+
+impl<'de, S: BlockSelector + ?Sized> DeserializeSeed<'de> for BlockSeed<'_, S> {
+    type Value = BlockWithTxHashes;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de, S: BlockSelector + ?Sized> Visitor<'de> for BlockSeed<'_, S> {
+    type Value = BlockWithTxHashes;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("struct BlockWithTxHashes")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut block: Option<BlockView> = None;
+        let mut shards = None;
+        while let Some(field) = map.next_key()? {
+            match field {
+                BlockField::Block => block = Some(map.next_value()?),
+                BlockField::Shards => shards = Some(map.next_value_seed(ShardsSeed(self.0))?),
+                BlockField::Other => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+        Ok(BlockWithTxHashes {
+            block: block.ok_or_else(|| de::Error::missing_field("block"))?,
+            shards: shards.ok_or_else(|| de::Error::missing_field("shards"))?,
+            unknown: vec![],
+        })
+    }
+}
+
+struct ShardsSeed<'a, S: ?Sized>(&'a S);
+
+impl<'de, S: BlockSelector + ?Sized> DeserializeSeed<'de> for ShardsSeed<'_, S> {
+    type Value = Vec<IndexerShardWithTxHashes>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de, S: BlockSelector + ?Sized> Visitor<'de> for ShardsSeed<'_, S> {
+    type Value = Vec<IndexerShardWithTxHashes>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a sequence of shards")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut shards = Vec::new();
+        while let Some(shard) = seq.next_element_seed(ShardSeed(self.0))? {
+            shards.extend(shard);
+        }
+        Ok(shards)
+    }
+}
+
+/// Decodes the shard, or `None` if it's not selected.
+struct ShardSeed<'a, S: ?Sized>(&'a S);
+
+impl<'de, S: BlockSelector + ?Sized> DeserializeSeed<'de> for ShardSeed<'_, S> {
+    type Value = Option<IndexerShardWithTxHashes>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de, S: BlockSelector + ?Sized> Visitor<'de> for ShardSeed<'_, S> {
+    type Value = Option<IndexerShardWithTxHashes>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("struct IndexerShardWithTxHashes")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let selector = self.0;
+        let mut shard_id = None;
+        let mut chunk = None;
+        let mut receipt_execution_outcomes = None;
+        let mut state_changes = None;
+        let mut is_skipped = false;
+        while let Some(field) = map.next_key()? {
+            if is_skipped {
+                map.next_value::<IgnoredAny>()?;
+                continue;
+            }
+            match field {
+                ShardField::ShardId => {
+                    let id: ShardId = map.next_value()?;
+                    is_skipped = !selector.select_shard(id);
+                    shard_id = Some(id);
+                }
+                ShardField::Chunk => {
+                    chunk = Some(map.next_value_seed(OptionalSeed(ChunkSeed(selector)))?)
+                }
+                ShardField::ReceiptExecutionOutcomes => {
+                    receipt_execution_outcomes =
+                        Some(map.next_value_seed(SelectedSeq::new(|outcome| {
+                            selector.select_outcome(outcome)
+                        }))?)
+                }
+                ShardField::StateChanges => {
+                    state_changes = Some(map.next_value_seed(SelectedSeq::new(|state_change| {
+                        selector.select_state_change(state_change)
+                    }))?)
+                }
+                ShardField::Other => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+        if is_skipped {
+            return Ok(None);
+        }
+        Ok(Some(IndexerShardWithTxHashes {
+            shard_id: shard_id.ok_or_else(|| de::Error::missing_field("shard_id"))?,
+            chunk: chunk.flatten(),
+            receipt_execution_outcomes: receipt_execution_outcomes
+                .ok_or_else(|| de::Error::missing_field("receipt_execution_outcomes"))?,
+            state_changes: state_changes
+                .ok_or_else(|| de::Error::missing_field("state_changes"))?,
+        }))
+    }
+}
+
+struct ChunkSeed<'a, S: ?Sized>(&'a S);
+
+impl<'de, S: BlockSelector + ?Sized> DeserializeSeed<'de> for ChunkSeed<'_, S> {
+    type Value = IndexerChunkView;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de, S: BlockSelector + ?Sized> Visitor<'de> for ChunkSeed<'_, S> {
+    type Value = IndexerChunkView;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("struct IndexerChunkView")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let selector = self.0;
+        let mut author: Option<AccountId> = None;
+        let mut header: Option<ChunkHeaderView> = None;
+        let mut transactions = None;
+        let mut receipts = None;
+        while let Some(field) = map.next_key()? {
+            match field {
+                ChunkField::Author => author = Some(map.next_value()?),
+                ChunkField::Header => header = Some(map.next_value()?),
+                ChunkField::Transactions => {
+                    transactions = Some(map.next_value_seed(SelectedSeq::new(|transaction| {
+                        selector.select_transaction(transaction)
+                    }))?)
+                }
+                ChunkField::Receipts => {
+                    receipts = Some(map.next_value_seed(SelectedSeq::new(|receipt| {
+                        selector.select_receipt(receipt)
+                    }))?)
+                }
+                ChunkField::Other => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+        Ok(IndexerChunkView {
+            author: author.ok_or_else(|| de::Error::missing_field("author"))?,
+            header: header.ok_or_else(|| de::Error::missing_field("header"))?,
+            transactions: transactions.ok_or_else(|| de::Error::missing_field("transactions"))?,
+            receipts: receipts.ok_or_else(|| de::Error::missing_field("receipts"))?,
+        })
+    }
+}
+
+/// Decodes a sequence, keeping only the elements accepted by the predicate.
+struct SelectedSeq<T, F> {
+    select: F,
+    element: PhantomData<T>,
+}
+
+impl<T, F: Fn(&T) -> bool> SelectedSeq<T, F> {
+    fn new(select: F) -> Self {
+        Self {
+            select,
+            element: PhantomData,
+        }
+    }
+}
+
+impl<'de, T: Deserialize<'de>, F: Fn(&T) -> bool> DeserializeSeed<'de> for SelectedSeq<T, F> {
+    type Value = Vec<T>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de, T: Deserialize<'de>, F: Fn(&T) -> bool> Visitor<'de> for SelectedSeq<T, F> {
+    type Value = Vec<T>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a sequence")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut elements = Vec::new();
+        while let Some(element) = seq.next_element()? {
+            if (self.select)(&element) {
+                elements.push(element);
+            }
+        }
+        Ok(elements)
+    }
+}
+
+/// Decodes `null` as `None` and anything else with the inner seed, e.g. a skipped block of the
+/// block API.
+pub struct OptionalSeed<S>(pub S);
+
+impl<'de, S: DeserializeSeed<'de>> DeserializeSeed<'de> for OptionalSeed<S> {
+    type Value = Option<S::Value>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_option(self)
+    }
+}
+
+impl<'de, S: DeserializeSeed<'de>> Visitor<'de> for OptionalSeed<S> {
+    type Value = Option<S::Value>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("an optional value")
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<Self::Value, E> {
+        Ok(None)
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Self::Value, E> {
+        Ok(None)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        self.0.deserialize(deserializer).map(Some)
+    }
+}
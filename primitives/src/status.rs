@@ -0,0 +1,130 @@
+//! Final status resolution of the transactions.
+//!
+//! A lighter alternative to `ReceiptTracker` when only the result of the transactions is needed.
+//! Only the receipts in the chain of `SuccessReceiptId` from the transaction outcome are tracked,
+//! so the refunds and the other receipts of the transaction are not waited for. The receipts of
+//! the chain can be executed a few blocks later on another shard.
+use crate::block_with_tx_hash::BlockWithTxHashes;
+use crate::trace::{follow_final_status, DEFAULT_MAX_TRACE_AGE};
+use near_primitives::hash::CryptoHash;
+use near_primitives::types::BlockHeight;
+use near_primitives::views::{ExecutionOutcomeView, ExecutionStatusView};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct TxFinalized {
+    pub tx_hash: CryptoHash,
+    /// `SuccessValue` or `Failure`
+    pub status: ExecutionStatusView,
+    /// The block where the final status became known
+    pub block_height: BlockHeight,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct PendingTx {
+    tx_hash: CryptoHash,
+    /// The block that includes the transaction
+    block_height: BlockHeight,
+}
+
+/// Resolves the final status of the transactions from consecutive blocks.
+pub struct TxStatusTracker {
+    max_age: BlockHeight,
+    /// The transactions by the receipt they wait for
+    pending: HashMap<CryptoHash, PendingTx>,
+    evicted: Vec<CryptoHash>,
+}
+
+impl Default for TxStatusTracker {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_TRACE_AGE)
+    }
+}
+
+impl TxStatusTracker {
+    /// The transactions without a final status within `max_age` blocks after their inclusion are
+    /// evicted.
+    pub fn new(max_age: BlockHeight) -> Self {
+        Self {
+            max_age,
+            pending: HashMap::new(),
+            evicted: vec![],
+        }
+    }
+
+    /// The number of the transactions without a final status.
+    pub fn num_pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Returns the hashes of the transactions evicted since the last call.
+    pub fn drain_evicted(&mut self) -> Vec<CryptoHash> {
+        std::mem::take(&mut self.evicted)
+    }
+
+    /// Processes the next block and returns the transactions finalized in it: first the pending
+    /// transactions, then the transactions of the block.
+    pub fn process_block(&mut self, block: &BlockWithTxHashes) -> Vec<TxFinalized> {
+        let block_height = block.block.header.height;
+        let outcomes: HashMap<CryptoHash, &ExecutionOutcomeView> = block
+            .shards
+            .iter()
+            .flat_map(|shard| &shard.receipt_execution_outcomes)
+            .map(|outcome| {
+                (
+                    outcome.receipt.receipt_id,
+                    &outcome.execution_outcome.outcome,
+                )
+            })
+            .collect();
+        let mut finalized = vec![];
+        let mut resolve =
+            |pending: &mut HashMap<_, _>, tx: PendingTx, status| match follow_final_status(
+                status,
+                |receipt_id| outcomes.get(receipt_id).copied(),
+            ) {
+                Ok(status) => finalized.push(TxFinalized {
+                    tx_hash: tx.tx_hash,
+                    status,
+                    block_height,
+                }),
+                Err(receipt_id) => {
+                    pending.insert(receipt_id, tx);
+                }
+            };
+        for shard in &block.shards {
+            for outcome in &shard.receipt_execution_outcomes {
+                if let Some(tx) = self.pending.remove(&outcome.receipt.receipt_id) {
+                    resolve(
+                        &mut self.pending,
+                        tx,
+                        &outcome.execution_outcome.outcome.status,
+                    );
+                }
+            }
+        }
+        for shard in &block.shards {
+            for tx in shard.chunk.iter().flat_map(|chunk| &chunk.transactions) {
+                let pending_tx = PendingTx {
+                    tx_hash: tx.transaction.hash,
+                    block_height,
+                };
+                resolve(
+                    &mut self.pending,
+                    pending_tx,
+                    &tx.outcome.execution_outcome.outcome.status,
+                );
+            }
+        }
+
+        let max_age = self.max_age;
+        self.pending.retain(|_, tx| {
+            let expired = tx.block_height + max_age < block_height;
+            if expired {
+                self.evicted.push(tx.tx_hash);
+            }
+            !expired
+        });
+        finalized
+    }
+}
@@ -0,0 +1,430 @@
+//! Fungible token, NFT and multi-token transfers, mints and burns.
+//!
+//! The records are decoded from the NEP-141, NEP-171 and NEP-245 events. The legacy contracts that
+//! don't emit events are covered by the `ft_transfer*` and `nft_transfer*` function calls.
+//! Every NFT or multi-token in an event is a separate record.
+use crate::block_with_tx_hash::{BlockWithTxHashes, IndexerExecutionOutcomeWithReceiptAndTxHash};
+use crate::events::{
+    outcome_events, ContractEvent, EventContext, MalformedEvent, EVENT_JSON_PREFIX,
+};
+use near_primitives::hash::CryptoHash;
+use near_primitives::serialize::dec_format;
+use near_primitives::types::{AccountId, Balance, BlockHeight};
+use near_primitives::views::{ActionView, ExecutionStatusView, ReceiptEnumView};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenStandard {
+    /// Fungible tokens
+    Nep141,
+    /// Non-fungible tokens
+    Nep171,
+    /// Multi tokens
+    Nep245,
+}
+
+impl TokenStandard {
+    pub const ALL: [TokenStandard; 3] = [
+        TokenStandard::Nep141,
+        TokenStandard::Nep171,
+        TokenStandard::Nep245,
+    ];
+
+    /// The standard name in the events, e.g. `nep141`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TokenStandard::Nep141 => "nep141",
+            TokenStandard::Nep171 => "nep171",
+            TokenStandard::Nep245 => "nep245",
+        }
+    }
+
+    pub fn from_event_standard(standard: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|token_standard| token_standard.as_str() == standard)
+    }
+}
+
+/// What the record was decoded from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "type")]
+pub enum TokenRecordSource {
+    Event {
+        log_index: usize,
+    },
+    /// The function call of a legacy contract without events
+    FunctionCall {
+        action_index: usize,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenContext {
+    pub block_height: BlockHeight,
+    pub receipt_id: CryptoHash,
+    pub tx_hash: Option<CryptoHash>,
+    /// The token contract
+    pub contract_id: AccountId,
+    pub standard: TokenStandard,
+    pub source: TokenRecordSource,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenTransfer {
+    #[serde(flatten)]
+    pub context: TokenContext,
+    /// `None` for fungible tokens
+    pub token_id: Option<String>,
+    /// Always 1 for NFTs
+    #[serde(with = "dec_format")]
+    pub amount: Balance,
+    /// `None` for the approved `nft_transfer` of a legacy contract, where the owner is not known
+    pub old_owner_id: Option<AccountId>,
+    pub new_owner_id: AccountId,
+    pub authorized_id: Option<AccountId>,
+    pub memo: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Mint {
+    #[serde(flatten)]
+    pub context: TokenContext,
+    pub token_id: Option<String>,
+    #[serde(with = "dec_format")]
+    pub amount: Balance,
+    pub owner_id: AccountId,
+    pub memo: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Burn {
+    #[serde(flatten)]
+    pub context: TokenContext,
+    pub token_id: Option<String>,
+    #[serde(with = "dec_format")]
+    pub amount: Balance,
+    pub owner_id: AccountId,
+    pub authorized_id: Option<AccountId>,
+    pub memo: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "kind")]
+pub enum TokenEvent {
+    Transfer(TokenTransfer),
+    Mint(Mint),
+    Burn(Burn),
+}
+
+impl TokenEvent {
+    pub fn context(&self) -> &TokenContext {
+        match self {
+            TokenEvent::Transfer(transfer) => &transfer.context,
+            TokenEvent::Mint(mint) => &mint.context,
+            TokenEvent::Burn(burn) => &burn.context,
+        }
+    }
+}
+
+/// The data of the mint, transfer and burn events of all three standards. The FT events have
+/// `amount`, the NFT events have `token_ids` and the MT events have both `token_ids` and `amounts`.
+#[derive(Deserialize)]
+struct MintData {
+    owner_id: AccountId,
+    #[serde(flatten)]
+    tokens: TokensData,
+    memo: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct TransferData {
+    authorized_id: Option<AccountId>,
+    old_owner_id: AccountId,
+    new_owner_id: AccountId,
+    #[serde(flatten)]
+    tokens: TokensData,
+    memo: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct BurnData {
+    authorized_id: Option<AccountId>,
+    owner_id: AccountId,
+    #[serde(flatten)]
+    tokens: TokensData,
+    memo: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct TokensData {
+    #[serde(default, with = "dec_format")]
+    amount: Option<Balance>,
+    token_ids: Option<Vec<String>>,
+    #[serde(default)]
+    amounts: Option<Vec<DecBalance>>,
+}
+
+#[derive(Deserialize)]
+struct DecBalance(#[serde(with = "dec_format")] Balance);
+
+impl TokensData {
+    /// Returns the token ID and the amount of every token.
+    fn into_tokens(
+        self,
+        standard: TokenStandard,
+    ) -> Result<Vec<(Option<String>, Balance)>, String> {
+        match (standard, self) {
+            (
+                TokenStandard::Nep141,
+                TokensData {
+                    amount: Some(amount),
+                    ..
+                },
+            ) => Ok(vec![(None, amount)]),
+            (
+                TokenStandard::Nep171,
+                TokensData {
+                    token_ids: Some(token_ids),
+                    ..
+                },
+            ) => Ok(token_ids
+                .into_iter()
+                .map(|token_id| (Some(token_id), 1))
+                .collect()),
+            (
+                TokenStandard::Nep245,
+                TokensData {
+                    token_ids: Some(token_ids),
+                    amounts: Some(amounts),
+                    ..
+                },
+            ) if token_ids.len() == amounts.len() => Ok(token_ids
+                .into_iter()
+                .map(Some)
+                .zip(amounts.into_iter().map(|amount| amount.0))
+                .collect()),
+            (standard, _) => Err(format!("Invalid token data for {:?}", standard)),
+        }
+    }
+}
+
+/// The arguments of `ft_transfer`, `ft_transfer_call`, `nft_transfer` and `nft_transfer_call`.
+#[derive(Deserialize)]
+struct TransferArgs {
+    receiver_id: AccountId,
+    #[serde(default, with = "dec_format")]
+    amount: Option<Balance>,
+    token_id: Option<String>,
+    /// Set when an approved account transfers the NFT on behalf of the owner
+    approval_id: Option<u64>,
+    memo: Option<String>,
+}
+
+fn decode_event(event: &ContractEvent) -> Result<Vec<TokenEvent>, String> {
+    let Some(standard) = TokenStandard::from_event_standard(&event.event.standard) else {
+        return Ok(vec![]);
+    };
+    let context = TokenContext {
+        block_height: event.context.block_height,
+        receipt_id: event.context.receipt_id,
+        tx_hash: event.context.tx_hash,
+        contract_id: event.context.account_id.clone(),
+        standard,
+        source: TokenRecordSource::Event {
+            log_index: event.context.log_index,
+        },
+    };
+    let data = || event.event.data.clone().ok_or("Missing event data");
+    let mut records = vec![];
+    match event.event.event.as_str() {
+        "ft_mint" | "nft_mint" | "mt_mint" => {
+            for mint in decode_data::<MintData>(data()?)? {
+                for (token_id, amount) in mint.tokens.into_tokens(standard)? {
+                    records.push(TokenEvent::Mint(Mint {
+                        context: context.clone(),
+                        token_id,
+                        amount,
+                        owner_id: mint.owner_id.clone(),
+                        memo: mint.memo.clone(),
+                    }));
+                }
+            }
+        }
+        "ft_transfer" | "nft_transfer" | "mt_transfer" => {
+            for transfer in decode_data::<TransferData>(data()?)? {
+                for (token_id, amount) in transfer.tokens.into_tokens(standard)? {
+                    records.push(TokenEvent::Transfer(TokenTransfer {
+                        context: context.clone(),
+                        token_id,
+                        amount,
+                        old_owner_id: Some(transfer.old_owner_id.clone()),
+                        new_owner_id: transfer.new_owner_id.clone(),
+                        authorized_id: transfer.authorized_id.clone(),
+                        memo: transfer.memo.clone(),
+                    }));
+                }
+            }
+        }
+        "ft_burn" | "nft_burn" | "mt_burn" => {
+            for burn in decode_data::<BurnData>(data()?)? {
+                for (token_id, amount) in burn.tokens.into_tokens(standard)? {
+                    records.push(TokenEvent::Burn(Burn {
+                        context: context.clone(),
+                        token_id,
+                        amount,
+                        owner_id: burn.owner_id.clone(),
+                        authorized_id: burn.authorized_id.clone(),
+                        memo: burn.memo.clone(),
+                    }));
+                }
+            }
+        }
+        // Other events of the standards, e.g. `nft_metadata_update`
+        _ => {}
+    }
+    Ok(records)
+}
+
+fn decode_data<T: DeserializeOwned>(data: serde_json::Value) -> Result<Vec<T>, String> {
+    serde_json::from_value(data).map_err(|err| err.to_string())
+}
+
+/// Decodes the transfers of a legacy contract from the function calls of the receipt.
+/// The amount of `ft_transfer_call` is the full attached amount, the refund by `ft_resolve_transfer`
+/// is not accounted for.
+/// The predecessor is the owner, except for the approved NFT transfers, where it's the approved
+/// account and the owner is not known.
+fn decode_function_calls(
+    outcome: &IndexerExecutionOutcomeWithReceiptAndTxHash,
+    block_height: BlockHeight,
+    has_events: impl Fn(TokenStandard) -> bool,
+) -> Vec<TokenEvent> {
+    let receipt = &outcome.receipt;
+    let ReceiptEnumView::Action { actions, .. } = &receipt.receipt else {
+        return vec![];
+    };
+    actions
+        .iter()
+        .enumerate()
+        .filter_map(|(action_index, action)| {
+            let ActionView::FunctionCall {
+                method_name, args, ..
+            } = action
+            else {
+                return None;
+            };
+            let standard = match method_name.as_str() {
+                "ft_transfer" | "ft_transfer_call" => TokenStandard::Nep141,
+                "nft_transfer" | "nft_transfer_call" => TokenStandard::Nep171,
+                _ => return None,
+            };
+            if has_events(standard) {
+                return None;
+            }
+            // The arguments that don't match the standard are not token transfers
+            let args: TransferArgs = serde_json::from_slice(args).ok()?;
+            let (token_id, amount) = match standard {
+                TokenStandard::Nep141 => (None, args.amount?),
+                _ => (Some(args.token_id?), 1),
+            };
+            let (old_owner_id, authorized_id) = match args.approval_id {
+                Some(_) => (None, Some(receipt.predecessor_id.clone())),
+                None => (Some(receipt.predecessor_id.clone()), None),
+            };
+            Some(TokenEvent::Transfer(TokenTransfer {
+                context: TokenContext {
+                    block_height,
+                    receipt_id: receipt.receipt_id,
+                    tx_hash: outcome.tx_hash,
+                    contract_id: receipt.receiver_id.clone(),
+                    standard,
+                    source: TokenRecordSource::FunctionCall { action_index },
+                },
+                token_id,
+                amount,
+                old_owner_id,
+                new_owner_id: args.receiver_id,
+                authorized_id,
+                memo: args.memo,
+            }))
+        })
+        .collect()
+}
+
+/// Returns the token records of the executed receipt. The events with invalid data are returned
+/// as errors. The function calls are only decoded if the receipt didn't emit events of the standard.
+pub fn outcome_token_events(
+    outcome: &IndexerExecutionOutcomeWithReceiptAndTxHash,
+    block_height: BlockHeight,
+) -> Vec<Result<TokenEvent, MalformedEvent>> {
+    if matches!(
+        outcome.execution_outcome.outcome.status,
+        ExecutionStatusView::Failure(_)
+    ) {
+        return vec![];
+    }
+    let events = outcome_events(outcome, block_height);
+    let mut records = vec![];
+    for event in &events {
+        match event {
+            Ok(event) => match decode_event(event) {
+                Ok(events) => records.extend(events.into_iter().map(Ok)),
+                Err(error) => records.push(Err(malformed_event(outcome, &event.context, error))),
+            },
+            Err(malformed_event) => {
+                if malformed_event_standard(&malformed_event.log).is_some() {
+                    records.push(Err(malformed_event.clone()));
+                }
+            }
+        }
+    }
+    // The malformed events count too, the contract is not a legacy one
+    let has_events = |standard: TokenStandard| {
+        events.iter().any(|event| match event {
+            Ok(event) => event.event.standard == standard.as_str(),
+            Err(malformed_event) => {
+                malformed_event_standard(&malformed_event.log) == Some(standard)
+            }
+        })
+    };
+    records.extend(
+        decode_function_calls(outcome, block_height, has_events)
+            .into_iter()
+            .map(Ok),
+    );
+    records
+}
+
+/// The token standard in the `standard` field of the malformed event log. The malformed logs are
+/// only reported if they are token events, i.e. valid JSON objects with the token standard.
+fn malformed_event_standard(log: &str) -> Option<TokenStandard> {
+    let json = log.strip_prefix(EVENT_JSON_PREFIX)?;
+    let event: serde_json::Value = serde_json::from_str(json.trim()).ok()?;
+    TokenStandard::from_event_standard(event.get("standard")?.as_str()?)
+}
+
+fn malformed_event(
+    outcome: &IndexerExecutionOutcomeWithReceiptAndTxHash,
+    context: &EventContext,
+    error: String,
+) -> MalformedEvent {
+    MalformedEvent {
+        context: context.clone(),
+        log: outcome.execution_outcome.outcome.logs[context.log_index].clone(),
+        error,
+    }
+}
+
+impl BlockWithTxHashes {
+    /// The token records of all receipts executed in the block.
+    pub fn token_events(&self) -> impl Iterator<Item = Result<TokenEvent, MalformedEvent>> + '_ {
+        let block_height = self.block.header.height;
+        self.shards
+            .iter()
+            .flat_map(|shard| &shard.receipt_execution_outcomes)
+            .flat_map(move |outcome| outcome_token_events(outcome, block_height))
+    }
+}
@@ -0,0 +1,287 @@
+//! Forward-compatible decoding of the JSON blocks.
+//!
+//! New protocol versions can add fields and enum variants to the NEAR views. The new fields are
+//! ignored by the strict decoding, but the new variants fail the whole block. The tolerant decoding
+//! removes the array elements that fail to decode, e.g. a state change or an action of an unknown
+//! kind, and keeps them with the fields that the types don't have as `UnknownValue`s.
+//! The paths of the unknown values use the indices of the original arrays, so the positions of the
+//! known elements, e.g. the index of an action in its receipt, can be restored with
+//! `Tolerant::original_index`. The decoded blocks carry their unknown values in
+//! `BlockWithTxHashes::unknown`, see `Tolerant::into_block`.
+//!
+//! The unknown fields are found by comparing the input with the decoded value serialized again, so
+//! a field that is serialized differently than it was read, e.g. with a serde alias or a rename
+//! that is only applied when deserializing, is reported as unknown even though it was decoded.
+//!
+//! The tolerant decoding goes through `serde_json::Value` and serializes the decoded value again to
+//! find the unknown fields, so it's 4 to 5 times slower than the strict decoding on the heavy
+//! blocks, see the `json` benchmark.
+use crate::block_with_tx_hash::BlockWithTxHashes;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use serde_path_to_error::Segment;
+use std::collections::HashMap;
+
+/// The maximum number of elements removed from one value before the decoding fails.
+pub const MAX_UNKNOWN_ELEMENTS: usize = 1000;
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case", tag = "kind")]
+pub enum UnknownValue {
+    /// A field that the type doesn't have. It's not in the decoded value.
+    Field { path: String, value: Value },
+    /// An array element that failed to decode, e.g. an unknown enum variant. It's removed from the
+    /// decoded value, the path has its index in the original array.
+    Element {
+        path: String,
+        value: Value,
+        error: String,
+    },
+}
+
+impl UnknownValue {
+    /// The location of the value, e.g. `shards[0].state_changes[3]`
+    pub fn path(&self) -> &str {
+        match self {
+            UnknownValue::Field { path, .. } | UnknownValue::Element { path, .. } => path,
+        }
+    }
+
+    /// The value with the path relative to the parent value at `prefix`, e.g. the unknown value
+    /// of a shard decoded on its own with the `shards[0]` prefix.
+    pub fn with_path_prefix(mut self, prefix: &str) -> Self {
+        let (UnknownValue::Field { path, .. } | UnknownValue::Element { path, .. }) = &mut self;
+        *path = if path.starts_with('[') {
+            format!("{}{}", prefix, path)
+        } else {
+            join_path(prefix, path)
+        };
+        self
+    }
+}
+
+/// The decoded value with the unknown values found while decoding it.
+#[derive(Debug, Clone)]
+pub struct Tolerant<T> {
+    pub value: T,
+    pub unknown: Vec<UnknownValue>,
+}
+
+impl<T> Tolerant<T> {
+    /// The index in the original array of the element at `index` of the decoded array at
+    /// `array_path`, e.g. `shards[1].receipt_execution_outcomes[0].receipt.receipt.Action.actions`.
+    /// It differs from `index` when the unknown elements before it were removed.
+    pub fn original_index(&self, array_path: &str, index: usize) -> usize {
+        original_index(&self.unknown, array_path, index)
+    }
+}
+
+impl Tolerant<BlockWithTxHashes> {
+    /// The block with the unknown values in `BlockWithTxHashes::unknown`.
+    pub fn into_block(self) -> BlockWithTxHashes {
+        let Tolerant {
+            value: mut block,
+            unknown,
+        } = self;
+        block.unknown = unknown;
+        block
+    }
+}
+
+/// The index in the original array of the element at `index` of the decoded array at
+/// `array_path`, given the unknown values found while decoding, see `Tolerant::original_index`.
+pub fn original_index(unknown: &[UnknownValue], array_path: &str, index: usize) -> usize {
+    let mut removed: Vec<_> = unknown
+        .iter()
+        .filter_map(|unknown| match unknown {
+            UnknownValue::Element { path, .. } => path
+                .strip_prefix(array_path)?
+                .strip_prefix('[')?
+                .strip_suffix(']')?
+                .parse::<usize>()
+                .ok(),
+            UnknownValue::Field { .. } => None,
+        })
+        .collect();
+    removed.sort_unstable();
+    to_original_index(&removed, index)
+}
+
+/// Maps the index in the array with the removed elements to the index in the original array.
+/// `removed` has the sorted original indices of the removed elements.
+fn to_original_index(removed: &[usize], index: usize) -> usize {
+    let mut original_index = index;
+    for removed_index in removed {
+        if *removed_index <= original_index {
+            original_index += 1;
+        }
+    }
+    original_index
+}
+
+pub fn from_slice_tolerant<T: DeserializeOwned + Serialize>(
+    data: &[u8],
+) -> Result<Tolerant<T>, serde_json::Error> {
+    from_value_tolerant(serde_json::from_slice(data)?)
+}
+
+pub fn from_value_tolerant<T: DeserializeOwned + Serialize>(
+    mut value: Value,
+) -> Result<Tolerant<T>, serde_json::Error> {
+    let mut unknown = vec![];
+    // The sorted original indices of the removed elements by the original path of the array
+    let mut removed = HashMap::new();
+    let decoded: T = loop {
+        match serde_path_to_error::deserialize(&value) {
+            Ok(decoded) => break decoded,
+            Err(err) => {
+                let path: Vec<_> = err.path().iter().cloned().collect();
+                let error = err.into_inner();
+                if unknown.len() >= MAX_UNKNOWN_ELEMENTS {
+                    return Err(error);
+                }
+                match remove_element(&mut value, &path, &mut removed) {
+                    Some((path, element)) => unknown.push(UnknownValue::Element {
+                        path,
+                        value: element,
+                        error: error.to_string(),
+                    }),
+                    None => return Err(error),
+                }
+            }
+        }
+    };
+    let decoded_value = serde_json::to_value(&decoded)?;
+    collect_unknown_fields(
+        &value,
+        &decoded_value,
+        String::new(),
+        &removed,
+        &mut unknown,
+    );
+    Ok(Tolerant {
+        value: decoded,
+        unknown,
+    })
+}
+
+/// Removes the deepest array element on the error path and returns it with its original path.
+fn remove_element(
+    value: &mut Value,
+    path: &[Segment],
+    removed: &mut HashMap<String, Vec<usize>>,
+) -> Option<(String, Value)> {
+    let mut current = &*value;
+    let mut current_path = String::new();
+    // The number of segments to the array, the element index and the original array path
+    let mut element = None;
+    for (depth, segment) in path.iter().enumerate() {
+        current = match segment {
+            Segment::Seq { index } => {
+                let next = current.get(index)?;
+                let original_index = removed
+                    .get(&current_path)
+                    .map_or(*index, |removed| to_original_index(removed, *index));
+                element = Some((depth, *index, current_path.clone(), original_index));
+                current_path = format!("{}[{}]", current_path, original_index);
+                next
+            }
+            Segment::Map { key } => {
+                let next = current.get(key)?;
+                current_path = join_path(&current_path, key);
+                next
+            }
+            // Externally tagged enums are objects with the variant as the key
+            Segment::Enum { variant } => match current.get(variant) {
+                Some(next) => {
+                    current_path = join_path(&current_path, variant);
+                    next
+                }
+                None => current,
+            },
+            Segment::Unknown => break,
+        };
+    }
+    let (depth, index, array_path, original_index) = element?;
+    let mut array = &mut *value;
+    for segment in &path[..depth] {
+        array = match segment {
+            Segment::Seq { index } => array.get_mut(index)?,
+            Segment::Map { key } => array.get_mut(key)?,
+            Segment::Enum { variant } => {
+                if array.get(variant).is_some() {
+                    array.get_mut(variant)?
+                } else {
+                    array
+                }
+            }
+            Segment::Unknown => return None,
+        };
+    }
+    let element = array.as_array_mut()?.remove(index);
+    let element_path = format!("{}[{}]", array_path, original_index);
+    let removed = removed.entry(array_path).or_default();
+    let position = removed.partition_point(|removed_index| *removed_index < original_index);
+    removed.insert(position, original_index);
+    Some((element_path, element))
+}
+
+fn join_path(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}.{}", path, key)
+    }
+}
+
+/// Collects the non-null fields of the original value that are missing in the decoded value.
+/// The original value has the unknown elements removed already. The fields read under an alias or
+/// a deserialize-only name are missing in the decoded value too, so they are reported as well.
+fn collect_unknown_fields(
+    original: &Value,
+    decoded: &Value,
+    path: String,
+    removed: &HashMap<String, Vec<usize>>,
+    unknown: &mut Vec<UnknownValue>,
+) {
+    match (original, decoded) {
+        (Value::Object(original), Value::Object(decoded)) => {
+            for (key, value) in original {
+                let field_path = join_path(&path, key);
+                match decoded.get(key) {
+                    Some(decoded) => {
+                        collect_unknown_fields(value, decoded, field_path, removed, unknown)
+                    }
+                    None if !value.is_null() => unknown.push(UnknownValue::Field {
+                        path: field_path,
+                        value: value.clone(),
+                    }),
+                    None => {}
+                }
+            }
+        }
+        (Value::Array(original), Value::Array(decoded)) if original.len() == decoded.len() => {
+            let array_removed = removed.get(&path).map_or(&[][..], Vec::as_slice);
+            for (index, (original, decoded)) in original.iter().zip(decoded).enumerate() {
+                let original_index = to_original_index(array_removed, index);
+                let element_path = format!("{}[{}]", path, original_index);
+                collect_unknown_fields(original, decoded, element_path, removed, unknown);
+            }
+        }
+        _ => {}
+    }
+}
+
+impl BlockWithTxHashes {
+    /// Decodes the block, keeping the unknown fields and elements instead of failing.
+    pub fn from_slice_tolerant(data: &[u8]) -> Result<Tolerant<Self>, serde_json::Error> {
+        from_slice_tolerant(data)
+    }
+
+    /// The index in the original array of the element at `index` of the decoded array at
+    /// `array_path`, see `Tolerant::original_index`. Always `index` without the tolerant decoding.
+    pub fn original_index(&self, array_path: &str, index: usize) -> usize {
+        original_index(&self.unknown, array_path, index)
+    }
+}
@@ -0,0 +1,257 @@
+//! Reconstruction of the transaction execution trees across blocks.
+//!
+//! The receipts of a transaction are executed over several blocks and linked through the
+//! `receipt_ids` of the execution outcomes. `ReceiptTracker` follows them from a stream of blocks
+//! and emits a `TransactionTrace` once the last receipt of the transaction is executed, including
+//! the gas refunds.
+use crate::block_with_tx_hash::BlockWithTxHashes;
+use near_primitives::hash::CryptoHash;
+use near_primitives::types::{Balance, BlockHeight, Gas, ShardId};
+use near_primitives::views::{
+    ExecutionOutcomeView, ExecutionOutcomeWithIdView, ExecutionStatusView, ReceiptView,
+    SignedTransactionView,
+};
+use std::collections::HashMap;
+
+/// The default number of blocks after which an incomplete transaction is evicted.
+pub const DEFAULT_MAX_TRACE_AGE: BlockHeight = 1000;
+
+/// An executed receipt of the transaction.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct TraceReceipt {
+    pub block_height: BlockHeight,
+    pub shard_id: ShardId,
+    /// The ID of the receipt or the hash of the transaction that produced the receipt
+    pub parent_id: CryptoHash,
+    pub receipt: ReceiptView,
+    pub execution_outcome: ExecutionOutcomeWithIdView,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct TransactionTrace {
+    pub tx_hash: CryptoHash,
+    /// The block that includes the transaction
+    pub block_height: BlockHeight,
+    /// The block that executed the last receipt
+    pub last_block_height: BlockHeight,
+    pub transaction: SignedTransactionView,
+    pub execution_outcome: ExecutionOutcomeWithIdView,
+    /// The executed receipts in the order of execution
+    pub receipts: Vec<TraceReceipt>,
+    /// The final status of the transaction, `Unknown` if the trace is incomplete
+    pub status: ExecutionStatusView,
+    /// The gas burnt by the transaction and all receipts
+    pub gas_burnt: Gas,
+    pub tokens_burnt: Balance,
+    /// The logs of all receipts in the order of execution
+    pub logs: Vec<String>,
+}
+
+impl TransactionTrace {
+    /// The receipts produced by the given receipt, or by the transaction for the transaction hash.
+    pub fn children(&self, parent_id: CryptoHash) -> impl Iterator<Item = &TraceReceipt> {
+        self.receipts
+            .iter()
+            .filter(move |receipt| receipt.parent_id == parent_id)
+    }
+}
+
+/// Resolves the final status of the transaction the same way as the RPC: the status is the status
+/// of the last outcome in the chain of `SuccessReceiptId`, starting from the transaction outcome.
+/// Returns `None` if an outcome in the chain is not known yet.
+pub fn resolve_final_status<'a>(
+    tx_outcome: &'a ExecutionOutcomeView,
+    receipt_outcome: impl Fn(&CryptoHash) -> Option<&'a ExecutionOutcomeView>,
+) -> Option<ExecutionStatusView> {
+    follow_final_status(&tx_outcome.status, receipt_outcome).ok()
+}
+
+/// Follows the chain of `SuccessReceiptId` from the given status. Returns the ID of the first
+/// receipt in the chain without a known outcome as the error.
+pub fn follow_final_status<'a>(
+    status: &'a ExecutionStatusView,
+    receipt_outcome: impl Fn(&CryptoHash) -> Option<&'a ExecutionOutcomeView>,
+) -> Result<ExecutionStatusView, CryptoHash> {
+    let mut status = status;
+    loop {
+        match status {
+            ExecutionStatusView::SuccessReceiptId(receipt_id) => {
+                status = &receipt_outcome(receipt_id).ok_or(*receipt_id)?.status;
+            }
+            status => return Ok(status.clone()),
+        }
+    }
+}
+
+struct PendingTrace {
+    trace: TransactionTrace,
+    num_pending_receipts: usize,
+}
+
+/// Rebuilds the execution trees of the transactions from consecutive blocks.
+pub struct ReceiptTracker {
+    max_age: BlockHeight,
+    traces: HashMap<CryptoHash, PendingTrace>,
+    /// The pending receipts with the transaction hash and the parent ID
+    pending_receipts: HashMap<CryptoHash, (CryptoHash, CryptoHash)>,
+    evicted: Vec<TransactionTrace>,
+}
+
+impl Default for ReceiptTracker {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_TRACE_AGE)
+    }
+}
+
+impl ReceiptTracker {
+    /// The transactions that are not completed within `max_age` blocks after their inclusion are
+    /// evicted.
+    pub fn new(max_age: BlockHeight) -> Self {
+        Self {
+            max_age,
+            traces: HashMap::new(),
+            pending_receipts: HashMap::new(),
+            evicted: vec![],
+        }
+    }
+
+    /// The number of the incomplete transactions.
+    pub fn num_pending(&self) -> usize {
+        self.traces.len()
+    }
+
+    /// Returns the incomplete traces evicted since the last call.
+    pub fn drain_evicted(&mut self) -> Vec<TransactionTrace> {
+        std::mem::take(&mut self.evicted)
+    }
+
+    /// Processes the next block and returns the transactions completed in it.
+    pub fn process_block(&mut self, block: &BlockWithTxHashes) -> Vec<TransactionTrace> {
+        let block_height = block.block.header.height;
+        let mut completed = vec![];
+        for shard in &block.shards {
+            for tx in shard.chunk.iter().flat_map(|chunk| &chunk.transactions) {
+                let tx_hash = tx.transaction.hash;
+                let execution_outcome = &tx.outcome.execution_outcome;
+                let trace = TransactionTrace {
+                    tx_hash,
+                    block_height,
+                    last_block_height: block_height,
+                    transaction: tx.transaction.clone(),
+                    execution_outcome: execution_outcome.clone(),
+                    receipts: vec![],
+                    status: ExecutionStatusView::Unknown,
+                    gas_burnt: execution_outcome.outcome.gas_burnt,
+                    tokens_burnt: execution_outcome.outcome.tokens_burnt,
+                    logs: execution_outcome.outcome.logs.clone(),
+                };
+                let receipt_ids = &execution_outcome.outcome.receipt_ids;
+                for receipt_id in receipt_ids {
+                    self.pending_receipts
+                        .insert(*receipt_id, (tx_hash, tx_hash));
+                }
+                self.traces.insert(
+                    tx_hash,
+                    PendingTrace {
+                        trace,
+                        num_pending_receipts: receipt_ids.len(),
+                    },
+                );
+                if receipt_ids.is_empty() {
+                    completed.push(tx_hash);
+                }
+            }
+        }
+
+        // The receipts produced in the block can be executed in the same block by another shard
+        let mut outcomes: Vec<_> = block
+            .shards
+            .iter()
+            .flat_map(|shard| {
+                shard
+                    .receipt_execution_outcomes
+                    .iter()
+                    .map(move |outcome| (shard.shard_id, outcome))
+            })
+            .collect();
+        loop {
+            let num_outcomes = outcomes.len();
+            outcomes.retain(|(shard_id, outcome)| {
+                let receipt = &outcome.receipt;
+                let Some((tx_hash, parent_id)) = self.pending_receipts.remove(&receipt.receipt_id)
+                else {
+                    return true;
+                };
+                let pending = self
+                    .traces
+                    .get_mut(&tx_hash)
+                    .expect("Pending receipt without a trace");
+                let execution_outcome = &outcome.execution_outcome;
+                for receipt_id in &execution_outcome.outcome.receipt_ids {
+                    self.pending_receipts
+                        .insert(*receipt_id, (tx_hash, receipt.receipt_id));
+                }
+                pending.num_pending_receipts += execution_outcome.outcome.receipt_ids.len();
+                pending.num_pending_receipts -= 1;
+                let trace = &mut pending.trace;
+                trace.last_block_height = block_height;
+                trace.gas_burnt += execution_outcome.outcome.gas_burnt;
+                trace.tokens_burnt += execution_outcome.outcome.tokens_burnt;
+                trace
+                    .logs
+                    .extend(execution_outcome.outcome.logs.iter().cloned());
+                trace.receipts.push(TraceReceipt {
+                    block_height,
+                    shard_id: *shard_id,
+                    parent_id,
+                    receipt: receipt.clone(),
+                    execution_outcome: execution_outcome.clone(),
+                });
+                if pending.num_pending_receipts == 0 {
+                    completed.push(tx_hash);
+                }
+                false
+            });
+            if outcomes.len() == num_outcomes {
+                break;
+            }
+        }
+
+        let completed = completed
+            .into_iter()
+            .filter_map(|tx_hash| self.traces.remove(&tx_hash))
+            .map(|PendingTrace { mut trace, .. }| {
+                trace.status = resolve_final_status(&trace.execution_outcome.outcome, |id| {
+                    trace
+                        .receipts
+                        .iter()
+                        .find(|receipt| &receipt.receipt.receipt_id == id)
+                        .map(|receipt| &receipt.execution_outcome.outcome)
+                })
+                .unwrap_or(ExecutionStatusView::Unknown);
+                trace
+            })
+            .collect();
+        self.evict(block_height);
+        completed
+    }
+
+    fn evict(&mut self, block_height: BlockHeight) {
+        let max_age = self.max_age;
+        let expired: Vec<_> = self
+            .traces
+            .iter()
+            .filter(|(_, pending)| pending.trace.block_height + max_age < block_height)
+            .map(|(tx_hash, _)| *tx_hash)
+            .collect();
+        if expired.is_empty() {
+            return;
+        }
+        for tx_hash in &expired {
+            let pending = self.traces.remove(tx_hash).unwrap();
+            self.evicted.push(pending.trace);
+        }
+        self.pending_receipts
+            .retain(|_, (tx_hash, _)| self.traces.contains_key(tx_hash));
+    }
+}
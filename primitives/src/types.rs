@@ -1,32 +1,36 @@
 use near_primitives::borsh::{BorshDeserialize, BorshSerialize};
 use std::fmt::Display;
+use std::str::FromStr;
 
-#[derive(
-    Debug,
-    Copy,
-    Clone,
-    PartialEq,
-    Eq,
-    BorshSerialize,
-    BorshDeserialize,
-    serde::Serialize,
-    serde::Deserialize,
-)]
-#[serde(rename_all = "lowercase")]
+/// The chain identifier. Serialized as the lowercase chain name, e.g. `"mainnet"`.
+/// The Borsh encoding of the known chains is the variant index, so the custom chains don't change
+/// the encoding of the existing values.
+/// `ChainId` is not `Copy` because of the custom chains, pass it by reference.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, BorshSerialize)]
 pub enum ChainId {
     Mainnet,
     Testnet,
+    /// Any other chain, e.g. localnet, sandbox or forknet.
+    /// Use `ChainId::try_from` to construct it, so the known chains are never custom.
+    Custom(String),
 }
 
-impl Display for ChainId {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl ChainId {
+    pub fn as_str(&self) -> &str {
         match self {
-            ChainId::Mainnet => write!(f, "mainnet"),
-            ChainId::Testnet => write!(f, "testnet"),
+            ChainId::Mainnet => "mainnet",
+            ChainId::Testnet => "testnet",
+            ChainId::Custom(chain_id) => chain_id,
         }
     }
 }
 
+impl Display for ChainId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
 impl TryFrom<String> for ChainId {
     type Error = String;
 
@@ -34,7 +38,57 @@ impl TryFrom<String> for ChainId {
         match value.as_str() {
             "mainnet" => Ok(ChainId::Mainnet),
             "testnet" => Ok(ChainId::Testnet),
+            _ if !value.is_empty()
+                && value.chars().all(|c| {
+                    c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, '-' | '_' | '.')
+                }) =>
+            {
+                Ok(ChainId::Custom(value))
+            }
             _ => Err(format!("Invalid chain id: {}", value)),
         }
     }
 }
+
+impl FromStr for ChainId {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ChainId::try_from(s.to_string())
+    }
+}
+
+/// Validates the custom chains like `ChainId::try_from`.
+impl BorshDeserialize for ChainId {
+    fn deserialize_reader<R: std::io::Read>(reader: &mut R) -> std::io::Result<Self> {
+        match u8::deserialize_reader(reader)? {
+            0 => Ok(ChainId::Mainnet),
+            1 => Ok(ChainId::Testnet),
+            2 => match ChainId::try_from(String::deserialize_reader(reader)?) {
+                Ok(ChainId::Custom(chain_id)) => Ok(ChainId::Custom(chain_id)),
+                Ok(chain_id) => Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("Known chain id as a custom chain: {}", chain_id),
+                )),
+                Err(e) => Err(std::io::Error::new(std::io::ErrorKind::InvalidData, e)),
+            },
+            variant => Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("Invalid chain id variant: {}", variant),
+            )),
+        }
+    }
+}
+
+impl serde::Serialize for ChainId {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for ChainId {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = <String as serde::Deserialize>::deserialize(deserializer)?;
+        ChainId::try_from(value).map_err(serde::de::Error::custom)
+    }
+}
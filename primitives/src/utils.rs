@@ -1,5 +1,10 @@
+use near_crypto::PublicKey;
+use near_primitives::account::id::{AccountIdRef, AccountType};
+use near_primitives::hash::CryptoHash;
 use near_primitives::types::AccountId;
-use near_primitives::views::StateChangeValueView;
+use near_primitives::views::{
+    ActionView, ReceiptView, SignedTransactionView, StateChangeCauseView, StateChangeValueView,
+};
 
 pub fn state_change_account_id(state_change_value: &StateChangeValueView) -> &AccountId {
     match &state_change_value {
@@ -13,3 +18,147 @@ pub fn state_change_account_id(state_change_value: &StateChangeValueView) -> &Ac
         StateChangeValueView::ContractCodeDeletion { account_id, .. } => account_id,
     }
 }
+
+/// The receipt of the state change cause.
+pub fn cause_receipt_id(cause: &StateChangeCauseView) -> Option<CryptoHash> {
+    match cause {
+        StateChangeCauseView::ActionReceiptProcessingStarted { receipt_hash }
+        | StateChangeCauseView::ActionReceiptGasReward { receipt_hash }
+        | StateChangeCauseView::ReceiptProcessing { receipt_hash }
+        | StateChangeCauseView::PostponedReceipt { receipt_hash } => Some(*receipt_hash),
+        _ => None,
+    }
+}
+
+/// The signer and the receiver of the transaction.
+pub fn transaction_account_ids(transaction: &SignedTransactionView) -> [&AccountId; 2] {
+    [&transaction.signer_id, &transaction.receiver_id]
+}
+
+/// The predecessor and the receiver of the receipt.
+pub fn receipt_account_ids(receipt: &ReceiptView) -> [&AccountId; 2] {
+    [&receipt.predecessor_id, &receipt.receiver_id]
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ActionKind {
+    CreateAccount,
+    DeployContract,
+    FunctionCall,
+    Transfer,
+    Stake,
+    AddKey,
+    DeleteKey,
+    DeleteAccount,
+    Delegate,
+    DeployGlobalContract,
+    DeployGlobalContractByAccountId,
+    UseGlobalContract,
+    UseGlobalContractByAccountId,
+    /// The actions that are only available with the protocol features of `near-primitives`
+    Other,
+}
+
+pub fn action_kind(action: &ActionView) -> ActionKind {
+    #[allow(unreachable_patterns)]
+    match action {
+        ActionView::CreateAccount => ActionKind::CreateAccount,
+        ActionView::DeployContract { .. } => ActionKind::DeployContract,
+        ActionView::FunctionCall { .. } => ActionKind::FunctionCall,
+        ActionView::Transfer { .. } => ActionKind::Transfer,
+        ActionView::Stake { .. } => ActionKind::Stake,
+        ActionView::AddKey { .. } => ActionKind::AddKey,
+        ActionView::DeleteKey { .. } => ActionKind::DeleteKey,
+        ActionView::DeleteAccount { .. } => ActionKind::DeleteAccount,
+        ActionView::Delegate { .. } => ActionKind::Delegate,
+        ActionView::DeployGlobalContract { .. } => ActionKind::DeployGlobalContract,
+        ActionView::DeployGlobalContractByAccountId { .. } => {
+            ActionKind::DeployGlobalContractByAccountId
+        }
+        ActionView::UseGlobalContract { .. } => ActionKind::UseGlobalContract,
+        ActionView::UseGlobalContractByAccountId { .. } => ActionKind::UseGlobalContractByAccountId,
+        _ => ActionKind::Other,
+    }
+}
+
+/// The method name of the function call action.
+pub fn action_method_name(action: &ActionView) -> Option<&str> {
+    match action {
+        ActionView::FunctionCall { method_name, .. } => Some(method_name),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AccountKind {
+    /// 64 hex characters, controlled by the ED25519 key
+    NearImplicit,
+    /// `0x` followed by 40 hex characters, controlled by the Secp256K1 key
+    EthImplicit,
+    /// The `system` account of the refunds
+    System,
+    /// A named account without a parent, e.g. `near`
+    TopLevel,
+    /// A named account with a parent, e.g. `alice.near`
+    SubAccount,
+}
+
+pub fn account_kind(account_id: &AccountIdRef) -> AccountKind {
+    match account_id.get_account_type() {
+        AccountType::NearImplicitAccount => AccountKind::NearImplicit,
+        AccountType::EthImplicitAccount => AccountKind::EthImplicit,
+        AccountType::NamedAccount if account_id.is_system() => AccountKind::System,
+        AccountType::NamedAccount if account_id.is_top_level() => AccountKind::TopLevel,
+        AccountType::NamedAccount => AccountKind::SubAccount,
+    }
+}
+
+pub fn is_near_implicit(account_id: &AccountIdRef) -> bool {
+    account_kind(account_id) == AccountKind::NearImplicit
+}
+
+pub fn is_eth_implicit(account_id: &AccountIdRef) -> bool {
+    account_kind(account_id) == AccountKind::EthImplicit
+}
+
+pub fn is_implicit(account_id: &AccountIdRef) -> bool {
+    account_id.get_account_type().is_implicit()
+}
+
+/// A named account without a parent. Unlike `AccountId::is_top_level`, the implicit accounts are
+/// not top-level.
+pub fn is_top_level(account_id: &AccountIdRef) -> bool {
+    account_kind(account_id) == AccountKind::TopLevel
+}
+
+/// Whether the account is a direct sub-account of the parent, e.g. `alice.near` of `near`, but not
+/// `app.alice.near`. Use `account_ancestors` for any depth.
+pub fn is_sub_account_of(account_id: &AccountIdRef, parent_id: &AccountIdRef) -> bool {
+    account_id.is_sub_account_of(parent_id)
+}
+
+pub fn is_system(account_id: &AccountIdRef) -> bool {
+    account_id.is_system()
+}
+
+/// The implicit account controlled by the key: NEAR-implicit for ED25519 keys and ETH-implicit
+/// for Secp256K1 keys.
+pub fn implicit_account_id(public_key: &PublicKey) -> AccountId {
+    match public_key {
+        PublicKey::ED25519(public_key) => {
+            near_primitives::utils::derive_near_implicit_account_id(public_key)
+        }
+        PublicKey::SECP256K1(public_key) => {
+            near_primitives::utils::derive_eth_implicit_account_id(public_key)
+        }
+    }
+}
+
+/// The parent accounts from the closest one, e.g. `b.near` and `near` for `a.b.near`.
+pub fn account_ancestors(account_id: &AccountIdRef) -> impl Iterator<Item = &AccountIdRef> {
+    std::iter::successors(account_id.get_parent_account_id(), |account_id| {
+        account_id.get_parent_account_id()
+    })
+}
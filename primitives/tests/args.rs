@@ -0,0 +1,99 @@
+use fastnear_primitives::args::{ArgsDecoderRegistry, DecodedArgs};
+use serde_json::json;
+
+mod common;
+use common::{account, read_block};
+
+#[test]
+fn decode_bytes() {
+    assert_eq!(DecodedArgs::from_bytes(b""), DecodedArgs::Empty);
+    assert_eq!(
+        DecodedArgs::from_bytes(br#"{"a":1}"#),
+        DecodedArgs::Json(json!({"a": 1}))
+    );
+    let binary = DecodedArgs::from_bytes(&[0, 1, 2]);
+    assert_eq!(binary, DecodedArgs::Binary(vec![0, 1, 2]));
+    assert_eq!(
+        serde_json::to_value(&binary).unwrap(),
+        json!({"type": "binary", "value": "AAEC"})
+    );
+}
+
+#[test]
+fn decode_block_args() {
+    let block = read_block();
+    let mut registry = ArgsDecoderRegistry::new();
+    registry.register(account("legacy.near"), "transfer", |args: &[u8]| {
+        let value: serde_json::Value = serde_json::from_slice(args).map_err(|e| e.to_string())?;
+        Ok(json!({"to": value["receiver_id"], "amount": value["amount"]}))
+    });
+    registry.register(account("nft.near"), "nft_transfer", |_: &[u8]| {
+        Err("Unsupported".to_string())
+    });
+
+    let args: Vec<_> = block
+        .actions()
+        .map(|action| {
+            (
+                action.receipt.receiver_id.to_string(),
+                action.args(&registry),
+            )
+        })
+        .collect();
+    assert_eq!(args.len(), 6);
+
+    let (receiver_id, ft_transfer) = &args[0];
+    assert_eq!(receiver_id, "token.near");
+    let ft_transfer = ft_transfer.as_ref().unwrap();
+    assert!(matches!(ft_transfer, DecodedArgs::Json(_)));
+    assert_eq!(
+        ft_transfer.as_json().unwrap()["receiver_id"],
+        json!("bob.near")
+    );
+
+    // The refund is a transfer
+    assert_eq!(args[1].1, None);
+
+    let nft_transfer = args[4].1.as_ref().unwrap();
+    assert!(matches!(nft_transfer, DecodedArgs::Invalid { error, .. } if error == "Unsupported"));
+
+    let legacy_transfer = args[5].1.as_ref().unwrap();
+    assert_eq!(
+        legacy_transfer,
+        &DecodedArgs::Custom(json!({"to": "carol.near", "amount": "5"}))
+    );
+
+    let transactions: Vec<_> = block.transactions().collect();
+    let tx_args = transactions[0].args(&registry);
+    assert_eq!(tx_args.len(), 1);
+    assert_eq!(
+        tx_args[0].as_ref().unwrap().as_json().unwrap()["amount"],
+        json!("100")
+    );
+    // A transfer transaction
+    assert_eq!(transactions[1].args(&registry), vec![None]);
+}
+
+#[test]
+fn registry_lookup() {
+    let decoder = |value: &'static str| move |_: &[u8]| Ok(json!(value));
+    let mut registry = ArgsDecoderRegistry::new();
+    registry.register(account("token.near"), "ft_transfer", decoder("ft_transfer"));
+    registry.register(account("token.near"), "ft_mint", decoder("ft_mint"));
+    registry.register(account("token.near"), "ft_mint", decoder("ft_mint_v2"));
+    registry.register(account("nft.near"), "ft_transfer", decoder("nft"));
+
+    let decode = |receiver_id: &str, method_name: &str| {
+        registry
+            .get(&account(receiver_id), method_name)
+            .map(|decoder| decoder.decode(b"").unwrap())
+    };
+    assert_eq!(
+        decode("token.near", "ft_transfer"),
+        Some(json!("ft_transfer"))
+    );
+    assert_eq!(decode("token.near", "ft_mint"), Some(json!("ft_mint_v2")));
+    assert_eq!(decode("nft.near", "ft_transfer"), Some(json!("nft")));
+    assert_eq!(decode("nft.near", "ft_mint"), None);
+    assert_eq!(decode("other.near", "ft_transfer"), None);
+}
@@ -0,0 +1,223 @@
+use arrow_array::cast::AsArray;
+use arrow_array::types::UInt64Type;
+use arrow_array::Array;
+use fastnear_primitives::arrow::{to_record_batches, ArrowExportError, ParquetWriter, Table};
+use fastnear_primitives::near_primitives::views::{ActionView, ReceiptEnumView};
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use std::path::Path;
+
+mod common;
+use common::{read_block, read_block_at};
+
+#[test]
+fn record_batches() {
+    let batches = to_record_batches(&[read_block_at(150000000), read_block_at(150000001)]).unwrap();
+    let num_rows: Vec<_> = Table::ALL
+        .iter()
+        .map(|table| (table.name(), batches[table].num_rows()))
+        .collect();
+    assert_eq!(
+        num_rows,
+        vec![
+            ("blocks", 2),
+            ("chunks", 4),
+            ("transactions", 4),
+            ("actions", 12),
+            ("receipts", 12),
+            ("outcomes", 16),
+            ("logs", 6),
+            ("state_changes", 20),
+        ]
+    );
+    for (table, batch) in &batches {
+        assert_eq!(batch.schema(), table.schema());
+    }
+
+    let heights = batches[&Table::Blocks]
+        .column_by_name("block_height")
+        .unwrap()
+        .as_primitive::<UInt64Type>();
+    assert_eq!(heights.values().to_vec(), vec![150000000, 150000001]);
+
+    let actions = &batches[&Table::Actions];
+    let kinds = actions
+        .column_by_name("action_kind")
+        .unwrap()
+        .as_string::<i32>();
+    assert_eq!(kinds.value(0), "function_call");
+    assert_eq!(kinds.value(1), "transfer");
+    let methods = actions
+        .column_by_name("method_name")
+        .unwrap()
+        .as_string::<i32>();
+    assert_eq!(methods.value(0), "ft_transfer");
+    assert!(methods.is_null(1));
+
+    let outcomes = &batches[&Table::Outcomes];
+    let kinds = outcomes
+        .column_by_name("outcome_kind")
+        .unwrap()
+        .as_string::<i32>();
+    assert_eq!(kinds.value(0), "transaction");
+    assert_eq!(kinds.value(1), "receipt");
+    let receipt_ids = outcomes
+        .column_by_name("receipt_ids")
+        .unwrap()
+        .as_list::<i32>();
+    assert_eq!(receipt_ids.value(0).len(), 1);
+
+    let logs = &batches[&Table::Logs];
+    let is_event = logs.column_by_name("is_event").unwrap().as_boolean();
+    assert_eq!(
+        (0..3).map(|i| is_event.value(i)).collect::<Vec<_>>(),
+        vec![true, true, false]
+    );
+
+    let state_changes = &batches[&Table::StateChanges];
+    let change_types = state_changes
+        .column_by_name("change_type")
+        .unwrap()
+        .as_string::<i32>();
+    assert_eq!(change_types.value(0), "account_update");
+    let cause_types = state_changes
+        .column_by_name("cause_type")
+        .unwrap()
+        .as_string::<i32>();
+    assert_eq!(cause_types.value(0), "transaction_processing");
+}
+
+#[test]
+fn parquet_files_by_height_range() {
+    let dir = std::env::temp_dir().join(format!("fastnear-parquet-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+
+    let mut writer = ParquetWriter::new(&dir, 10);
+    for height in [150000000, 150000009, 150000010] {
+        writer.write_block(&read_block_at(height)).unwrap();
+    }
+    // The first range is written once the next range starts
+    assert_eq!(writer.written_files().len(), Table::ALL.len());
+    let files = writer.finish().unwrap();
+    assert_eq!(files.len(), 2 * Table::ALL.len());
+
+    let read_rows = |table: &str, range: &str| {
+        let file = std::fs::File::open(dir.join(table).join(format!("{}.parquet", range))).unwrap();
+        ParquetRecordBatchReaderBuilder::try_new(file)
+            .unwrap()
+            .build()
+            .unwrap()
+            .map(|batch| batch.unwrap().num_rows())
+            .sum::<usize>()
+    };
+    assert_eq!(read_rows("blocks", "000150000000-000150000010"), 2);
+    assert_eq!(read_rows("blocks", "000150000010-000150000020"), 1);
+    assert_eq!(read_rows("state_changes", "000150000000-000150000010"), 20);
+    assert_eq!(read_rows("logs", "000150000010-000150000020"), 3);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn stake_column() {
+    let mut block = read_block();
+    let receipt = &mut block.shards[0].receipt_execution_outcomes[0].receipt;
+    let ReceiptEnumView::Action { actions, .. } = &mut receipt.receipt else {
+        panic!("Expected an action receipt");
+    };
+    actions[0] = ActionView::Stake {
+        stake: 5,
+        public_key: "ed25519:6E8sCci9badyRkXb3JoRpBj5p8C6Tw41ELDZoiihKEtp"
+            .parse()
+            .unwrap(),
+    };
+
+    let batches = to_record_batches(&[block]).unwrap();
+    let actions = &batches[&Table::Actions];
+    let kinds = actions
+        .column_by_name("action_kind")
+        .unwrap()
+        .as_string::<i32>();
+    let stake_index = (0..kinds.len())
+        .find(|&i| kinds.value(i) == "stake")
+        .unwrap();
+    let deposits = actions
+        .column_by_name("deposit")
+        .unwrap()
+        .as_string::<i32>();
+    let stakes = actions.column_by_name("stake").unwrap().as_string::<i32>();
+    assert!(deposits.is_null(stake_index));
+    assert_eq!(stakes.value(stake_index), "5");
+    let transfer_index = (0..kinds.len())
+        .find(|&i| kinds.value(i) == "transfer")
+        .unwrap();
+    assert!(!deposits.is_null(transfer_index));
+    assert!(stakes.is_null(transfer_index));
+}
+
+fn num_parquet_rows(path: &Path) -> usize {
+    let file = std::fs::File::open(path).unwrap();
+    ParquetRecordBatchReaderBuilder::try_new(file)
+        .unwrap()
+        .build()
+        .unwrap()
+        .map(|batch| batch.unwrap().num_rows())
+        .sum()
+}
+
+#[test]
+fn unordered_blocks() {
+    let dir =
+        std::env::temp_dir().join(format!("fastnear-parquet-unordered-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+
+    let mut writer = ParquetWriter::new(&dir, 10);
+    for height in [150000000, 150000001, 150000010] {
+        writer.write_block(&read_block_at(height)).unwrap();
+    }
+    for height in [150000005, 150000010] {
+        match writer.write_block(&read_block_at(height)) {
+            Err(ArrowExportError::UnorderedBlock {
+                block_height,
+                last_block_height: 150000010,
+            }) => assert_eq!(block_height, height),
+            result => panic!("Unexpected result: {:?}", result),
+        }
+    }
+    assert_eq!(writer.finish().unwrap().len(), 2 * Table::ALL.len());
+    let blocks_file = dir.join("blocks/000150000010-000150000020.parquet");
+    assert_eq!(num_parquet_rows(&blocks_file), 1);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn written_ranges_are_not_overwritten() {
+    let dir = std::env::temp_dir().join(format!("fastnear-parquet-rewrite-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let blocks_file = dir.join("blocks/000150000000-000150000010.parquet");
+
+    let mut writer = ParquetWriter::new(&dir, 10);
+    writer.write_block(&read_block_at(150000000)).unwrap();
+    writer.finish().unwrap();
+
+    // The second export of the same range fails without touching the first one
+    let mut writer = ParquetWriter::new(&dir, 10);
+    for height in [150000001, 150000002] {
+        writer.write_block(&read_block_at(height)).unwrap();
+    }
+    match writer.finish() {
+        Err(ArrowExportError::IoError(e)) => {
+            assert_eq!(e.kind(), std::io::ErrorKind::AlreadyExists)
+        }
+        result => panic!("Unexpected result: {:?}", result.map(|files| files.len())),
+    }
+    assert!(writer.written_files().is_empty());
+    assert_eq!(num_parquet_rows(&blocks_file), 1);
+
+    // The blocks are kept until the files are written
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(writer.finish().unwrap().len(), Table::ALL.len());
+    assert_eq!(num_parquet_rows(&blocks_file), 2);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,91 @@
+use fastnear_primitives::balances::AccountBalance;
+use fastnear_primitives::near_primitives::views::StateChangeCauseView;
+use std::collections::HashMap;
+
+mod common;
+use common::{account, read_block};
+
+const NEAR: u128 = 10u128.pow(24);
+
+#[test]
+fn balance_changes() {
+    let block = read_block();
+    let changes = block.balance_changes();
+    let accounts: Vec<_> = changes
+        .iter()
+        .map(|change| change.account_id.as_str())
+        .collect();
+    assert_eq!(
+        accounts,
+        vec![
+            "alice.near",
+            "token.near",
+            "alice.near",
+            "token.near",
+            "bob.near",
+            "carol.near"
+        ]
+    );
+
+    // The first change of the account in the block has no previous balance
+    let alice_tx = &changes[0];
+    assert_eq!(alice_tx.before, None);
+    assert_eq!(alice_tx.amount_delta(), None);
+    assert_eq!(alice_tx.receipt_id, None);
+    assert_eq!(
+        alice_tx.tx_hash,
+        Some(
+            block.shards[0].chunk.as_ref().unwrap().transactions[0]
+                .transaction
+                .hash
+        )
+    );
+    assert!(matches!(
+        alice_tx.cause,
+        StateChangeCauseView::TransactionProcessing { .. }
+    ));
+
+    // The gas refund of alice.near
+    let refund = &block.shards[0].receipt_execution_outcomes[1];
+    let alice_refund = &changes[2];
+    assert_eq!(alice_refund.receipt_id, Some(refund.receipt.receipt_id));
+    assert_eq!(alice_refund.tx_hash, refund.tx_hash);
+    assert_eq!(
+        alice_refund.amount_delta(),
+        Some(1_250_000_000_000_000_000_000)
+    );
+    assert_eq!(alice_refund.locked_delta(), Some(0));
+    assert_eq!(alice_refund.storage_usage_delta(), Some(0));
+    assert_eq!(alice_refund.block_height, 150000000);
+
+    assert_eq!(changes[3].amount_delta(), Some(1));
+}
+
+#[test]
+fn balance_changes_across_blocks() {
+    let block = read_block();
+    let mut balances = HashMap::from([(
+        account("bob.near"),
+        AccountBalance {
+            amount: 3 * NEAR,
+            locked: 0,
+            storage_usage: 500,
+        },
+    )]);
+    let changes = block.balance_changes_with(&mut balances);
+    let bob = changes
+        .iter()
+        .find(|change| change.account_id == account("bob.near"))
+        .unwrap();
+    assert_eq!(
+        bob.amount_delta(),
+        Some(1_999_000_000_000_000_000_000_000 - 3 * NEAR as i128)
+    );
+
+    // The balances are updated with the block
+    assert_eq!(balances.len(), 4);
+    assert_eq!(
+        balances[&account("alice.near")].amount,
+        5_001_250_000_000_000_000_000_000
+    );
+}
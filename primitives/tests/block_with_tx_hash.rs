@@ -0,0 +1,56 @@
+use fastnear_primitives::block_with_tx_hash::BlockWithTxHashes;
+use fastnear_primitives::near_indexer_primitives::StreamerMessage;
+
+mod common;
+use common::read_block;
+
+#[test]
+fn streamer_message_round_trip() {
+    let block = read_block();
+    let expected = serde_json::to_value(&block).unwrap();
+
+    let streamer_message = StreamerMessage::from(block);
+    let block = BlockWithTxHashes::from_streamer_message_with_tx_hashes(streamer_message.clone());
+
+    let tx_hashes: Vec<_> = block
+        .shards
+        .iter()
+        .flat_map(|shard| &shard.receipt_execution_outcomes)
+        .map(|outcome| outcome.tx_hash)
+        .collect();
+    let tx_hash = |index: usize| {
+        block.shards[index].chunk.as_ref().unwrap().transactions[0]
+            .transaction
+            .hash
+    };
+    // The receipts from the transactions of earlier blocks can't be traced
+    assert_eq!(
+        tx_hashes,
+        vec![
+            Some(tx_hash(0)),
+            Some(tx_hash(0)),
+            None,
+            Some(tx_hash(1)),
+            None,
+            None
+        ]
+    );
+
+    let mut expected_streamer_message = expected.clone();
+    for shard in expected_streamer_message["shards"].as_array_mut().unwrap() {
+        for outcome in shard["receipt_execution_outcomes"].as_array_mut().unwrap() {
+            outcome.as_object_mut().unwrap().remove("tx_hash");
+        }
+    }
+    assert_eq!(
+        serde_json::to_value(&streamer_message).unwrap(),
+        expected_streamer_message
+    );
+
+    let untraced = BlockWithTxHashes::from(streamer_message);
+    assert!(untraced
+        .shards
+        .iter()
+        .flat_map(|shard| &shard.receipt_execution_outcomes)
+        .all(|outcome| outcome.tx_hash.is_none()));
+}
@@ -0,0 +1,45 @@
+//! The fixtures shared by the tests. Each test crate uses a part of them.
+#![allow(dead_code)]
+
+use fastnear_primitives::block_with_tx_hash::BlockWithTxHashes;
+use fastnear_primitives::near_primitives::types::{AccountId, BlockHeight};
+
+/// The hand-built block with the transactions, receipts and state changes of 2 shards.
+pub const BLOCK_PATH: &str = "../res/blocks/synthetic_block.json";
+
+pub fn read_block_json() -> Vec<u8> {
+    std::fs::read(BLOCK_PATH).unwrap()
+}
+
+pub fn read_block() -> BlockWithTxHashes {
+    serde_json::from_slice(&read_block_json()).unwrap()
+}
+
+/// The fixture block at the given height.
+pub fn read_block_at(height: BlockHeight) -> BlockWithTxHashes {
+    let mut block = read_block();
+    block.block.header.height = height;
+    block
+}
+
+/// The block with the transactions, but without the receipts.
+pub fn transactions_block() -> BlockWithTxHashes {
+    let mut block = read_block();
+    for shard in &mut block.shards {
+        shard.receipt_execution_outcomes.clear();
+    }
+    block
+}
+
+/// The block that executes the receipts of the fixture, at the given height.
+pub fn receipts_block(height: BlockHeight) -> BlockWithTxHashes {
+    let mut block = read_block_at(height);
+    for shard in &mut block.shards {
+        shard.chunk = None;
+    }
+    block
+}
+
+pub fn account(account_id: &str) -> AccountId {
+    account_id.parse().unwrap()
+}
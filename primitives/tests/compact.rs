@@ -0,0 +1,52 @@
+use fastnear_primitives::block_with_tx_hash::BlockWithTxHashes;
+use fastnear_primitives::compact::{CompactEncoding, CompactError, COMPACT_VERSION};
+
+mod common;
+use common::read_block;
+
+#[test]
+fn block_round_trip() {
+    let block = read_block();
+
+    let data = block.to_compact().unwrap();
+    let decoded = BlockWithTxHashes::from_compact(&data).unwrap();
+
+    assert_eq!(
+        serde_json::to_value(&decoded).unwrap(),
+        serde_json::to_value(&block).unwrap()
+    );
+    assert!(data.len() < serde_json::to_vec(&block).unwrap().len());
+}
+
+#[test]
+fn blocks_round_trip() {
+    let blocks = vec![read_block(), read_block()];
+
+    let data = blocks.to_compact().unwrap();
+    let decoded = Vec::<BlockWithTxHashes>::from_compact(&data).unwrap();
+
+    assert_eq!(
+        serde_json::to_value(&decoded).unwrap(),
+        serde_json::to_value(&blocks).unwrap()
+    );
+}
+
+#[test]
+fn invalid_header() {
+    let mut data = read_block().to_compact().unwrap();
+
+    assert!(matches!(
+        BlockWithTxHashes::from_compact(&data[..2]),
+        Err(CompactError::InvalidHeader)
+    ));
+    assert!(matches!(
+        BlockWithTxHashes::from_compact(b"{\"block\": {}}"),
+        Err(CompactError::InvalidHeader)
+    ));
+
+    data[3] = COMPACT_VERSION + 1;
+    assert!(matches!(
+        BlockWithTxHashes::from_compact(&data),
+        Err(CompactError::UnsupportedVersion(version)) if version == COMPACT_VERSION + 1
+    ));
+}
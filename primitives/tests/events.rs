@@ -0,0 +1,62 @@
+use fastnear_primitives::events::{outcome_events, Event};
+
+mod common;
+use common::read_block;
+
+#[test]
+fn block_events() {
+    let block = read_block();
+    let events: Vec<_> = block.events().map(Result::unwrap).collect();
+    assert_eq!(events.len(), 2);
+
+    let ft_transfer = &events[0];
+    let outcome = &block.shards[0].receipt_execution_outcomes[0];
+    assert_eq!(ft_transfer.event.standard, "nep141");
+    assert_eq!(ft_transfer.event.version, "1.0.0");
+    assert_eq!(ft_transfer.event.event, "ft_transfer");
+    assert_eq!(
+        ft_transfer.event.data.as_ref().unwrap()[0]["amount"],
+        serde_json::json!("100")
+    );
+    assert_eq!(ft_transfer.context.block_height, 150000000);
+    assert_eq!(ft_transfer.context.receipt_id, outcome.receipt.receipt_id);
+    assert_eq!(ft_transfer.context.tx_hash, outcome.tx_hash);
+    assert_eq!(ft_transfer.context.account_id.as_str(), "token.near");
+    assert_eq!(ft_transfer.context.log_index, 0);
+
+    assert_eq!(events[1].event.standard, "nep171");
+    assert_eq!(events[1].context.account_id.as_str(), "nft.near");
+}
+
+#[test]
+fn malformed_events() {
+    let block = read_block();
+    let mut outcome = block.shards[0].receipt_execution_outcomes[0].clone();
+    outcome.execution_outcome.outcome.logs = vec![
+        "plain log".to_string(),
+        "EVENT_JSON:{\"standard\":\"nep141\"".to_string(),
+        "EVENT_JSON:{\"standard\":\"nep141\",\"version\":\"1.0.0\"}".to_string(),
+        "EVENT_JSON:{\"standard\":\"nep171\",\"version\":\"1.0.0\",\"event\":\"nft_mint\"}"
+            .to_string(),
+    ];
+
+    let events = outcome_events(&outcome, 150000000);
+    assert_eq!(events.len(), 3);
+    let errors: Vec<_> = events.iter().filter_map(|e| e.as_ref().err()).collect();
+    assert_eq!(errors.len(), 2);
+    assert_eq!(errors[0].context.log_index, 1);
+    assert_eq!(errors[0].log, outcome.execution_outcome.outcome.logs[1]);
+    assert_eq!(errors[1].context.log_index, 2);
+
+    let event = events[2].as_ref().unwrap();
+    assert_eq!(event.context.log_index, 3);
+    assert_eq!(
+        event.event,
+        Event {
+            standard: "nep171".to_string(),
+            version: "1.0.0".to_string(),
+            event: "nft_mint".to_string(),
+            data: None,
+        }
+    );
+}
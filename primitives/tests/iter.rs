@@ -0,0 +1,75 @@
+use fastnear_primitives::utils::ActionKind;
+
+mod common;
+use common::{account, read_block};
+
+#[test]
+fn items_carry_context() {
+    let block = read_block();
+
+    let transactions: Vec<_> = block.transactions().collect();
+    assert_eq!(transactions.len(), 2);
+    for tx in &transactions {
+        assert_eq!(tx.block_height, 150000000);
+        assert_eq!(tx.block_timestamp, 1747000000123456789);
+        assert_eq!(tx.tx_hash, Some(tx.transaction.hash));
+    }
+    assert_eq!(
+        transactions
+            .iter()
+            .map(|tx| tx.shard_id.into())
+            .collect::<Vec<u64>>(),
+        vec![0, 1]
+    );
+
+    let receipts: Vec<_> = block.receipts().collect();
+    assert_eq!(receipts.len(), 6);
+    assert_eq!(receipts[0].tx_hash, transactions[0].tx_hash);
+    assert_eq!(receipts[1].tx_hash, transactions[0].tx_hash);
+    assert_eq!(receipts[3].tx_hash, transactions[1].tx_hash);
+
+    // The transaction outcomes come first in every shard
+    let outcomes: Vec<_> = block.outcomes().collect();
+    assert_eq!(outcomes.len(), 8);
+    assert_eq!(outcomes[0].id, transactions[0].transaction.hash);
+    assert_eq!(outcomes[1].id, receipts[0].receipt_id);
+    assert_eq!(outcomes[4].id, transactions[1].transaction.hash);
+
+    // The state changes of the receipts resolve to the transaction of the receipt
+    let state_changes: Vec<_> = block.state_changes().collect();
+    assert_eq!(state_changes.len(), 10);
+    assert!(state_changes.iter().all(|change| change.tx_hash.is_some()));
+    assert_eq!(state_changes[2].tx_hash, transactions[0].tx_hash);
+    assert_eq!(state_changes[2].account_id(), &account("token.near"));
+
+    let actions: Vec<_> = block.actions().collect();
+    assert_eq!(actions.len(), 6);
+    assert_eq!(actions[0].index, 0);
+    assert_eq!(actions[0].signer_id, &account("alice.near"));
+    assert_eq!(actions[0].tx_hash, receipts[0].tx_hash);
+}
+
+#[test]
+fn filters() {
+    let block = read_block();
+
+    let alice = account("alice.near");
+    assert_eq!(block.transactions_by_account(&alice).count(), 1);
+    assert_eq!(block.receipts_by_account(&alice).count(), 2);
+    assert_eq!(block.state_changes_by_account(&alice).count(), 3);
+
+    let bob = account("bob.near");
+    assert_eq!(block.transactions_by_account(&bob).count(), 1);
+    assert_eq!(block.receipts_by_account(&bob).count(), 2);
+
+    assert_eq!(block.actions_by_kind(ActionKind::Transfer).count(), 2);
+    assert_eq!(block.actions_by_kind(ActionKind::FunctionCall).count(), 4);
+    assert_eq!(block.actions_by_kind(ActionKind::Stake).count(), 0);
+
+    let ft_transfers: Vec<_> = block.function_calls("ft_transfer").collect();
+    assert_eq!(ft_transfers.len(), 2);
+    assert!(ft_transfers
+        .iter()
+        .all(|action| action.receipt.receiver_id == account("token.near")));
+    assert_eq!(block.function_calls("nft_transfer").count(), 1);
+}
@@ -0,0 +1,101 @@
+//! The comparison with `serde_json` only runs with the SIMD parser, e.g.
+//! `cargo test -p fastnear-primitives --features simd-json`.
+use fastnear_primitives::block_with_tx_hash::BlockWithTxHashes;
+use fastnear_primitives::json;
+use fastnear_primitives::near_primitives::types::ShardId;
+use fastnear_primitives::select::{BlockSeed, BlockSelector, OptionalSeed};
+
+mod common;
+use common::read_block_json;
+
+#[cfg(feature = "simd-json")]
+const HEAVY_BLOCKS_DIR: &str = "../res/blocks/heavy";
+
+#[cfg(feature = "simd-json")]
+fn read_heavy_blocks() -> Vec<Vec<u8>> {
+    use std::io::Read;
+
+    let mut paths: Vec<_> = std::fs::read_dir(HEAVY_BLOCKS_DIR)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.to_string_lossy().ends_with(".json.gz"))
+        .collect();
+    paths.sort();
+    paths
+        .into_iter()
+        .map(|path| {
+            let mut data = vec![];
+            flate2::read::GzDecoder::new(std::fs::File::open(path).unwrap())
+                .read_to_end(&mut data)
+                .unwrap();
+            data
+        })
+        .collect()
+}
+
+#[cfg(feature = "simd-json")]
+fn assert_same_as_serde_json(data: &[u8]) {
+    let expected: BlockWithTxHashes = serde_json::from_slice(data).unwrap();
+    let block = BlockWithTxHashes::from_json_slice(data).unwrap();
+    assert_eq!(
+        serde_json::to_value(&block).unwrap(),
+        serde_json::to_value(&expected).unwrap()
+    );
+    let block: BlockWithTxHashes = json::from_mut_slice(&mut data.to_vec()).unwrap();
+    assert_eq!(
+        serde_json::to_value(&block).unwrap(),
+        serde_json::to_value(&expected).unwrap()
+    );
+}
+
+#[cfg(feature = "simd-json")]
+#[test]
+fn decodes_same_as_serde_json() {
+    assert_same_as_serde_json(&read_block_json());
+    let heavy_blocks = read_heavy_blocks();
+    assert!(!heavy_blocks.is_empty());
+    for data in &heavy_blocks {
+        assert_same_as_serde_json(data);
+    }
+}
+
+struct Shard(ShardId);
+
+impl BlockSelector for Shard {
+    fn select_shard(&self, shard_id: ShardId) -> bool {
+        shard_id == self.0
+    }
+}
+
+#[test]
+fn decodes_selected_in_place() {
+    let data = read_block_json();
+    let selector = Shard(ShardId::from(1u64));
+    let mut deserializer = serde_json::Deserializer::from_slice(&data);
+    let expected = BlockWithTxHashes::deserialize_selected(&mut deserializer, &selector).unwrap();
+    assert_eq!(expected.shards.len(), 1);
+
+    let block =
+        BlockWithTxHashes::from_json_mut_slice_selected(&mut data.clone(), &selector).unwrap();
+    assert_eq!(
+        serde_json::to_value(&block).unwrap(),
+        serde_json::to_value(&expected).unwrap()
+    );
+    let block =
+        json::from_mut_slice_seed(&mut data.clone(), OptionalSeed(BlockSeed(&selector))).unwrap();
+    assert_eq!(
+        serde_json::to_value(block.unwrap()).unwrap(),
+        serde_json::to_value(&expected).unwrap()
+    );
+    let block =
+        json::from_mut_slice_seed(&mut b"null".to_vec(), OptionalSeed(BlockSeed(&selector)));
+    assert!(block.unwrap().is_none());
+}
+
+#[test]
+fn invalid_json() {
+    let data = read_block_json();
+    assert!(BlockWithTxHashes::from_json_slice(&data[..data.len() / 2]).is_err());
+    assert!(BlockWithTxHashes::from_json_slice(b"{\"block\":null}").is_err());
+    assert!(json::from_slice::<BlockWithTxHashes>(b"").is_err());
+}
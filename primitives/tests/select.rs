@@ -0,0 +1,136 @@
+use fastnear_primitives::block_with_tx_hash::{
+    BlockWithTxHashes, IndexerExecutionOutcomeWithReceiptAndTxHash,
+};
+use fastnear_primitives::near_primitives::types::ShardId;
+use fastnear_primitives::near_primitives::views::StateChangeWithCauseView;
+use fastnear_primitives::select::{BlockSeed, BlockSelector, OptionalSeed};
+use fastnear_primitives::utils::state_change_account_id;
+use serde::de::DeserializeSeed;
+
+mod common;
+use common::read_block_json;
+
+fn decode(data: &[u8], selector: &impl BlockSelector) -> BlockWithTxHashes {
+    let mut deserializer = serde_json::Deserializer::from_slice(data);
+    BlockWithTxHashes::deserialize_selected(&mut deserializer, selector).unwrap()
+}
+
+struct All;
+
+impl BlockSelector for All {}
+
+struct Shards(Vec<ShardId>);
+
+impl BlockSelector for Shards {
+    fn select_shard(&self, shard_id: ShardId) -> bool {
+        self.0.contains(&shard_id)
+    }
+}
+
+struct Account(&'static str);
+
+impl BlockSelector for Account {
+    fn select_transaction(
+        &self,
+        transaction: &fastnear_primitives::near_indexer_primitives::IndexerTransactionWithOutcome,
+    ) -> bool {
+        transaction.transaction.signer_id == self.0 || transaction.transaction.receiver_id == self.0
+    }
+
+    fn select_outcome(&self, outcome: &IndexerExecutionOutcomeWithReceiptAndTxHash) -> bool {
+        outcome.receipt.receiver_id == self.0
+    }
+
+    fn select_state_change(&self, state_change: &StateChangeWithCauseView) -> bool {
+        state_change_account_id(&state_change.value) == self.0
+    }
+}
+
+fn to_value(block: &BlockWithTxHashes) -> serde_json::Value {
+    serde_json::to_value(block).unwrap()
+}
+
+#[test]
+fn selects_everything_by_default() {
+    let data = read_block_json();
+    let expected: BlockWithTxHashes = serde_json::from_slice(&data).unwrap();
+    assert_eq!(to_value(&decode(&data, &All)), to_value(&expected));
+}
+
+#[test]
+fn skips_shards() {
+    let data = read_block_json();
+    let expected: BlockWithTxHashes = serde_json::from_slice(&data).unwrap();
+
+    let block = decode(&data, &Shards(vec![ShardId::new(1)]));
+    assert_eq!(block.shards.len(), 1);
+    assert_eq!(
+        serde_json::to_value(&block.shards[0]).unwrap(),
+        serde_json::to_value(&expected.shards[1]).unwrap()
+    );
+    assert_eq!(
+        serde_json::to_value(&block.block).unwrap(),
+        serde_json::to_value(&expected.block).unwrap()
+    );
+    assert!(decode(&data, &Shards(vec![])).shards.is_empty());
+
+    // The shard ID after the other fields
+    let mut value: serde_json::Value = serde_json::from_slice(&data).unwrap();
+    for shard in value["shards"].as_array_mut().unwrap() {
+        let shard_id = shard.as_object_mut().unwrap().remove("shard_id").unwrap();
+        shard["shard_id"] = shard_id;
+    }
+    let block = decode(
+        &serde_json::to_vec(&value).unwrap(),
+        &Shards(vec![ShardId::new(0)]),
+    );
+    assert_eq!(block.shards.len(), 1);
+    assert_eq!(block.shards[0].shard_id, ShardId::new(0));
+}
+
+#[test]
+fn selects_elements() {
+    let data = read_block_json();
+    let block = decode(&data, &Account("carol.near"));
+
+    let num_transactions: usize = block
+        .shards
+        .iter()
+        .map(|shard| {
+            shard
+                .chunk
+                .as_ref()
+                .map_or(0, |chunk| chunk.transactions.len())
+        })
+        .sum();
+    assert_eq!(num_transactions, 1);
+    let receivers: Vec<_> = block
+        .shards
+        .iter()
+        .flat_map(|shard| &shard.receipt_execution_outcomes)
+        .map(|outcome| outcome.receipt.receiver_id.as_str())
+        .collect();
+    assert_eq!(receivers, vec!["carol.near"]);
+    assert!(block
+        .shards
+        .iter()
+        .flat_map(|shard| &shard.state_changes)
+        .all(|state_change| state_change_account_id(&state_change.value) == "carol.near"));
+    // The chunk receipts are kept by the selector
+    let num_receipts: usize = block
+        .shards
+        .iter()
+        .map(|shard| shard.chunk.as_ref().map_or(0, |chunk| chunk.receipts.len()))
+        .sum();
+    assert!(num_receipts > 0);
+}
+
+#[test]
+fn optional_block() {
+    let decode_optional = |data: &[u8]| {
+        OptionalSeed(BlockSeed(&All)).deserialize(&mut serde_json::Deserializer::from_slice(data))
+    };
+    assert!(decode_optional(b"null").unwrap().is_none());
+    let block = decode_optional(&read_block_json()).unwrap().unwrap();
+    assert_eq!(block.shards.len(), 2);
+}
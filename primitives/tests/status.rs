@@ -0,0 +1,79 @@
+use fastnear_primitives::near_primitives::views::ExecutionStatusView;
+use fastnear_primitives::status::TxStatusTracker;
+
+mod common;
+use common::{read_block, receipts_block, transactions_block};
+
+#[test]
+fn finalized_in_one_block() {
+    let block = read_block();
+    let mut tracker = TxStatusTracker::default();
+    let finalized = tracker.process_block(&block);
+    let tx_hashes: Vec<_> = block.transactions().map(|tx| tx.transaction.hash).collect();
+    assert_eq!(
+        finalized.iter().map(|tx| tx.tx_hash).collect::<Vec<_>>(),
+        tx_hashes
+    );
+    for tx in &finalized {
+        assert_eq!(tx.status, ExecutionStatusView::SuccessValue(vec![]));
+        assert_eq!(tx.block_height, 150000000);
+    }
+    assert_eq!(tracker.num_pending(), 0);
+}
+
+#[test]
+fn cross_shard_delay_and_refunds() {
+    let mut tracker = TxStatusTracker::default();
+    assert!(tracker.process_block(&transactions_block()).is_empty());
+    assert_eq!(tracker.num_pending(), 2);
+
+    // Only the function call of the first transaction is executed, its refund is not needed
+    let mut block = receipts_block(150000002);
+    block.shards[0].receipt_execution_outcomes.truncate(1);
+    block.shards[1].receipt_execution_outcomes.clear();
+    let finalized = tracker.process_block(&block);
+    assert_eq!(finalized.len(), 1);
+    assert_eq!(finalized[0].block_height, 150000002);
+    assert_eq!(tracker.num_pending(), 1);
+
+    // The refund of the first transaction doesn't finalize anything again
+    let mut block = receipts_block(150000003);
+    block.shards[0].receipt_execution_outcomes.remove(0);
+    let finalized = tracker.process_block(&block);
+    assert_eq!(finalized.len(), 1);
+    assert_eq!(finalized[0].block_height, 150000003);
+    assert_eq!(tracker.num_pending(), 0);
+}
+
+#[test]
+fn failure_and_eviction() {
+    let mut tracker = TxStatusTracker::new(5);
+    tracker.process_block(&transactions_block());
+
+    let mut block = receipts_block(150000001);
+    let failure = block.shards[0].receipt_execution_outcomes[2]
+        .execution_outcome
+        .outcome
+        .status
+        .clone();
+    assert!(matches!(failure, ExecutionStatusView::Failure(_)));
+    block.shards[0].receipt_execution_outcomes[0]
+        .execution_outcome
+        .outcome
+        .status = failure.clone();
+    block.shards[1].receipt_execution_outcomes.clear();
+    let finalized = tracker.process_block(&block);
+    assert_eq!(finalized.len(), 1);
+    assert_eq!(finalized[0].status, failure);
+
+    let mut block = receipts_block(150000006);
+    for shard in &mut block.shards {
+        shard.receipt_execution_outcomes.clear();
+    }
+    assert!(tracker.process_block(&block).is_empty());
+    assert_eq!(tracker.num_pending(), 0);
+    let tx_hash = read_block().shards[1].chunk.as_ref().unwrap().transactions[0]
+        .transaction
+        .hash;
+    assert_eq!(tracker.drain_evicted(), vec![tx_hash]);
+}
@@ -0,0 +1,214 @@
+use fastnear_primitives::block_with_tx_hash::IndexerExecutionOutcomeWithReceiptAndTxHash;
+use fastnear_primitives::near_primitives::views::{ActionView, ReceiptEnumView};
+use fastnear_primitives::tokens::{
+    outcome_token_events, TokenEvent, TokenRecordSource, TokenStandard,
+};
+
+mod common;
+use common::read_block;
+
+fn with_logs(
+    outcome: &IndexerExecutionOutcomeWithReceiptAndTxHash,
+    logs: &[&str],
+) -> IndexerExecutionOutcomeWithReceiptAndTxHash {
+    let mut outcome = outcome.clone();
+    outcome.execution_outcome.outcome.logs = logs.iter().map(|log| log.to_string()).collect();
+    outcome
+}
+
+#[test]
+fn block_token_events() {
+    let block = read_block();
+    let records: Vec<_> = block.token_events().map(Result::unwrap).collect();
+    // The failed `ft_transfer` of erin.near has no records
+    assert_eq!(records.len(), 2);
+
+    let TokenEvent::Transfer(ft_transfer) = &records[0] else {
+        panic!("Expected a transfer");
+    };
+    let outcome = &block.shards[0].receipt_execution_outcomes[0];
+    assert_eq!(ft_transfer.context.standard, TokenStandard::Nep141);
+    assert_eq!(ft_transfer.context.contract_id.as_str(), "token.near");
+    assert_eq!(ft_transfer.context.receipt_id, outcome.receipt.receipt_id);
+    assert_eq!(ft_transfer.context.tx_hash, outcome.tx_hash);
+    assert_eq!(ft_transfer.context.block_height, 150000000);
+    assert_eq!(
+        ft_transfer.context.source,
+        TokenRecordSource::Event { log_index: 0 }
+    );
+    assert_eq!(ft_transfer.token_id, None);
+    assert_eq!(ft_transfer.amount, 100);
+    assert_eq!(
+        ft_transfer.old_owner_id.as_ref().unwrap().as_str(),
+        "alice.near"
+    );
+    assert_eq!(ft_transfer.new_owner_id.as_str(), "bob.near");
+    assert_eq!(ft_transfer.memo.as_deref(), Some("thanks"));
+
+    let TokenEvent::Transfer(nft_transfer) = &records[1] else {
+        panic!("Expected a transfer");
+    };
+    assert_eq!(nft_transfer.context.standard, TokenStandard::Nep171);
+    assert_eq!(nft_transfer.token_id.as_deref(), Some("42"));
+    assert_eq!(nft_transfer.amount, 1);
+    assert_eq!(
+        nft_transfer.old_owner_id.as_ref().unwrap().as_str(),
+        "dave.near"
+    );
+    assert_eq!(nft_transfer.new_owner_id.as_str(), "erin.near");
+}
+
+#[test]
+fn mints_burns_and_multi_tokens() {
+    let block = read_block();
+    let outcome = with_logs(
+        &block.shards[1].receipt_execution_outcomes[1],
+        &[
+            r#"EVENT_JSON:{"standard":"nep171","version":"1.0.0","event":"nft_mint","data":[{"owner_id":"erin.near","token_ids":["1","2"]}]}"#,
+            r#"EVENT_JSON:{"standard":"nep245","version":"1.0.0","event":"mt_burn","data":[{"owner_id":"erin.near","authorized_id":"dave.near","token_ids":["a","b"],"amounts":["3","4"]}]}"#,
+            r#"EVENT_JSON:{"standard":"nep141","version":"1.0.0","event":"ft_mint","data":[{"owner_id":"erin.near","amount":"7","memo":"init"}]}"#,
+            r#"EVENT_JSON:{"standard":"nep171","version":"1.0.0","event":"nft_metadata_update","data":[{"token_ids":["1"]}]}"#,
+        ],
+    );
+    let records: Vec<_> = outcome_token_events(&outcome, 150000000)
+        .into_iter()
+        .map(Result::unwrap)
+        .collect();
+    assert_eq!(records.len(), 5);
+
+    let TokenEvent::Mint(mint) = &records[1] else {
+        panic!("Expected a mint");
+    };
+    assert_eq!(mint.token_id.as_deref(), Some("2"));
+    assert_eq!(mint.owner_id.as_str(), "erin.near");
+
+    let TokenEvent::Burn(burn) = &records[3] else {
+        panic!("Expected a burn");
+    };
+    assert_eq!(burn.context.standard, TokenStandard::Nep245);
+    assert_eq!(
+        burn.context.source,
+        TokenRecordSource::Event { log_index: 1 }
+    );
+    assert_eq!(burn.token_id.as_deref(), Some("b"));
+    assert_eq!(burn.amount, 4);
+    assert_eq!(burn.authorized_id.as_ref().unwrap().as_str(), "dave.near");
+
+    let TokenEvent::Mint(ft_mint) = &records[4] else {
+        panic!("Expected a mint");
+    };
+    assert_eq!(ft_mint.token_id, None);
+    assert_eq!(ft_mint.amount, 7);
+    assert_eq!(ft_mint.memo.as_deref(), Some("init"));
+}
+
+#[test]
+fn malformed_token_events() {
+    let block = read_block();
+    let outcome = with_logs(
+        &block.shards[1].receipt_execution_outcomes[2],
+        &[
+            r#"EVENT_JSON:{"standard":"nep245","version":"1.0.0","event":"mt_transfer","data":[{"old_owner_id":"a.near","new_owner_id":"b.near","token_ids":["a","b"],"amounts":["3"]}]}"#,
+            r#"EVENT_JSON:{"standard":"nep141","version":"1.0.0","event":"ft_transfer","data":[{"old_owner_id":"a.near","new_owner_id":"b.near","amount":"lots"}]}"#,
+            r#"EVENT_JSON:{"standard":"nep141","event":"ft_transfer"}"#,
+            r#"EVENT_JSON:{"standard":"other","event":"nep141_transfer"}"#,
+            r#"EVENT_JSON:{"standard":"nep141""#,
+            r#"EVENT_JSON:nep171"#,
+        ],
+    );
+    let records = outcome_token_events(&outcome, 150000000);
+    let log_indices: Vec<_> = records
+        .iter()
+        .map(|record| record.as_ref().unwrap_err().context.log_index)
+        .collect();
+    // Only the JSON objects with a token standard are token events
+    assert_eq!(log_indices, vec![0, 1, 2]);
+}
+
+#[test]
+fn legacy_function_call_fallback() {
+    let block = read_block();
+    // The legacy contract doesn't emit events
+    let mut outcome = block.shards[1].receipt_execution_outcomes[2].clone();
+    let ReceiptEnumView::Action { actions, .. } = &mut outcome.receipt.receipt else {
+        panic!("Expected an action receipt");
+    };
+    let ActionView::FunctionCall { method_name, .. } = &mut actions[0] else {
+        panic!("Expected a function call");
+    };
+    *method_name = "ft_transfer".to_string();
+
+    let records: Vec<_> = outcome_token_events(&outcome, 150000000)
+        .into_iter()
+        .map(Result::unwrap)
+        .collect();
+    assert_eq!(records.len(), 1);
+    let TokenEvent::Transfer(transfer) = &records[0] else {
+        panic!("Expected a transfer");
+    };
+    assert_eq!(
+        transfer.context.source,
+        TokenRecordSource::FunctionCall { action_index: 0 }
+    );
+    assert_eq!(transfer.context.contract_id.as_str(), "legacy.near");
+    assert_eq!(transfer.context.tx_hash, outcome.tx_hash);
+    assert_eq!(transfer.old_owner_id.as_ref().unwrap().as_str(), "bob.near");
+    assert_eq!(transfer.new_owner_id.as_str(), "carol.near");
+    assert_eq!(transfer.amount, 5);
+    assert_eq!(transfer.authorized_id, None);
+
+    // The function call is not decoded if the contract emits the events
+    let outcome = with_logs(
+        &outcome,
+        &[
+            r#"EVENT_JSON:{"standard":"nep141","version":"1.0.0","event":"ft_transfer","data":[{"old_owner_id":"bob.near","new_owner_id":"carol.near","amount":"5"}]}"#,
+        ],
+    );
+    let records = outcome_token_events(&outcome, 150000000);
+    assert_eq!(records.len(), 1);
+    assert_eq!(
+        records[0].as_ref().unwrap().context().source,
+        TokenRecordSource::Event { log_index: 0 }
+    );
+}
+
+#[test]
+fn legacy_approved_nft_transfer() {
+    let block = read_block();
+    for (approval_id, old_owner_id, authorized_id) in [
+        (None, Some("bob.near"), None),
+        (Some(7), None, Some("bob.near")),
+    ] {
+        let mut outcome = block.shards[1].receipt_execution_outcomes[2].clone();
+        let ReceiptEnumView::Action { actions, .. } = &mut outcome.receipt.receipt else {
+            panic!("Expected an action receipt");
+        };
+        let args = serde_json::json!({
+            "receiver_id": "carol.near",
+            "token_id": "42",
+            "approval_id": approval_id,
+        });
+        actions[0] = ActionView::FunctionCall {
+            method_name: "nft_transfer".to_string(),
+            args: serde_json::to_vec(&args).unwrap().into(),
+            gas: 30_000_000_000_000,
+            deposit: 1,
+        };
+
+        let records = outcome_token_events(&outcome, 150000000);
+        assert_eq!(records.len(), 1);
+        let TokenEvent::Transfer(transfer) = records[0].as_ref().unwrap() else {
+            panic!("Expected a transfer");
+        };
+        // The predecessor of an approved transfer is the approved account, not the owner
+        assert_eq!(
+            transfer.old_owner_id.as_ref().map(|id| id.as_str()),
+            old_owner_id
+        );
+        assert_eq!(
+            transfer.authorized_id.as_ref().map(|id| id.as_str()),
+            authorized_id
+        );
+        assert_eq!(transfer.token_id.as_deref(), Some("42"));
+    }
+}
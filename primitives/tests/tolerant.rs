@@ -0,0 +1,155 @@
+use fastnear_primitives::block_with_tx_hash::BlockWithTxHashes;
+use fastnear_primitives::tolerant::{from_slice_tolerant, Tolerant, UnknownValue};
+use serde_json::{json, Value};
+
+mod common;
+use common::read_block_json;
+
+fn read_block_value() -> Value {
+    serde_json::from_slice(&read_block_json()).unwrap()
+}
+
+#[test]
+fn known_block_has_no_unknown_values() {
+    let data = read_block_json();
+    let block = BlockWithTxHashes::from_slice_tolerant(&data).unwrap();
+    assert_eq!(block.unknown, vec![]);
+    assert_eq!(
+        serde_json::to_value(&block.value).unwrap(),
+        serde_json::to_value(serde_json::from_slice::<BlockWithTxHashes>(&data).unwrap()).unwrap()
+    );
+}
+
+#[test]
+fn unknown_fields_and_variants() {
+    let mut value = read_block_value();
+    value["block"]["header"]["new_header_field"] = json!({"a": 1});
+    let state_changes = value["shards"][0]["state_changes"].as_array_mut().unwrap();
+    let num_state_changes = state_changes.len();
+    let new_state_change = json!({
+        "cause": {"type": "new_cause"},
+        "type": "new_change",
+        "change": {"account_id": "alice.near"}
+    });
+    state_changes.insert(1, new_state_change.clone());
+    let new_action = json!({"NewAction": {"payload": "AAEC"}});
+    value["shards"][1]["receipt_execution_outcomes"][1]["receipt"]["receipt"]["Action"]["actions"]
+        .as_array_mut()
+        .unwrap()
+        .push(new_action.clone());
+
+    let data = serde_json::to_vec(&value).unwrap();
+    assert!(serde_json::from_slice::<BlockWithTxHashes>(&data).is_err());
+
+    let block = BlockWithTxHashes::from_slice_tolerant(&data).unwrap();
+    assert_eq!(block.value.shards[0].state_changes.len(), num_state_changes);
+    assert_eq!(block.unknown.len(), 3);
+
+    let paths: Vec<_> = block.unknown.iter().map(|unknown| unknown.path()).collect();
+    assert!(paths.contains(&"shards[0].state_changes[1]"));
+    assert!(paths
+        .contains(&"shards[1].receipt_execution_outcomes[1].receipt.receipt.Action.actions[1]"));
+    assert!(paths.contains(&"block.header.new_header_field"));
+
+    for unknown in &block.unknown {
+        match unknown {
+            UnknownValue::Element { path, value, .. } if path.ends_with("state_changes[1]") => {
+                assert_eq!(value, &new_state_change)
+            }
+            UnknownValue::Element { value, .. } => assert_eq!(value, &new_action),
+            UnknownValue::Field { value, .. } => assert_eq!(value, &json!({"a": 1})),
+        }
+    }
+}
+
+#[test]
+fn invalid_block_fails() {
+    let mut value = read_block_value();
+    value["block"]["header"]["height"] = json!("not a height");
+    let data = serde_json::to_vec(&value).unwrap();
+    assert!(BlockWithTxHashes::from_slice_tolerant(&data).is_err());
+}
+
+#[test]
+fn action_positions_are_preserved() {
+    let mut value = read_block_value();
+    let actions_path = "shards[1].receipt_execution_outcomes[1].receipt.receipt.Action.actions";
+    let actions = value["shards"][1]["receipt_execution_outcomes"][1]["receipt"]["receipt"]
+        ["Action"]["actions"]
+        .as_array_mut()
+        .unwrap();
+    actions[0]["FunctionCall"]["new_field"] = json!(1);
+    // The known function call ends up at index 2 of the original actions
+    actions.insert(0, json!({"NewAction": 0}));
+    actions.insert(1, json!({"NewAction": 1}));
+    actions.push(json!({"NewAction": 3}));
+    actions.push(json!("Transfer"));
+    let state_changes = value["shards"][0]["state_changes"].as_array_mut().unwrap();
+    state_changes.insert(1, json!({"type": "new_change", "index": 1}));
+    state_changes.insert(2, json!({"type": "new_change", "index": 2}));
+
+    let data = serde_json::to_vec(&value).unwrap();
+    let block = BlockWithTxHashes::from_slice_tolerant(&data).unwrap();
+
+    let paths: Vec<_> = block.unknown.iter().map(|unknown| unknown.path()).collect();
+    for path in [
+        format!("{}[0]", actions_path),
+        format!("{}[1]", actions_path),
+        format!("{}[3]", actions_path),
+        format!("{}[4]", actions_path),
+        format!("{}[2].FunctionCall.new_field", actions_path),
+        "shards[0].state_changes[1]".to_string(),
+        "shards[0].state_changes[2]".to_string(),
+    ] {
+        assert!(
+            paths.contains(&path.as_str()),
+            "{} is not in {:?}",
+            path,
+            paths
+        );
+    }
+    assert_eq!(block.unknown.len(), 7);
+    for unknown in &block.unknown {
+        if let UnknownValue::Element { path, value, .. } = unknown {
+            if let Some(index) = value.get("NewAction").or(value.get("index")) {
+                assert!(path.ends_with(&format!("[{}]", index)), "{}", path);
+            }
+        }
+    }
+
+    let actions: Vec<_> = block.value.actions().collect();
+    let function_call = actions
+        .iter()
+        .find(|action| action.receipt.receiver_id == "nft.near")
+        .unwrap();
+    assert_eq!(function_call.index, 0);
+    assert_eq!(block.original_index(actions_path, function_call.index), 2);
+    assert_eq!(block.original_index("shards[0].state_changes", 1), 3);
+    assert_eq!(block.original_index("shards[0].state_changes", 0), 0);
+
+    let block = block.into_block();
+    assert_eq!(block.unknown.len(), 7);
+    assert_eq!(block.original_index("shards[0].state_changes", 1), 3);
+}
+
+#[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+struct Renamed {
+    #[serde(alias = "old_name")]
+    new_name: u64,
+}
+
+#[test]
+fn aliased_field_is_reported_as_unknown() {
+    // The field is decoded, but it's serialized under the new name, so it's missing in the
+    // decoded value and reported as unknown.
+    let decoded: Tolerant<Renamed> = from_slice_tolerant(br#"{"old_name": 1}"#).unwrap();
+
+    assert_eq!(decoded.value, Renamed { new_name: 1 });
+    assert_eq!(
+        decoded.unknown,
+        vec![UnknownValue::Field {
+            path: "old_name".to_string(),
+            value: json!(1),
+        }]
+    );
+}
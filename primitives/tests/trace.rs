@@ -0,0 +1,80 @@
+use fastnear_primitives::near_primitives::views::ExecutionStatusView;
+use fastnear_primitives::trace::ReceiptTracker;
+
+mod common;
+use common::{read_block, receipts_block, transactions_block};
+
+#[test]
+fn traces_in_one_block() {
+    let block = read_block();
+    let mut tracker = ReceiptTracker::default();
+    let traces = tracker.process_block(&block);
+    // The receipts of the transactions from earlier blocks are ignored
+    assert_eq!(traces.len(), 2);
+    assert_eq!(tracker.num_pending(), 0);
+
+    let ft_transfer = &traces[0];
+    let transactions: Vec<_> = block.transactions().collect();
+    assert_eq!(ft_transfer.tx_hash, transactions[0].transaction.hash);
+    assert_eq!(ft_transfer.receipts.len(), 2);
+    assert_eq!(
+        ft_transfer.status,
+        ExecutionStatusView::SuccessValue(vec![])
+    );
+    assert_eq!(ft_transfer.gas_burnt, 3 * 2428000000000);
+    assert_eq!(ft_transfer.logs.len(), 1);
+    assert!(ft_transfer.logs[0].starts_with("EVENT_JSON:"));
+
+    // The refund is a child of the function call receipt
+    let children: Vec<_> = ft_transfer.children(ft_transfer.tx_hash).collect();
+    assert_eq!(children.len(), 1);
+    let refunds: Vec<_> = ft_transfer
+        .children(children[0].receipt.receipt_id)
+        .collect();
+    assert_eq!(refunds.len(), 1);
+    assert_eq!(refunds[0].receipt.predecessor_id.as_str(), "system");
+
+    assert_eq!(traces[1].tx_hash, transactions[1].transaction.hash);
+    assert_eq!(traces[1].receipts.len(), 1);
+}
+
+#[test]
+fn traces_across_blocks() {
+    let mut tracker = ReceiptTracker::default();
+    assert!(tracker.process_block(&transactions_block()).is_empty());
+    assert_eq!(tracker.num_pending(), 2);
+
+    let traces = tracker.process_block(&receipts_block(150000003));
+    assert_eq!(traces.len(), 2);
+    for trace in &traces {
+        assert_eq!(trace.block_height, 150000000);
+        assert_eq!(trace.last_block_height, 150000003);
+        assert!(trace
+            .receipts
+            .iter()
+            .all(|receipt| receipt.block_height == 150000003));
+    }
+    assert_eq!(tracker.num_pending(), 0);
+}
+
+#[test]
+fn incomplete_traces_are_evicted() {
+    let mut tracker = ReceiptTracker::new(10);
+    tracker.process_block(&transactions_block());
+    let mut block = receipts_block(150000011);
+    for shard in &mut block.shards {
+        shard.receipt_execution_outcomes.clear();
+    }
+    assert!(tracker.process_block(&block).is_empty());
+    assert_eq!(tracker.num_pending(), 0);
+
+    let evicted = tracker.drain_evicted();
+    assert_eq!(evicted.len(), 2);
+    assert!(evicted
+        .iter()
+        .all(|trace| trace.status == ExecutionStatusView::Unknown));
+    assert!(tracker.drain_evicted().is_empty());
+
+    // The receipts of the evicted transactions are not tracked anymore
+    assert!(tracker.process_block(&receipts_block(150000012)).is_empty());
+}
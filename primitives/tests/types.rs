@@ -0,0 +1,28 @@
+use borsh::BorshDeserialize;
+use fastnear_primitives::types::ChainId;
+
+#[test]
+fn chain_id_round_trip() {
+    for chain_id in ["mainnet", "testnet", "localnet", "forknet-1.test_2"] {
+        let chain_id: ChainId = chain_id.parse().unwrap();
+        let json = serde_json::to_string(&chain_id).unwrap();
+        assert_eq!(serde_json::from_str::<ChainId>(&json).unwrap(), chain_id);
+        let bytes = borsh::to_vec(&chain_id).unwrap();
+        assert_eq!(ChainId::try_from_slice(&bytes).unwrap(), chain_id);
+    }
+    assert_eq!(borsh::to_vec(&ChainId::Testnet).unwrap(), vec![1]);
+}
+
+#[test]
+fn invalid_chain_ids_are_rejected() {
+    for chain_id in ["", "LocalNet", "local net", "local/net"] {
+        assert!(chain_id.parse::<ChainId>().is_err(), "{:?}", chain_id);
+        assert!(serde_json::from_value::<ChainId>(chain_id.into()).is_err());
+        // The Borsh encoding of `ChainId::Custom`, bypassing the validation
+        let bytes = borsh::to_vec(&(2u8, chain_id.to_string())).unwrap();
+        assert!(ChainId::try_from_slice(&bytes).is_err(), "{:?}", chain_id);
+    }
+    let bytes = borsh::to_vec(&(2u8, "mainnet".to_string())).unwrap();
+    assert!(ChainId::try_from_slice(&bytes).is_err());
+    assert!(ChainId::try_from_slice(&[3]).is_err());
+}
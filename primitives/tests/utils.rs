@@ -0,0 +1,78 @@
+use fastnear_primitives::near_primitives::types::AccountId;
+use fastnear_primitives::utils::{
+    account_ancestors, account_kind, implicit_account_id, is_eth_implicit, is_implicit,
+    is_near_implicit, is_sub_account_of, is_system, is_top_level, AccountKind,
+};
+use near_crypto::{KeyType, PublicKey};
+use std::fmt::Write;
+
+fn account(account_id: &str) -> AccountId {
+    account_id.parse().unwrap()
+}
+
+const NEAR_IMPLICIT: &str = "98793cd91a3f870fb126f66285808c7e094afcfc4eda8a970f6648cdf0dbd6de";
+const ETH_IMPLICIT: &str = "0xb794f5ea0ba39494ce839613fffba74279579268";
+
+#[test]
+fn classification() {
+    let cases = [
+        (NEAR_IMPLICIT, AccountKind::NearImplicit),
+        (ETH_IMPLICIT, AccountKind::EthImplicit),
+        ("system", AccountKind::System),
+        ("near", AccountKind::TopLevel),
+        ("alice.near", AccountKind::SubAccount),
+        ("app.alice.near", AccountKind::SubAccount),
+        // Not 64 characters
+        ("98793cd91a3f870fb126f66285808c7e", AccountKind::TopLevel),
+    ];
+    for (account_id, kind) in cases {
+        assert_eq!(account_kind(&account(account_id)), kind, "{}", account_id);
+    }
+
+    assert!(is_near_implicit(&account(NEAR_IMPLICIT)));
+    assert!(is_eth_implicit(&account(ETH_IMPLICIT)));
+    assert!(is_implicit(&account(ETH_IMPLICIT)));
+    assert!(!is_implicit(&account("near")));
+    assert!(is_top_level(&account("near")));
+    assert!(!is_top_level(&account(NEAR_IMPLICIT)));
+    assert!(!is_top_level(&account("system")));
+    assert!(is_system(&account("system")));
+
+    assert!(is_sub_account_of(&account("alice.near"), &account("near")));
+    assert!(!is_sub_account_of(
+        &account("app.alice.near"),
+        &account("near")
+    ));
+    assert!(!is_sub_account_of(&account("alicenear"), &account("near")));
+}
+
+#[test]
+fn ancestors() {
+    let account_id = account("a.b.near");
+    let ancestors: Vec<_> = account_ancestors(&account_id)
+        .map(|ancestor| ancestor.as_str())
+        .collect();
+    assert_eq!(ancestors, vec!["b.near", "near"]);
+    assert_eq!(account_ancestors(&account("near")).count(), 0);
+}
+
+#[test]
+fn implicit_accounts_from_keys() {
+    let public_key: PublicKey = "ed25519:DcA2MzgpJbrUATQLLceocVckhhAqrkingax4oJ9kZ847"
+        .parse()
+        .unwrap();
+    let account_id = implicit_account_id(&public_key);
+    assert_eq!(account_kind(&account_id), AccountKind::NearImplicit);
+    assert_eq!(account_id.as_str(), hex(public_key.key_data()));
+
+    let public_key = PublicKey::from_seed(KeyType::SECP256K1, "test");
+    let account_id = implicit_account_id(&public_key);
+    assert_eq!(account_kind(&account_id), AccountKind::EthImplicit);
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut hex, byte| {
+        write!(hex, "{:02x}", byte).unwrap();
+        hex
+    })
+}